@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS user_lang
+    (
+        user_id TEXT NOT NULL PRIMARY KEY,
+        lang TEXT NOT NULL
+    );
@@ -0,0 +1 @@
+ALTER TABLE delivery_queue ADD COLUMN claimed_until INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- 0_setup.sql inserted rows for the tests, which now live in src/query/fixtures/seed.sql
+DELETE FROM tag_filter WHERE tag_id IN
+    (SELECT tag_id FROM user_folder WHERE (tag_name, owner_id) IN
+        (VALUES ('test_a', 'U00001'), ('test_b', 'U00001'), ('test_pub', 'public'), ('test_dist', 'U0987654'), ('test_target', 'U00001'), ('test_target_bot', 'U00001')));
+DELETE FROM dist WHERE tag_id IN
+    (SELECT tag_id FROM user_folder WHERE (tag_name, owner_id) IN
+        (VALUES ('test_a', 'U00001'), ('test_b', 'U00001'), ('test_pub', 'public'), ('test_dist', 'U0987654'), ('test_target', 'U00001'), ('test_target_bot', 'U00001')));
+DELETE FROM channel_list WHERE tag_id IN
+    (SELECT tag_id FROM user_folder WHERE (tag_name, owner_id) IN
+        (VALUES ('test_a', 'U00001'), ('test_b', 'U00001'), ('test_pub', 'public'), ('test_dist', 'U0987654'), ('test_target', 'U00001'), ('test_target_bot', 'U00001')));
+DELETE FROM user_folder WHERE (tag_name, owner_id) IN
+    (VALUES ('test_a', 'U00001'), ('test_b', 'U00001'), ('test_pub', 'public'), ('test_dist', 'U0987654'), ('test_target', 'U00001'), ('test_target_bot', 'U00001'));
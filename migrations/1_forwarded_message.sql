@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS forwarded_message
+    (
+        source_channel_id TEXT NOT NULL,
+        source_ts TEXT NOT NULL,
+        dist_channel_id TEXT NOT NULL,
+        forwarded_ts TEXT NOT NULL,
+        PRIMARY KEY(source_channel_id, source_ts, dist_channel_id)
+    );
@@ -0,0 +1 @@
+ALTER TABLE user_folder ADD COLUMN thread_mode TEXT NOT NULL DEFAULT 'mirror';
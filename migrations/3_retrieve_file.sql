@@ -0,0 +1 @@
+ALTER TABLE user_folder ADD COLUMN file BOOLEAN NOT NULL DEFAULT true;
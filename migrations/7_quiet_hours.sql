@@ -0,0 +1,2 @@
+ALTER TABLE dist_schedule ADD COLUMN quiet_start INTEGER;
+ALTER TABLE dist_schedule ADD COLUMN quiet_end INTEGER;
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS user_folder 
+    (
+        tag_id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY, 
+        tag_name TEXT NOT NULL,
+        owner_id TEXT NOT NULL,
+        bot BOOLEAN NOT NULL DEFAULT false,
+        valid_count BIGINT NOT NULL DEFAULT 0,
+        UNIQUE (tag_name, owner_id)
+    );
+CREATE TABLE IF NOT EXISTS dist 
+    (
+        user_id TEXT NOT NULL,
+        tag_id BIGINT NOT NULL, 
+        dist_channel_id TEXT NOT NULL,
+        PRIMARY KEY(user_id, tag_id, dist_channel_id),
+        FOREIGN KEY (tag_id) REFERENCES user_folder(tag_id) ON DELETE CASCADE
+    );
+CREATE TABLE IF NOT EXISTS channel_list
+    (
+        tag_id BIGINT NOT NULL, 
+        channel_id TEXT NOT NULL,
+        PRIMARY KEY(tag_id, channel_id),
+        FOREIGN KEY (tag_id) REFERENCES user_folder(tag_id) ON DELETE CASCADE
+    );
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS audit_log
+    (
+        audit_id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        actor_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        tag_name TEXT,
+        owner_id TEXT,
+        channels TEXT NOT NULL,
+        detail TEXT NOT NULL,
+        created_at BIGINT NOT NULL
+    );
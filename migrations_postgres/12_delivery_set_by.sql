@@ -0,0 +1 @@
+ALTER TABLE dist_schedule ADD COLUMN set_by TEXT;
@@ -0,0 +1 @@
+ALTER TABLE delivery_queue ADD COLUMN claimed_until BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+ALTER TABLE audit_log ADD COLUMN tag_id BIGINT;
+-- a rename is recorded under the old name, and the tag is found by the new one in detail
+UPDATE audit_log SET tag_id =
+    (SELECT tag_id FROM user_folder
+    WHERE user_folder.owner_id = audit_log.owner_id
+    AND user_folder.tag_name = CASE WHEN audit_log.action = 'rename' THEN audit_log.detail ELSE audit_log.tag_name END);
@@ -0,0 +1,5 @@
+-- times of the day are read in an IANA time zone such as Asia/Tokyo, or a fixed offset kept as UTC+09:00
+ALTER TABLE dist_schedule ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC+00:00';
+UPDATE dist_schedule SET time_zone = 'UTC' || CASE WHEN utc_offset < 0 THEN '-' ELSE '+' END
+    || lpad((abs(utc_offset) / 60)::text, 2, '0') || ':' || lpad((abs(utc_offset) % 60)::text, 2, '0');
+ALTER TABLE dist_schedule DROP COLUMN utc_offset;
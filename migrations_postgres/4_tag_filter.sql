@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS tag_filter
+    (
+        tag_id BIGINT NOT NULL,
+        kind TEXT NOT NULL,
+        pattern TEXT NOT NULL,
+        is_regex BOOLEAN NOT NULL DEFAULT false,
+        PRIMARY KEY(tag_id, kind, pattern, is_regex),
+        FOREIGN KEY (tag_id) REFERENCES user_folder(tag_id) ON DELETE CASCADE
+    );
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS sender_rule
+    (
+        tag_id BIGINT NOT NULL,
+        kind TEXT NOT NULL,
+        sender_id TEXT NOT NULL,
+        PRIMARY KEY(tag_id, kind, sender_id),
+        FOREIGN KEY (tag_id) REFERENCES user_folder(tag_id) ON DELETE CASCADE
+    );
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS dist_schedule
+    (
+        dist_channel_id TEXT NOT NULL PRIMARY KEY,
+        mode TEXT NOT NULL DEFAULT 'immediate',
+        mode_minutes BIGINT NOT NULL DEFAULT 0,
+        utc_offset BIGINT NOT NULL DEFAULT 0,
+        last_delivered_at BIGINT NOT NULL DEFAULT 0
+    );
+CREATE TABLE IF NOT EXISTS delivery_queue
+    (
+        queue_id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        dist_channel_id TEXT NOT NULL,
+        source_channel_id TEXT NOT NULL,
+        source_ts TEXT NOT NULL,
+        sender_name TEXT NOT NULL,
+        icon_url TEXT NOT NULL,
+        text TEXT NOT NULL,
+        queued_at BIGINT NOT NULL
+    );
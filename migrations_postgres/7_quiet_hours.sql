@@ -0,0 +1,2 @@
+ALTER TABLE dist_schedule ADD COLUMN quiet_start BIGINT;
+ALTER TABLE dist_schedule ADD COLUMN quiet_end BIGINT;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS tag_member
+    (
+        tag_id BIGINT NOT NULL,
+        member_id TEXT NOT NULL,
+        role TEXT NOT NULL,
+        PRIMARY KEY(tag_id, member_id),
+        FOREIGN KEY (tag_id) REFERENCES user_folder(tag_id) ON DELETE CASCADE
+    );
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS workspace_admin
+    (
+        user_id TEXT NOT NULL PRIMARY KEY
+    );
@@ -0,0 +1,41 @@
+use std::env::Args;
+
+use anyhow::Context;
+
+use crate::{
+    config_file::{ConfigFile, Format},
+    query, storage,
+};
+
+// `slack-some-things export [--yaml]` prints every tag to stdout
+pub async fn export(mut args: Args) -> anyhow::Result<()> {
+    let format = if args.any(|arg| arg == "--yaml") {
+        Format::Yaml
+    } else {
+        Format::Json
+    };
+    let storage = storage::connect(&query::db_url()?).await?;
+    let configs = storage.tag_config_list().await?;
+    println!("{}", ConfigFile::from_configs(&configs).to_text(format)?);
+    Ok(())
+}
+
+// `slack-some-things import [file] [--dry-run]` applies an exported file, printing the changes
+pub async fn import(args: Args) -> anyhow::Result<()> {
+    let (flags, paths): (Vec<String>, Vec<String>) = args.partition(|arg| arg.starts_with("--"));
+    let dry_run = flags.iter().any(|flag| flag == "--dry-run");
+    let path = paths.first().context("argument error")?;
+
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read {path}"))?;
+    let file = ConfigFile::parse(&text)?;
+
+    let storage = storage::connect(&query::db_url()?).await?;
+    let plan = file.plan(&storage.tag_config_list().await?)?;
+    print!("{plan}");
+    if !dry_run {
+        plan.apply(storage.as_ref()).await?;
+    }
+    Ok(())
+}
@@ -1,16 +1,21 @@
 use std::sync::Arc;
 
-use anyhow::Context;
 use slack_morphism::{
     prelude::{
         SlackClientEventsUserState, SlackCommandEvent, SlackCommandEventResponse, SlackHyperClient,
     },
-    SlackMessageContent,
+    SlackChannelId, SlackMessageContent, SlackTriggerId, SlackUserId,
 };
 
 use crate::{
-    commands::{self, create_channel, operate, set_target_tags},
-    post_message::MessagePoster,
+    command_parser::{Command, DistCommand, SettingCommand, TagCommand},
+    commands::{
+        self, admin, audit, create_channel, digest, export, filter, lang, modal, operate, quiet,
+        sender, set_target_tags, share,
+    },
+    response,
+    state::{self, AppState},
+    storage::Storage,
 };
 
 // retrurn response to slack early, to avoid timeout error
@@ -30,14 +35,12 @@ pub async fn handler_catch_error(
     cli: Arc<SlackHyperClient>,
     state: SlackClientEventsUserState,
 ) -> anyhow::Result<()> {
-    match command_event_handler(event.clone(), cli.clone(), state).await {
-        Ok(_) => Ok(()),
+    let app_state = state::app_state(&state).await?;
+    match command_event_handler(event.clone(), cli.clone(), &app_state).await {
+        Ok(()) => Ok(()),
         Err(err) => {
-            let err_message = format!("{err:#?}");
-            MessagePoster::new(event.channel_id, err_message, cli)
-                .post_ephemeral(event.user_id)
-                .await?;
-            Ok(())
+            let storage = app_state.storage.as_ref();
+            response::post_error(cli, storage, event.channel_id, event.user_id, &err).await
         }
     }
 }
@@ -45,58 +48,318 @@ pub async fn handler_catch_error(
 pub async fn command_event_handler(
     event: SlackCommandEvent,
     cli: Arc<SlackHyperClient>,
-    _state: SlackClientEventsUserState,
+    app_state: &AppState,
 ) -> anyhow::Result<()> {
     let channel_id_command = event.channel_id.clone();
     let user_id_command = event.user_id;
 
-    let full = event.text.clone().unwrap_or(String::new());
-    let mut args_iter = full.split_whitespace();
-    let first_arg = args_iter.next().context("argument error")?;
+    let full = event.text.clone().unwrap_or_default();
+    let command = Command::parse(&full)?;
+
+    let result = dispatch_command(
+        &command,
+        cli,
+        app_state.storage.as_ref(),
+        channel_id_command,
+        user_id_command,
+        event.trigger_id,
+    )
+    .await;
+
+    // the routes are reloaded even when the command failed halfway
+    if command.changes_routing() {
+        app_state.routing.invalidate().await;
+    }
+
+    result
+}
 
-    match first_arg {
-        "add" => operate::add_command(cli, channel_id_command, user_id_command, args_iter).await?,
-        "delete" => {
-            operate::delete_command(cli, channel_id_command, user_id_command, args_iter).await?;
+async fn dispatch_command(
+    command: &Command,
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    trigger_id: SlackTriggerId,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Tag(command) => {
+            dispatch_tag_command(command, cli, storage, channel_id_command, user_id_command)
+                .await?;
         }
-        "set" => {
-            set_target_tags::set_command(cli, channel_id_command, user_id_command, args_iter)
+        Command::Setting(command) => {
+            dispatch_setting_command(command, cli, storage, channel_id_command, user_id_command)
                 .await?;
         }
-        "unset" => {
-            set_target_tags::unset_command(cli, channel_id_command, user_id_command, args_iter)
+        Command::Dist(command) => {
+            dispatch_dist_command(command, cli, storage, channel_id_command, user_id_command)
                 .await?;
         }
-        "create_channel" => {
-            create_channel::create_command(
-                cli.clone(),
+        Command::Export(format) => {
+            export::export_command(cli, storage, channel_id_command, user_id_command, *format)
+                .await?;
+        }
+        Command::Audit {
+            tag,
+            channel,
+            limit,
+        } => {
+            audit::audit_command(
+                cli,
+                storage,
                 channel_id_command,
-                user_id_command.clone(),
-                args_iter,
+                user_id_command,
+                tag.as_ref(),
+                channel.clone(),
+                *limit,
             )
             .await?;
         }
-        "retrieve_bot" => {
-            operate::retreieve_bot_command(cli, channel_id_command, user_id_command, args_iter)
+        Command::Admin(op) => {
+            admin::admin_command(cli, storage, channel_id_command, user_id_command, op).await?;
+        }
+        Command::TagList => {
+            commands::tag_list_command(cli, storage, channel_id_command, user_id_command).await?;
+        }
+        Command::ChList(tag) => {
+            commands::ch_list_command(cli, storage, channel_id_command, user_id_command, tag)
+                .await?;
+        }
+        Command::TargetList => {
+            commands::target_list_command(cli, storage, channel_id_command, user_id_command)
                 .await?;
         }
+        Command::Lang(op) => {
+            lang::lang_command(cli, storage, channel_id_command, user_id_command, op).await?;
+        }
+        Command::Help(topic) => {
+            commands::help::help(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                topic.as_deref(),
+            )
+            .await?;
+        }
+        Command::Modal => {
+            let context = modal::ModalContext {
+                channel_id: channel_id_command,
+                selected: None,
+                from_home: false,
+            };
+            modal::open_modal(cli, storage, trigger_id, user_id_command, &context).await?;
+        }
+        Command::Undefined(_) => {
+            commands::undefined_command(cli, storage, channel_id_command, user_id_command).await?;
+        }
+    }
 
-        "tag_list" => {
-            commands::tag_list_command(cli, channel_id_command, user_id_command).await?;
+    Ok(())
+}
+
+// Commands that create, change the channels of, or remove a tag
+async fn dispatch_tag_command(
+    command: &TagCommand,
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+) -> anyhow::Result<()> {
+    match command {
+        TagCommand::Add { tag, channels } => {
+            operate::add_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                channels,
+            )
+            .await?;
         }
-        "ch_list" => {
-            commands::ch_list_command(cli, channel_id_command, user_id_command, args_iter).await?;
+        TagCommand::Delete { tag, channels } => {
+            operate::delete_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                channels,
+            )
+            .await?;
         }
-        "target_list" => {
-            commands::target_list_command(cli, channel_id_command, user_id_command).await?;
+        TagCommand::DropTag { tag, confirmed } => {
+            operate::drop_tag_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                *confirmed,
+            )
+            .await?;
         }
-        "help" => {
-            commands::help::help(cli, channel_id_command, user_id_command, args_iter).await?;
+        TagCommand::Rename { tag, new_name } => {
+            operate::rename_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                new_name,
+            )
+            .await?;
+        }
+        TagCommand::Copy { tag, new_name } => {
+            operate::copy_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                new_name,
+            )
+            .await?;
         }
-        _ => {
-            commands::undefined_command(cli, channel_id_command, user_id_command).await?;
+        TagCommand::Merge { tag, into } => {
+            operate::merge_command(cli, storage, channel_id_command, user_id_command, tag, into)
+                .await?;
         }
-    };
+    }
+
+    Ok(())
+}
+
+// Commands that change how a tag collects messages, or who may edit it
+async fn dispatch_setting_command(
+    command: &SettingCommand,
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+) -> anyhow::Result<()> {
+    match command {
+        SettingCommand::RetrieveBot { tag, retrieve } => {
+            operate::retreieve_bot_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                *retrieve,
+            )
+            .await?;
+        }
+        SettingCommand::RetrieveFile { tag, retrieve } => {
+            operate::retrieve_file_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                *retrieve,
+            )
+            .await?;
+        }
+        SettingCommand::Thread { tag, mode } => {
+            operate::thread_mode_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tag,
+                *mode,
+            )
+            .await?;
+        }
+        SettingCommand::Filter { tag, op } => {
+            filter::filter_command(cli, storage, channel_id_command, user_id_command, tag, op)
+                .await?;
+        }
+        SettingCommand::Sender { tag, op } => {
+            sender::sender_command(cli, storage, channel_id_command, user_id_command, tag, op)
+                .await?;
+        }
+        SettingCommand::Share { tag, op } => {
+            share::share_command(cli, storage, channel_id_command, user_id_command, tag, op)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Commands that change what the channel receives
+async fn dispatch_dist_command(
+    command: &DistCommand,
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+) -> anyhow::Result<()> {
+    match command {
+        DistCommand::Set { tags, is_public } => {
+            set_target_tags::set_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tags,
+                *is_public,
+            )
+            .await?;
+        }
+        DistCommand::Unset { tags, is_public } => {
+            set_target_tags::unset_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                tags,
+                *is_public,
+            )
+            .await?;
+        }
+        DistCommand::CreateChannel {
+            name,
+            tags,
+            is_public,
+        } => {
+            create_channel::create_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                name,
+                tags,
+                *is_public,
+            )
+            .await?;
+        }
+        DistCommand::Digest { mode, time_zone } => {
+            digest::digest_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                *mode,
+                time_zone.as_ref(),
+            )
+            .await?;
+        }
+        DistCommand::Quiet { hours, time_zone } => {
+            quiet::quiet_command(
+                cli,
+                storage,
+                channel_id_command,
+                user_id_command,
+                *hours,
+                time_zone.as_ref(),
+            )
+            .await?;
+        }
+    }
 
     Ok(())
 }
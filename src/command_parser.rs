@@ -0,0 +1,1231 @@
+use std::{collections::VecDeque, fmt};
+
+use slack_morphism::{SlackChannelId, SlackUserId};
+
+use crate::{
+    config_file::Format,
+    query::{
+        delivery::{self, DeliveryMode, TimeZone},
+        sender_rule::{RuleKind, SenderRule},
+        tag_filter::{FilterKind, TagFilter},
+        tag_member::TagRole,
+        thread_mode::ThreadMode,
+        user_lang::Lang,
+    },
+    utils,
+};
+
+// Options that take the next word as their value
+const VALUE_FLAGS: [&str; 1] = ["--limit"];
+
+// A tag given to a command; `--public` picks the public tag of the name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagArg {
+    pub name: String,
+    pub is_public: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterOp {
+    Add(Vec<TagFilter>),
+    Remove(Vec<TagFilter>),
+    List,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SenderOp {
+    Add(Vec<SenderRule>),
+    Remove(Vec<SenderRule>),
+    List,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShareOp {
+    List,
+    // a user id or a user group id, with the role given to it
+    Share(String, TagRole),
+    Unshare(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminOp {
+    Add(SlackUserId),
+    Remove(SlackUserId),
+    List,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LangOp {
+    Show,
+    Set(Lang),
+    // back to the language of the user's Slack locale
+    Auto,
+}
+
+// Commands that create, change the channels of, or remove a tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagCommand {
+    Add {
+        tag: TagArg,
+        channels: Vec<SlackChannelId>,
+    },
+    Delete {
+        tag: TagArg,
+        channels: Vec<SlackChannelId>,
+    },
+    DropTag {
+        tag: TagArg,
+        confirmed: bool,
+    },
+    Rename {
+        tag: TagArg,
+        new_name: String,
+    },
+    Copy {
+        tag: TagArg,
+        new_name: String,
+    },
+    Merge {
+        tag: TagArg,
+        into: String,
+    },
+}
+
+// Commands that change how a tag collects messages, or who may edit it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingCommand {
+    RetrieveBot { tag: TagArg, retrieve: bool },
+    RetrieveFile { tag: TagArg, retrieve: bool },
+    Thread { tag: TagArg, mode: ThreadMode },
+    Filter { tag: TagArg, op: FilterOp },
+    Sender { tag: TagArg, op: SenderOp },
+    Share { tag: TagArg, op: ShareOp },
+}
+
+// Commands that change what the channel receives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DistCommand {
+    Set {
+        tags: Vec<String>,
+        is_public: bool,
+    },
+    Unset {
+        tags: Vec<String>,
+        is_public: bool,
+    },
+    CreateChannel {
+        name: String,
+        tags: Vec<String>,
+        is_public: bool,
+    },
+    Digest {
+        mode: DeliveryMode,
+        time_zone: Option<TimeZone>,
+    },
+    Quiet {
+        hours: Option<(i64, i64)>,
+        time_zone: Option<TimeZone>,
+    },
+}
+
+// A slash command with its arguments checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Tag(TagCommand),
+    Setting(SettingCommand),
+    Dist(DistCommand),
+    Admin(AdminOp),
+    Audit {
+        tag: Option<TagArg>,
+        channel: Option<SlackChannelId>,
+        limit: Option<i64>,
+    },
+    Export(Format),
+    ChList(TagArg),
+    TagList,
+    TargetList,
+    Lang(LangOp),
+    Help(Option<String>),
+    Modal,
+    Undefined(String),
+}
+
+impl Command {
+    // Commands that change which messages are forwarded where, or when
+    pub fn changes_routing(&self) -> bool {
+        match self {
+            Command::Tag(command) => match command {
+                TagCommand::Add { .. }
+                | TagCommand::Delete { .. }
+                | TagCommand::DropTag { .. }
+                | TagCommand::Merge { .. } => true,
+                TagCommand::Rename { .. } | TagCommand::Copy { .. } => false,
+            },
+            Command::Setting(command) => match command {
+                SettingCommand::RetrieveBot { .. }
+                | SettingCommand::RetrieveFile { .. }
+                | SettingCommand::Thread { .. }
+                | SettingCommand::Filter { .. }
+                | SettingCommand::Sender { .. } => true,
+                SettingCommand::Share { .. } => false,
+            },
+            // the delivery schedules are cached with the routes
+            Command::Dist(_) => true,
+            Command::Admin(_)
+            | Command::Audit { .. }
+            | Command::Export(_)
+            | Command::ChList(_)
+            | Command::TagList
+            | Command::TargetList
+            | Command::Lang(_)
+            | Command::Help(_)
+            | Command::Modal
+            | Command::Undefined(_) => false,
+        }
+    }
+
+    // Flags may be anywhere in the line, and quotes keep spaces and `--` in a word
+    pub fn parse(line: &str) -> Result<Self, ParseError> {
+        let mut tokens = VecDeque::from(tokenize(line)?);
+        // a bare `/channel_bugyo` opens the modal
+        let Some(name) = tokens.pop_front().map(|token| token.text) else {
+            return Ok(Command::Modal);
+        };
+        let Some(usage) = usage(&name) else {
+            return Ok(Command::Undefined(name));
+        };
+        let mut args = Args::new(usage, tokens)?;
+
+        let command = match name.as_str() {
+            "add" => {
+                let tag = args.tag()?;
+                let channels = args.channels()?;
+                if channels.is_empty() {
+                    return Err(args.missing("#channel"));
+                }
+                Command::Tag(TagCommand::Add { tag, channels })
+            }
+            "delete" => {
+                let tag = args.tag()?;
+                let channels = args.channels()?;
+                if channels.is_empty() {
+                    return Err(ParseError::NoChannelsToDelete);
+                }
+                Command::Tag(TagCommand::Delete { tag, channels })
+            }
+            "drop_tag" => Command::Tag(TagCommand::DropTag {
+                confirmed: args.flag("--confirm"),
+                tag: args.tag()?,
+            }),
+            "rename" => Command::Tag(TagCommand::Rename {
+                tag: args.tag()?,
+                new_name: args.name("new_tag")?,
+            }),
+            "copy" => Command::Tag(TagCommand::Copy {
+                tag: args.tag()?,
+                new_name: args.name("new_tag")?,
+            }),
+            "merge" => Command::Tag(TagCommand::Merge {
+                tag: args.tag()?,
+                into: args.name("into_tag")?,
+            }),
+            "retrieve_bot" => Command::Setting(SettingCommand::RetrieveBot {
+                tag: args.tag()?,
+                retrieve: args.boolean("retrieve")?,
+            }),
+            "retrieve_file" => Command::Setting(SettingCommand::RetrieveFile {
+                tag: args.tag()?,
+                retrieve: args.boolean("retrieve")?,
+            }),
+            "thread" => parse_thread(&mut args)?,
+            "filter" => parse_filter(&mut args)?,
+            "sender" => parse_sender(&mut args)?,
+            "share" => parse_share(&mut args)?,
+            "admin" => parse_admin(&mut args)?,
+            "audit" => parse_audit(&mut args)?,
+            "export" => parse_export(&mut args)?,
+            "ch_list" => Command::ChList(args.tag()?),
+            "tag_list" => Command::TagList,
+            "set" => Command::Dist(DistCommand::Set {
+                is_public: args.flag("--public"),
+                tags: args.tags()?,
+            }),
+            "unset" => Command::Dist(DistCommand::Unset {
+                is_public: args.flag("--public"),
+                tags: args.tags()?,
+            }),
+            "create_channel" => Command::Dist(DistCommand::CreateChannel {
+                is_public: args.flag("--public"),
+                name: args.name("channel_name")?,
+                tags: args.tags()?,
+            }),
+            "digest" => parse_digest(&mut args)?,
+            "quiet" => parse_quiet(&mut args)?,
+            "target_list" => Command::TargetList,
+            "lang" => parse_lang(&mut args)?,
+            "help" => Command::Help(args.optional()),
+            _ => Command::Undefined(name.clone()),
+        };
+        args.finish()?;
+        Ok(command)
+    }
+}
+
+// The usage shown when an argument is missing, None for unknown commands
+fn usage(command: &str) -> Option<&'static str> {
+    let usage = match command {
+        "add" => "add [--public] <tag> <#channel>...",
+        "delete" => "delete [--public] <tag> <#channel>...",
+        "drop_tag" => "drop_tag [--public] <tag> [--confirm]",
+        "rename" => "rename [--public] <tag> <new_tag>",
+        "copy" => "copy [--public] <tag> <new_tag>",
+        "merge" => "merge [--public] <tag> <into_tag>",
+        "retrieve_bot" => "retrieve_bot [--public] <tag> <true|false>",
+        "retrieve_file" => "retrieve_file [--public] <tag> <true|false>",
+        "thread" => "thread [--public] <tag> <mirror|drop|root>",
+        "filter" => "filter <add|remove|list> [--public] <tag> [<include|exclude> <pattern>...]",
+        "sender" => "sender <add|remove|list> [--public] <tag> [<allow|deny> <@user>...]",
+        "share" => "share [--public] <tag> [<@member> <editor|viewer|off>]",
+        "admin" => "admin <add|remove|list> [<@user>]",
+        "audit" => "audit [<tag>] [<#channel>] [--public] [--limit N]",
+        "export" => "export [--json|--yaml]",
+        "ch_list" => "ch_list [--public] <tag>",
+        "tag_list" => "tag_list",
+        "set" => "set [--public] <tag>...",
+        "unset" => "unset [--public] <tag>...",
+        "create_channel" => "create_channel [--public] <channel_name> <tag>...",
+        "digest" => "digest <every N|daily HH:MM [Asia/Tokyo]|off>",
+        "quiet" => "quiet <HH:MM HH:MM [Asia/Tokyo]|off>",
+        "target_list" => "target_list",
+        "lang" => "lang [ja|en|auto]",
+        "help" => "help [command]",
+        _ => return None,
+    };
+    Some(usage)
+}
+
+// Why a command line could not be read. These are shown to users, so they are translated
+// through the catalog, and the English here is what is logged.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("a quote is not closed")]
+    UnclosedQuote,
+    #[error("{0} needs a value")]
+    NeedsValue(String),
+    #[error("{name} is missing\nusage: `/channel_bugyo {usage}`")]
+    Missing { name: String, usage: &'static str },
+    #[error("{0} should not be empty")]
+    Empty(String),
+    #[error("unknown option {flag}\nusage: `/channel_bugyo {usage}`")]
+    UnknownOption { flag: String, usage: &'static str },
+    #[error("unexpected argument `{arg}`\nusage: `/channel_bugyo {usage}`")]
+    UnexpectedArgument { arg: String, usage: &'static str },
+    #[error("invalid {name} `{value}`: {expected}")]
+    Invalid {
+        name: String,
+        value: String,
+        expected: Expected,
+    },
+    #[error("give the channels to delete, or use drop_tag to delete the tag itself")]
+    NoChannelsToDelete,
+    #[error("give only one of --json and --yaml")]
+    BothFormats,
+    #[error("start and end should be different")]
+    SameStartAndEnd,
+}
+
+// What an invalid argument should have been
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    // one of the words
+    OneOf(&'static [&'static str]),
+    // a value written like the example
+    Like(&'static str),
+    PositiveNumber,
+    ChannelMention,
+    UserMention,
+    MemberMention,
+    Sender,
+    // a regex that does not compile, with the reason given by the regex crate
+    Regex(String),
+}
+
+// "a, b or c"
+fn or_list(words: &[&str]) -> String {
+    match words {
+        [] => String::new(),
+        [word] => (*word).to_string(),
+        [init @ .., last] => format!("{} or {last}", init.join(", ")),
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::OneOf(words) => write!(f, "it should be {}", or_list(words)),
+            Expected::Like(example) => write!(f, "it should be like {example}"),
+            Expected::PositiveNumber => write!(f, "it should be a positive number"),
+            Expected::ChannelMention => write!(f, "mention the channel like #general"),
+            Expected::UserMention => write!(f, "mention the user like @someone"),
+            Expected::MemberMention => write!(f, "mention a user or a user group"),
+            Expected::Sender => write!(f, "mention the user, or give a user or bot id"),
+            Expected::Regex(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+// An error naming the argument and the value given to it
+fn bad(name: &str, value: &str, expected: Expected) -> ParseError {
+    ParseError::Invalid {
+        name: name.to_string(),
+        value: value.to_string(),
+        expected,
+    }
+}
+
+// A word of the line; quoted words are never read as flags
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+// Slack may turn straight quotes into curly ones, so both are accepted
+fn tokenize(line: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut in_quote = false;
+    for c in line.chars() {
+        match c {
+            '"' | '“' | '”' => {
+                in_quote = !in_quote;
+                current.get_or_insert_with(Token::default).quoted = true;
+            }
+            c if c.is_whitespace() && !in_quote => tokens.extend(current.take()),
+            c => current.get_or_insert_with(Token::default).text.push(c),
+        }
+    }
+    if in_quote {
+        return Err(ParseError::UnclosedQuote);
+    }
+    tokens.extend(current);
+    Ok(tokens)
+}
+
+// The words after the command name, with the flags taken out
+struct Args {
+    usage: &'static str,
+    positional: VecDeque<Token>,
+    flags: Vec<String>,
+    values: Vec<(String, String)>,
+    is_public: Option<bool>,
+}
+
+impl Args {
+    fn new(usage: &'static str, tokens: VecDeque<Token>) -> Result<Self, ParseError> {
+        let mut args = Args {
+            usage,
+            positional: VecDeque::new(),
+            flags: Vec::new(),
+            values: Vec::new(),
+            is_public: None,
+        };
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            if token.quoted || !token.text.starts_with("--") {
+                args.positional.push_back(token);
+            } else if VALUE_FLAGS.contains(&token.text.as_str()) {
+                let value = tokens
+                    .next()
+                    .ok_or_else(|| ParseError::NeedsValue(token.text.clone()))?;
+                args.values.push((token.text, value.text));
+            } else {
+                args.flags.push(token.text);
+            }
+        }
+        Ok(args)
+    }
+
+    fn missing(&self, name: &str) -> ParseError {
+        ParseError::Missing {
+            name: name.to_string(),
+            usage: self.usage,
+        }
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        let len = self.flags.len();
+        self.flags.retain(|flag| flag != name);
+        self.flags.len() != len
+    }
+
+    fn value(&mut self, name: &str) -> Option<String> {
+        let index = self.values.iter().position(|(flag, _)| flag == name)?;
+        Some(self.values.remove(index).1)
+    }
+
+    fn optional(&mut self) -> Option<String> {
+        self.positional.pop_front().map(|token| token.text)
+    }
+
+    fn required(&mut self, name: &str) -> Result<String, ParseError> {
+        self.optional().ok_or_else(|| self.missing(name))
+    }
+
+    fn rest(&mut self) -> Vec<String> {
+        self.positional.drain(..).map(|token| token.text).collect()
+    }
+
+    fn name(&mut self, name: &str) -> Result<String, ParseError> {
+        let value = self.required(name)?;
+        if value.trim().is_empty() {
+            return Err(ParseError::Empty(name.to_string()));
+        }
+        Ok(value)
+    }
+
+    // `--public` is read once, whichever tag of the command comes first
+    fn public(&mut self) -> bool {
+        let flag = self.flag("--public");
+        *self.is_public.get_or_insert(flag)
+    }
+
+    fn tag(&mut self) -> Result<TagArg, ParseError> {
+        let is_public = self.public();
+        Ok(TagArg {
+            name: self.name("tag")?,
+            is_public,
+        })
+    }
+
+    // the rest of the words, at least one
+    fn tags(&mut self) -> Result<Vec<String>, ParseError> {
+        let tags = self.rest();
+        if tags.is_empty() {
+            return Err(self.missing("tag"));
+        }
+        Ok(tags)
+    }
+
+    fn boolean(&mut self, name: &str) -> Result<bool, ParseError> {
+        let value = self.required(name)?;
+        match value.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(bad(name, &value, Expected::OneOf(&["true", "false"]))),
+        }
+    }
+
+    fn channels(&mut self) -> Result<Vec<SlackChannelId>, ParseError> {
+        self.rest()
+            .iter()
+            .map(|channel| parse_channel(channel))
+            .collect()
+    }
+
+    fn finish(self) -> Result<(), ParseError> {
+        let unknown = self
+            .flags
+            .iter()
+            .chain(self.values.iter().map(|(flag, _)| flag))
+            .next();
+        if let Some(flag) = unknown {
+            return Err(ParseError::UnknownOption {
+                flag: flag.clone(),
+                usage: self.usage,
+            });
+        }
+        if let Some(token) = self.positional.front() {
+            return Err(ParseError::UnexpectedArgument {
+                arg: token.text.clone(),
+                usage: self.usage,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn parse_channel(channel: &str) -> Result<SlackChannelId, ParseError> {
+    utils::channel_preprocess(channel)
+        .map_err(|_| bad("channel", channel, Expected::ChannelMention))
+}
+
+fn parse_user(user: &str) -> Result<SlackUserId, ParseError> {
+    match utils::member_preprocess(user) {
+        Ok(id) if !id.starts_with('S') => Ok(SlackUserId::new(id)),
+        _ => Err(bad("user", user, Expected::UserMention)),
+    }
+}
+
+fn parse_thread(args: &mut Args) -> Result<Command, ParseError> {
+    let tag = args.tag()?;
+    let mode = args.required("mode")?;
+    let mode = mode
+        .parse::<ThreadMode>()
+        .map_err(|_| bad("mode", &mode, Expected::OneOf(&["mirror", "drop", "root"])))?;
+    Ok(Command::Setting(SettingCommand::Thread { tag, mode }))
+}
+
+fn parse_export(args: &mut Args) -> Result<Command, ParseError> {
+    let format = match (args.flag("--json"), args.flag("--yaml")) {
+        (_, false) => Format::Json,
+        (false, true) => Format::Yaml,
+        (true, true) => return Err(ParseError::BothFormats),
+    };
+    Ok(Command::Export(format))
+}
+
+// add and remove take the kind and at least one value, list takes nothing more
+fn parse_operation(args: &mut Args) -> Result<String, ParseError> {
+    let operation = args.required("operation")?;
+    match operation.as_str() {
+        "add" | "remove" | "list" => Ok(operation),
+        _ => Err(bad(
+            "operation",
+            &operation,
+            Expected::OneOf(&["add", "remove", "list"]),
+        )),
+    }
+}
+
+fn parse_filter(args: &mut Args) -> Result<Command, ParseError> {
+    let operation = parse_operation(args)?;
+    let tag = args.tag()?;
+    if operation == "list" {
+        return Ok(Command::Setting(SettingCommand::Filter {
+            tag,
+            op: FilterOp::List,
+        }));
+    }
+    let kind = args.required("kind")?;
+    let kind = kind
+        .parse::<FilterKind>()
+        .map_err(|_| bad("kind", &kind, Expected::OneOf(&["include", "exclude"])))?;
+    let filters = args
+        .rest()
+        .iter()
+        .map(|pattern| {
+            TagFilter::parse(kind, pattern).map_err(|err| {
+                bad(
+                    "pattern",
+                    pattern,
+                    Expected::Regex(err.root_cause().to_string()),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    if filters.is_empty() {
+        return Err(args.missing("pattern"));
+    }
+    let op = if operation == "add" {
+        FilterOp::Add(filters)
+    } else {
+        FilterOp::Remove(filters)
+    };
+    Ok(Command::Setting(SettingCommand::Filter { tag, op }))
+}
+
+fn parse_sender(args: &mut Args) -> Result<Command, ParseError> {
+    let operation = parse_operation(args)?;
+    let tag = args.tag()?;
+    if operation == "list" {
+        return Ok(Command::Setting(SettingCommand::Sender {
+            tag,
+            op: SenderOp::List,
+        }));
+    }
+    let kind = args.required("kind")?;
+    let kind = kind
+        .parse::<RuleKind>()
+        .map_err(|_| bad("kind", &kind, Expected::OneOf(&["allow", "deny"])))?;
+    let rules = args
+        .rest()
+        .iter()
+        .map(|sender| {
+            let sender_id = utils::sender_preprocess(sender)
+                .map_err(|_| bad("sender", sender, Expected::Sender))?;
+            Ok(SenderRule { kind, sender_id })
+        })
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    if rules.is_empty() {
+        return Err(args.missing("sender"));
+    }
+    let op = if operation == "add" {
+        SenderOp::Add(rules)
+    } else {
+        SenderOp::Remove(rules)
+    };
+    Ok(Command::Setting(SettingCommand::Sender { tag, op }))
+}
+
+fn parse_share(args: &mut Args) -> Result<Command, ParseError> {
+    let tag = args.tag()?;
+    let Some(member) = args.optional() else {
+        return Ok(Command::Setting(SettingCommand::Share {
+            tag,
+            op: ShareOp::List,
+        }));
+    };
+    let member_id = utils::member_preprocess(&member)
+        .map_err(|_| bad("member", &member, Expected::MemberMention))?;
+    let role = args.required("role")?;
+    let op = if role == "off" {
+        ShareOp::Unshare(member_id)
+    } else {
+        let role = role
+            .parse::<TagRole>()
+            .map_err(|_| bad("role", &role, Expected::OneOf(&["editor", "viewer", "off"])))?;
+        ShareOp::Share(member_id, role)
+    };
+    Ok(Command::Setting(SettingCommand::Share { tag, op }))
+}
+
+fn parse_admin(args: &mut Args) -> Result<Command, ParseError> {
+    let operation = parse_operation(args)?;
+    let op = match operation.as_str() {
+        "list" => AdminOp::List,
+        "add" => AdminOp::Add(parse_user(&args.required("user")?)?),
+        _ => AdminOp::Remove(parse_user(&args.required("user")?)?),
+    };
+    Ok(Command::Admin(op))
+}
+
+fn parse_lang(args: &mut Args) -> Result<Command, ParseError> {
+    let op = match args.optional() {
+        None => LangOp::Show,
+        Some(lang) if lang == "auto" => LangOp::Auto,
+        Some(lang) => LangOp::Set(
+            lang.parse::<Lang>()
+                .map_err(|_| bad("lang", &lang, Expected::OneOf(&["ja", "en", "auto"])))?,
+        ),
+    };
+    Ok(Command::Lang(op))
+}
+
+// a channel mention filters by the channel, any other word is the tag
+fn parse_audit(args: &mut Args) -> Result<Command, ParseError> {
+    let is_public = args.public();
+    let limit = args
+        .value("--limit")
+        .map(|limit| {
+            limit
+                .parse::<i64>()
+                .ok()
+                .filter(|limit| 0 < *limit)
+                .ok_or_else(|| bad("--limit", &limit, Expected::PositiveNumber))
+        })
+        .transpose()?;
+    // a tag and a channel, in either order, and at most one of each
+    let mut tag = None;
+    let mut channel = None;
+    while let Some(token) = args.positional.pop_front() {
+        let is_channel = !token.quoted && token.text.starts_with("<#");
+        if (is_channel && channel.is_some()) || (!is_channel && tag.is_some()) {
+            return Err(ParseError::UnexpectedArgument {
+                arg: token.text,
+                usage: args.usage,
+            });
+        }
+        if is_channel {
+            channel = Some(parse_channel(&token.text)?);
+        } else {
+            tag = Some(TagArg {
+                name: token.text,
+                is_public,
+            });
+        }
+    }
+    Ok(Command::Audit {
+        tag,
+        channel,
+        limit,
+    })
+}
+
+fn parse_time_zone(args: &mut Args) -> Result<Option<TimeZone>, ParseError> {
+    args.optional()
+        .map(|zone| {
+            zone.parse::<TimeZone>()
+                .map_err(|_| bad("time_zone", &zone, Expected::Like("Asia/Tokyo")))
+        })
+        .transpose()
+}
+
+fn parse_time(args: &mut Args, name: &str) -> Result<i64, ParseError> {
+    let time = args.required(name)?;
+    delivery::parse_time_of_day(&time).map_err(|_| bad(name, &time, Expected::Like("HH:MM")))
+}
+
+fn parse_digest(args: &mut Args) -> Result<Command, ParseError> {
+    let mode = args.required("mode")?;
+    let (mode, time_zone) = match mode.as_str() {
+        "every" => {
+            let minutes = args.required("minutes")?;
+            let minutes = minutes
+                .parse::<i64>()
+                .ok()
+                .filter(|minutes| 0 < *minutes)
+                .ok_or_else(|| bad("minutes", &minutes, Expected::PositiveNumber))?;
+            (DeliveryMode::Interval(minutes), None)
+        }
+        "daily" => {
+            let minute_of_day = parse_time(args, "time")?;
+            (DeliveryMode::Daily(minute_of_day), parse_time_zone(args)?)
+        }
+        "off" => (DeliveryMode::Immediate, None),
+        _ => {
+            return Err(bad(
+                "mode",
+                &mode,
+                Expected::OneOf(&["every", "daily", "off"]),
+            ))
+        }
+    };
+    Ok(Command::Dist(DistCommand::Digest { mode, time_zone }))
+}
+
+fn parse_quiet(args: &mut Args) -> Result<Command, ParseError> {
+    if args.positional.front().map(|token| token.text.as_str()) == Some("off") {
+        args.positional.pop_front();
+        return Ok(Command::Dist(DistCommand::Quiet {
+            hours: None,
+            time_zone: None,
+        }));
+    }
+    let start = parse_time(args, "start")?;
+    let end = parse_time(args, "end")?;
+    if start == end {
+        return Err(ParseError::SameStartAndEnd);
+    }
+    Ok(Command::Dist(DistCommand::Quiet {
+        hours: Some((start, end)),
+        time_zone: parse_time_zone(args)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str, is_public: bool) -> TagArg {
+        TagArg {
+            name: name.to_string(),
+            is_public,
+        }
+    }
+
+    fn channel(id: &str) -> SlackChannelId {
+        SlackChannelId::new(id.to_string())
+    }
+
+    fn error(line: &str) -> String {
+        Command::parse(line).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn tokenize_test() {
+        let tokens = tokenize("add “my tag” \"--public\"  x").unwrap();
+        let texts = tokens.iter().map(|t| t.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["add", "my tag", "--public", "x"]);
+        assert!(tokens[1].quoted && tokens[2].quoted && !tokens[3].quoted);
+        assert!(tokenize("add \"my tag").is_err());
+        assert!(tokenize("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn add_test() {
+        assert_eq!(
+            Command::parse("add major <#C01|general> <#C02|>").unwrap(),
+            Command::Tag(TagCommand::Add {
+                tag: tag("major", false),
+                channels: vec![channel("C01"), channel("C02")]
+            })
+        );
+        // flags may come anywhere, and quotes keep spaces
+        assert_eq!(
+            Command::parse("add \"release notes\" <#C01|general> --public").unwrap(),
+            Command::Tag(TagCommand::Add {
+                tag: tag("release notes", true),
+                channels: vec![channel("C01")]
+            })
+        );
+        assert!(error("add major general").contains("`general`"));
+        assert!(error("add major").contains("#channel is missing"));
+        assert!(error("add").contains("tag is missing"));
+        assert!(error("add major <#C01|> --pubic").contains("unknown option --pubic"));
+    }
+
+    #[test]
+    fn delete_test() {
+        assert_eq!(
+            Command::parse("delete --public major <#C01|general>").unwrap(),
+            Command::Tag(TagCommand::Delete {
+                tag: tag("major", true),
+                channels: vec![channel("C01")]
+            })
+        );
+        assert!(error("delete major").contains("drop_tag"));
+    }
+
+    #[test]
+    fn drop_tag_test() {
+        assert_eq!(
+            Command::parse("drop_tag major").unwrap(),
+            Command::Tag(TagCommand::DropTag {
+                tag: tag("major", false),
+                confirmed: false
+            })
+        );
+        assert_eq!(
+            Command::parse("drop_tag --confirm --public major").unwrap(),
+            Command::Tag(TagCommand::DropTag {
+                tag: tag("major", true),
+                confirmed: true
+            })
+        );
+        assert!(error("drop_tag major yes").contains("unexpected argument `yes`"));
+    }
+
+    #[test]
+    fn rename_copy_merge_test() {
+        assert_eq!(
+            Command::parse("rename major \"major news\"").unwrap(),
+            Command::Tag(TagCommand::Rename {
+                tag: tag("major", false),
+                new_name: "major news".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse("copy --public major minor").unwrap(),
+            Command::Tag(TagCommand::Copy {
+                tag: tag("major", true),
+                new_name: "minor".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse("merge minor major").unwrap(),
+            Command::Tag(TagCommand::Merge {
+                tag: tag("minor", false),
+                into: "major".to_string()
+            })
+        );
+        assert!(error("rename major").contains("new_tag is missing"));
+        assert!(error("copy major \"\"").contains("new_tag should not be empty"));
+        assert!(error("merge minor").contains("into_tag is missing"));
+    }
+
+    #[test]
+    fn retrieve_test() {
+        assert_eq!(
+            Command::parse("retrieve_bot major true").unwrap(),
+            Command::Setting(SettingCommand::RetrieveBot {
+                tag: tag("major", false),
+                retrieve: true
+            })
+        );
+        assert_eq!(
+            Command::parse("retrieve_file major false --public").unwrap(),
+            Command::Setting(SettingCommand::RetrieveFile {
+                tag: tag("major", true),
+                retrieve: false
+            })
+        );
+        assert!(error("retrieve_bot major yes").contains("invalid retrieve `yes`"));
+        assert!(error("retrieve_file major").contains("retrieve is missing"));
+    }
+
+    #[test]
+    fn thread_test() {
+        assert_eq!(
+            Command::parse("thread major root").unwrap(),
+            Command::Setting(SettingCommand::Thread {
+                tag: tag("major", false),
+                mode: ThreadMode::Root
+            })
+        );
+        assert!(error("thread major all").contains("invalid mode `all`"));
+    }
+
+    #[test]
+    fn filter_test() {
+        assert_eq!(
+            Command::parse("filter add major include release \"/v[0-9] beta/\"").unwrap(),
+            Command::Setting(SettingCommand::Filter {
+                tag: tag("major", false),
+                op: FilterOp::Add(vec![
+                    TagFilter::parse(FilterKind::Include, "release").unwrap(),
+                    TagFilter::parse(FilterKind::Include, "/v[0-9] beta/").unwrap(),
+                ])
+            })
+        );
+        assert_eq!(
+            Command::parse("filter remove --public major exclude bot").unwrap(),
+            Command::Setting(SettingCommand::Filter {
+                tag: tag("major", true),
+                op: FilterOp::Remove(vec![TagFilter::parse(FilterKind::Exclude, "bot").unwrap()])
+            })
+        );
+        assert_eq!(
+            Command::parse("filter list major").unwrap(),
+            Command::Setting(SettingCommand::Filter {
+                tag: tag("major", false),
+                op: FilterOp::List
+            })
+        );
+        assert!(error("filter clear major").contains("invalid operation `clear`"));
+        assert!(error("filter add major only x").contains("invalid kind `only`"));
+        assert!(error("filter add major include").contains("pattern is missing"));
+        assert!(error("filter add major include /[/").contains("invalid pattern `/[/`"));
+    }
+
+    #[test]
+    fn sender_test() {
+        assert_eq!(
+            Command::parse("sender add major deny <@U01|someone> B02").unwrap(),
+            Command::Setting(SettingCommand::Sender {
+                tag: tag("major", false),
+                op: SenderOp::Add(vec![
+                    SenderRule {
+                        kind: RuleKind::Deny,
+                        sender_id: "U01".to_string()
+                    },
+                    SenderRule {
+                        kind: RuleKind::Deny,
+                        sender_id: "B02".to_string()
+                    },
+                ])
+            })
+        );
+        assert_eq!(
+            Command::parse("sender list --public major").unwrap(),
+            Command::Setting(SettingCommand::Sender {
+                tag: tag("major", true),
+                op: SenderOp::List
+            })
+        );
+        assert!(matches!(
+            Command::parse("sender remove major allow U01").unwrap(),
+            Command::Setting(SettingCommand::Sender {
+                op: SenderOp::Remove(_),
+                ..
+            })
+        ));
+        assert!(error("sender add major allow someone").contains("invalid sender `someone`"));
+        assert!(error("sender add major allow").contains("sender is missing"));
+    }
+
+    #[test]
+    fn share_test() {
+        assert_eq!(
+            Command::parse("share major").unwrap(),
+            Command::Setting(SettingCommand::Share {
+                tag: tag("major", false),
+                op: ShareOp::List
+            })
+        );
+        assert_eq!(
+            Command::parse("share major <!subteam^S01|@team> editor").unwrap(),
+            Command::Setting(SettingCommand::Share {
+                tag: tag("major", false),
+                op: ShareOp::Share("S01".to_string(), TagRole::Editor)
+            })
+        );
+        assert_eq!(
+            Command::parse("share --public major <@U01|someone> off").unwrap(),
+            Command::Setting(SettingCommand::Share {
+                tag: tag("major", true),
+                op: ShareOp::Unshare("U01".to_string())
+            })
+        );
+        assert!(error("share major <@U01> owner").contains("invalid role `owner`"));
+        assert!(error("share major someone viewer").contains("invalid member `someone`"));
+        assert!(error("share major <@U01>").contains("role is missing"));
+    }
+
+    #[test]
+    fn admin_test() {
+        assert_eq!(
+            Command::parse("admin list").unwrap(),
+            Command::Admin(AdminOp::List)
+        );
+        assert_eq!(
+            Command::parse("admin add <@U01|someone>").unwrap(),
+            Command::Admin(AdminOp::Add(SlackUserId::new("U01".to_string())))
+        );
+        assert_eq!(
+            Command::parse("admin remove U01").unwrap(),
+            Command::Admin(AdminOp::Remove(SlackUserId::new("U01".to_string())))
+        );
+        assert!(error("admin add <!subteam^S01|@team>").contains("invalid user"));
+        assert!(error("admin remove").contains("user is missing"));
+    }
+
+    #[test]
+    fn audit_test() {
+        assert_eq!(
+            Command::parse("audit").unwrap(),
+            Command::Audit {
+                tag: None,
+                channel: None,
+                limit: None
+            }
+        );
+        assert_eq!(
+            Command::parse("audit --limit 5 major <#C01|general> --public").unwrap(),
+            Command::Audit {
+                tag: Some(tag("major", true)),
+                channel: Some(channel("C01")),
+                limit: Some(5)
+            }
+        );
+        assert!(error("audit --limit 0").contains("invalid --limit `0`"));
+        assert!(error("audit --limit").contains("--limit needs a value"));
+        assert!(error("audit major minor").contains("unexpected argument `minor`"));
+        assert!(error("audit <#C01|general> <#C02|random>").contains("unexpected argument"));
+    }
+
+    #[test]
+    fn export_test() {
+        assert_eq!(
+            Command::parse("export").unwrap(),
+            Command::Export(Format::Json)
+        );
+        assert_eq!(
+            Command::parse("export --yaml").unwrap(),
+            Command::Export(Format::Yaml)
+        );
+        assert!(Command::parse("export --json --yaml").is_err());
+        assert!(error("export --toml").contains("unknown option --toml"));
+    }
+
+    #[test]
+    fn list_test() {
+        assert_eq!(
+            Command::parse("ch_list --public major").unwrap(),
+            Command::ChList(tag("major", true))
+        );
+        assert_eq!(Command::parse("tag_list").unwrap(), Command::TagList);
+        assert_eq!(Command::parse("target_list").unwrap(), Command::TargetList);
+        assert!(error("ch_list").contains("tag is missing"));
+        assert!(error("tag_list major").contains("unexpected argument `major`"));
+    }
+
+    #[test]
+    fn set_test() {
+        assert_eq!(
+            Command::parse("set major \"minor news\"").unwrap(),
+            Command::Dist(DistCommand::Set {
+                tags: vec!["major".to_string(), "minor news".to_string()],
+                is_public: false
+            })
+        );
+        assert_eq!(
+            Command::parse("unset major --public").unwrap(),
+            Command::Dist(DistCommand::Unset {
+                tags: vec!["major".to_string()],
+                is_public: true
+            })
+        );
+        assert!(error("set --public").contains("tag is missing"));
+    }
+
+    #[test]
+    fn create_channel_test() {
+        assert_eq!(
+            Command::parse("create_channel --public news major minor").unwrap(),
+            Command::Dist(DistCommand::CreateChannel {
+                name: "news".to_string(),
+                tags: vec!["major".to_string(), "minor".to_string()],
+                is_public: true
+            })
+        );
+        assert!(error("create_channel news").contains("tag is missing"));
+        assert!(error("create_channel").contains("channel_name is missing"));
+    }
+
+    #[test]
+    fn digest_test() {
+        assert_eq!(
+            Command::parse("digest every 30").unwrap(),
+            Command::Dist(DistCommand::Digest {
+                mode: DeliveryMode::Interval(30),
+                time_zone: None
+            })
+        );
+        assert_eq!(
+            Command::parse("digest daily 09:00 Asia/Tokyo").unwrap(),
+            Command::Dist(DistCommand::Digest {
+                mode: DeliveryMode::Daily(540),
+                time_zone: Some(TimeZone::Named("Asia/Tokyo".to_string()))
+            })
+        );
+        assert_eq!(
+            Command::parse("digest off").unwrap(),
+            Command::Dist(DistCommand::Digest {
+                mode: DeliveryMode::Immediate,
+                time_zone: None
+            })
+        );
+        assert!(error("digest every -1").contains("invalid minutes `-1`"));
+        assert!(error("digest daily 25:00").contains("invalid time `25:00`"));
+        assert!(error("digest weekly").contains("invalid mode `weekly`"));
+        assert!(error("digest daily 09:00 Tokyo").contains("invalid time_zone `Tokyo`"));
+    }
+
+    #[test]
+    fn quiet_test() {
+        assert_eq!(
+            Command::parse("quiet 22:00 07:00 -05:00").unwrap(),
+            Command::Dist(DistCommand::Quiet {
+                hours: Some((1320, 420)),
+                time_zone: Some(TimeZone::Fixed(-300))
+            })
+        );
+        assert_eq!(
+            Command::parse("quiet off").unwrap(),
+            Command::Dist(DistCommand::Quiet {
+                hours: None,
+                time_zone: None
+            })
+        );
+        assert!(error("quiet 22:00").contains("end is missing"));
+        assert!(error("quiet 22:00 22:00").contains("different"));
+    }
+
+    #[test]
+    fn lang_test() {
+        assert_eq!(Command::parse("lang").unwrap(), Command::Lang(LangOp::Show));
+        assert_eq!(
+            Command::parse("lang en").unwrap(),
+            Command::Lang(LangOp::Set(Lang::En))
+        );
+        assert_eq!(
+            Command::parse("lang auto").unwrap(),
+            Command::Lang(LangOp::Auto)
+        );
+        assert!(error("lang fr").contains("invalid lang `fr`"));
+        assert!(error("lang ja en").contains("unexpected argument `en`"));
+    }
+
+    #[test]
+    fn help_test() {
+        assert_eq!(Command::parse("help").unwrap(), Command::Help(None));
+        assert_eq!(
+            Command::parse("help add").unwrap(),
+            Command::Help(Some("add".to_string()))
+        );
+        assert_eq!(
+            Command::parse("list").unwrap(),
+            Command::Undefined("list".to_string())
+        );
+        assert_eq!(Command::parse("").unwrap(), Command::Modal);
+        assert_eq!(Command::parse("  ").unwrap(), Command::Modal);
+    }
+
+    #[test]
+    fn changes_routing_test() {
+        assert!(Command::parse("set major").unwrap().changes_routing());
+        assert!(Command::parse("digest every 30").unwrap().changes_routing());
+        assert!(!Command::parse("tag_list").unwrap().changes_routing());
+    }
+}
@@ -1,86 +1,93 @@
+pub mod access;
+pub mod admin;
+pub mod audit;
 pub mod create_channel;
+pub mod digest;
+pub mod export;
+pub mod filter;
 pub mod help;
+pub mod home;
+pub mod lang;
+pub mod modal;
 pub mod operate;
+pub mod quiet;
+pub mod sender;
 pub mod set_target_tags;
+pub mod share;
 
 use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
-use std::{str::SplitWhitespace, sync::Arc};
-
-use anyhow::Context;
+use std::sync::Arc;
 
 use crate::{
-    post_message::MessagePoster,
-    query::{dist, fetch_user_folder},
-    utils,
+    command_parser::TagArg, i18n, post_message::MessagePoster, query::tag_member::TagRole,
+    response, storage::Storage,
 };
 
 const PUBLIC_TAGS: &str = "public";
 
 pub async fn tag_list_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
 ) -> anyhow::Result<()> {
-    let user_tags = fetch_user_folder::tag_list_user(user_id_command.clone()).await?;
-    let public_tags = fetch_user_folder::tag_list_pub().await?;
+    let user_tags = storage.tag_list_user(user_id_command.clone()).await?;
+    let public_tags = storage.tag_list_public().await?;
+    let members = access::member_ids(&cli, &user_id_command).await?;
+    let shared_tags = storage.member_shares(&members).await?;
 
-    let tag_list_text =
-        format!("タグのリストは以下です。\n user: {user_tags:#?}\n public: {public_tags:#?}");
-    let _ = MessagePoster::new(channel_id_command, tag_list_text, cli)
-        .post_ephemeral(user_id_command)
-        .await?;
-    Ok(())
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    response::tag_list(msg, &user_tags, &shared_tags, &public_tags)
+        .post_ephemeral(cli, channel_id_command, user_id_command)
+        .await
 }
 
 pub async fn ch_list_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    tag: &TagArg,
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (tag, _owner_id) = match first_arg {
-        "--public" => {
-            let tag = args_iter.next().context("argument error")?;
-            (tag, SlackUserId::new(PUBLIC_TAGS.to_string()))
-        }
-        tag => (tag, user_id_command.clone()),
-    };
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Viewer,
+    )
+    .await?;
 
-    let ch_id_list = fetch_user_folder::channel_list(tag, user_id_command.clone()).await?;
-    let ch_name_list = ch_id_list
-        .iter()
-        .map(utils::channel_id_to_channel_name)
-        .collect::<Vec<_>>();
-    let ch_list_text = format!("タグ {tag} に登録されたチャンネルは以下です。 {ch_name_list:#?}");
-    let _ = MessagePoster::new(channel_id_command, ch_list_text, cli)
-        .post_ephemeral(user_id_command)
-        .await?;
-
-    Ok(())
+    let ch_id_list = storage.channel_list(tag, owner_id).await?;
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    response::channel_list(msg, tag, &ch_id_list)
+        .post_ephemeral(cli, channel_id_command, user_id_command)
+        .await
 }
 
 pub async fn target_list_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
 ) -> anyhow::Result<()> {
-    let target_list = dist::target_list(&channel_id_command).await?;
-    let target_list_text = format!(
-        "現在このチャンネルが収集対象としているタグのリストは以下です。\n {target_list:#?}"
-    );
-    let _ = MessagePoster::new(channel_id_command, target_list_text, cli)
-        .post_ephemeral(user_id_command)
-        .await?;
-    Ok(())
+    let target_list = storage.target_list(&channel_id_command).await?;
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    response::target_list(msg, &target_list)
+        .post_ephemeral(cli, channel_id_command, user_id_command)
+        .await
 }
 
 pub async fn undefined_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
 ) -> anyhow::Result<()> {
-    let undefined_text = "このコマンド引数は未定義です。".to_string();
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let undefined_text = msg.undefined_command().to_string();
     let _ = MessagePoster::new(channel_id_command, undefined_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
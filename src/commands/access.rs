@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use slack_morphism::{
+    prelude::{SlackApiUserGroupsListRequest, SlackHyperClient},
+    SlackApiTokenType, SlackUserId,
+};
+
+use crate::{query::tag_member::TagRole, storage::Storage, utils};
+
+// Why the user may not do what a command asks. These are shown to users, so they are
+// translated through the catalog, and the English here is what is logged.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AccessError {
+    #[error("the tag {0} is shared with you as a viewer, and cannot be edited")]
+    Viewer(String),
+    #[error("several tags named {0} are shared with you, ask their owners to rename one")]
+    Ambiguous(String),
+    #[error("only admins and editors of the public tag {0} can change it")]
+    PublicTag(String),
+    #[error("only the owner of the tag can {0} it")]
+    OwnerOnly(OwnerAction),
+    #[error("only admins can share public tags")]
+    SharePublic,
+    #[error("only admins can change admins")]
+    AdminOnly,
+    #[error("admins given by SLACK_ADMIN_IDS cannot be removed by the command")]
+    FixedAdmin,
+}
+
+// What editors may not do with a shared tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OwnerAction {
+    Drop,
+    Rename,
+    Copy,
+    Merge,
+    Share,
+}
+
+impl fmt::Display for OwnerAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            OwnerAction::Drop => "drop",
+            OwnerAction::Rename => "rename",
+            OwnerAction::Copy => "copy",
+            OwnerAction::Merge => "merge",
+            OwnerAction::Share => "share",
+        };
+        write!(f, "{action}")
+    }
+}
+
+// The user groups of a user are asked of Slack at most once in this long
+const GROUPS_TTL: Duration = Duration::from_mins(5);
+
+type GroupsCache = Mutex<HashMap<SlackUserId, (Vec<String>, Instant)>>;
+
+fn groups_cache() -> &'static GroupsCache {
+    static GROUPS_CACHE: OnceLock<GroupsCache> = OnceLock::new();
+    GROUPS_CACHE.get_or_init(Mutex::default)
+}
+
+fn cached_groups(user: &SlackUserId) -> Option<Vec<String>> {
+    let cache = groups_cache().lock().ok()?;
+    cache
+        .get(user)
+        .filter(|(_, resolved_at)| resolved_at.elapsed() < GROUPS_TTL)
+        .map(|(groups, _)| groups.clone())
+}
+
+fn cache_groups(user: &SlackUserId, groups: Vec<String>) {
+    if let Ok(mut cache) = groups_cache().lock() {
+        cache.retain(|_, (_, resolved_at)| resolved_at.elapsed() < GROUPS_TTL);
+        cache.insert(user.clone(), (groups, Instant::now()));
+    }
+}
+
+// The ids of the user groups the user is in, all listed with a single call
+async fn user_groups(
+    cli: &Arc<SlackHyperClient>,
+    user: &SlackUserId,
+) -> anyhow::Result<Vec<String>> {
+    let token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&token);
+    let list_req = SlackApiUserGroupsListRequest::new().with_include_users(true);
+    let res = session.usergroups_list(&list_req).await?;
+    Ok(res
+        .usergroups
+        .into_iter()
+        .filter(|group| {
+            group
+                .users
+                .as_ref()
+                .is_some_and(|users| users.contains(user))
+        })
+        .map(|group| group.id.0)
+        .collect())
+}
+
+// The members tags may be shared with that the user is: the user and the user's groups
+pub async fn member_ids(
+    cli: &Arc<SlackHyperClient>,
+    user: &SlackUserId,
+) -> anyhow::Result<Vec<String>> {
+    let groups = if let Some(groups) = cached_groups(user) {
+        groups
+    } else {
+        let groups = user_groups(cli, user).await?;
+        cache_groups(user, groups.clone());
+        groups
+    };
+    Ok(std::iter::once(user.0.clone()).chain(groups).collect())
+}
+
+// The owner of the tag named `tag` that the user may use with the role, if any.
+// The user's own tag comes first, then the tags shared with the user or the user's groups.
+pub async fn resolve_owner(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+    tag: &str,
+    required: TagRole,
+) -> anyhow::Result<Option<SlackUserId>> {
+    if storage.is_valid_tag_for_user(user, tag).await? {
+        return Ok(Some(user.clone()));
+    }
+
+    let mut owners = Vec::new();
+    let mut lacks_role = false;
+    let shares = storage.member_shares(&member_ids(cli, user).await?).await?;
+    for share in shares.into_iter().filter(|share| share.tag_name == tag) {
+        if required <= share.role {
+            owners.push(share.owner_id);
+        } else {
+            lacks_role = true;
+        }
+    }
+    owners.sort_by(|a, b| a.0.cmp(&b.0));
+    owners.dedup();
+
+    match owners.as_slice() {
+        [] if lacks_role => Err(AccessError::Viewer(tag.to_string()).into()),
+        [] => Ok(None),
+        [owner] => Ok(Some(owner.clone())),
+        _ => Err(AccessError::Ambiguous(tag.to_string()).into()),
+    }
+}
+
+// The private tags of other users the user may edit through a share, as (name, owner).
+// Names `resolve_owner` would not take to these owners are left out: the user's own tags come
+// first, and a name shared by several owners is refused.
+pub async fn editable_shares(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+) -> anyhow::Result<Vec<(String, SlackUserId)>> {
+    let own_tags = storage.tag_list_user(user.clone()).await?;
+    let mut shared = Vec::new();
+    for share in storage.member_shares(&member_ids(cli, user).await?).await? {
+        if share.role < TagRole::Editor
+            || share.owner_id.0 == super::PUBLIC_TAGS
+            || own_tags.contains(&share.tag_name)
+        {
+            continue;
+        }
+        shared.push((share.tag_name, share.owner_id));
+    }
+    shared.sort_by(|a, b| (&a.0, &a.1 .0).cmp(&(&b.0, &b.1 .0)));
+    shared.dedup();
+
+    let names = shared
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    shared.retain(|(name, _)| names.iter().filter(|other| *other == name).count() == 1);
+    Ok(shared)
+}
+
+pub async fn is_admin(storage: &dyn Storage, user: &SlackUserId) -> anyhow::Result<bool> {
+    if utils::get_admin_ids().contains(user) {
+        return Ok(true);
+    }
+    Ok(storage.admin_list().await?.contains(user))
+}
+
+// Everyone may use a public tag, but only admins and its editors may change it.
+// A public tag that does not exist yet may be created by anyone, who becomes its editor.
+async fn check_public(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+    tag: &str,
+    required: TagRole,
+) -> anyhow::Result<()> {
+    if required == TagRole::Viewer || is_admin(storage, user).await? {
+        return Ok(());
+    }
+    let public = SlackUserId::new(super::PUBLIC_TAGS.to_string());
+    if !storage.is_valid_tag_for_user(&public, tag).await? {
+        return Ok(());
+    }
+
+    let editors = storage
+        .tag_shares(tag, &public)
+        .await?
+        .into_iter()
+        .filter(|share| required <= share.role)
+        .map(|share| share.member_id)
+        .collect::<Vec<_>>();
+    if !editors.is_empty() {
+        let members = member_ids(cli, user).await?;
+        if editors.iter().any(|editor| members.contains(editor)) {
+            return Ok(());
+        }
+    }
+    Err(AccessError::PublicTag(tag.to_string()).into())
+}
+
+// The owner to pass to the queries for the tag given in a command.
+// A private tag nobody shared with the user is the user's own, which may not exist yet.
+pub async fn tag_owner(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+    tag: &str,
+    is_public: bool,
+    required: TagRole,
+) -> anyhow::Result<SlackUserId> {
+    if is_public {
+        check_public(cli, storage, user, tag, required).await?;
+        return Ok(SlackUserId::new(super::PUBLIC_TAGS.to_string()));
+    }
+    let owner = resolve_owner(cli, storage, user, tag, required).await?;
+    Ok(owner.unwrap_or_else(|| user.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use slack_morphism::prelude::{SlackClient, SlackClientHyperConnector};
+
+    use crate::storage::testing::storage_tests;
+
+    use super::*;
+
+    async fn test_editable_shares(storage: &dyn Storage) -> anyhow::Result<()> {
+        // the user is in no group, which is cached so that Slack is not called
+        let cli = Arc::new(SlackClient::new(SlackClientHyperConnector::new()));
+        let owner_a = SlackUserId::new("U0a".to_string());
+        let owner_b = SlackUserId::new("U0b".to_string());
+        let user = SlackUserId::new("U00001".to_string());
+        cache_groups(&user, Vec::new());
+        assert_eq!(member_ids(&cli, &user).await?, vec![user.0.clone()]);
+        let channel = slack_morphism::SlackChannelId::new("C01".to_string());
+        for (tag, owner) in [
+            ("news", &owner_a),
+            ("viewed", &owner_a),
+            ("test_a", &owner_a),
+            ("twice", &owner_a),
+            ("twice", &owner_b),
+        ] {
+            storage
+                .register_channel(tag, channel.clone(), owner.clone())
+                .await?;
+            let role = if tag == "viewed" {
+                TagRole::Viewer
+            } else {
+                TagRole::Editor
+            };
+            storage.share_tag(tag, owner.clone(), &user.0, role).await?;
+        }
+        storage
+            .share_tag("news", owner_a.clone(), "U00002", TagRole::Editor)
+            .await?;
+
+        // a viewer, the user's own test_a first, and a name of two owners are left out
+        assert_eq!(
+            editable_shares(&cli, storage, &user).await?,
+            vec![("news".to_string(), owner_a.clone())]
+        );
+        for (tag, owner) in editable_shares(&cli, storage, &user).await? {
+            assert_eq!(
+                tag_owner(&cli, storage, &user, &tag, false, TagRole::Editor).await?,
+                owner
+            );
+        }
+
+        Ok(())
+    }
+
+    storage_tests!(test_editable_shares);
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::AdminOp,
+    i18n,
+    post_message::MessagePoster,
+    query::audit_log::{AuditAction, AuditEntry},
+    response,
+    storage::Storage,
+    utils,
+};
+
+use super::access::{self, AccessError};
+
+pub async fn admin_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    op: &AdminOp,
+) -> anyhow::Result<()> {
+    if *op != AdminOp::List && !access::is_admin(storage, &user_id_command).await? {
+        return Err(AccessError::AdminOnly.into());
+    }
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let admin_text = match op {
+        AdminOp::List => {
+            let mut admins = utils::get_admin_ids();
+            admins.extend(storage.admin_list().await?);
+            let admins = admins
+                .iter()
+                .map(|admin| format!("<@{admin}>"))
+                .collect::<Vec<_>>();
+            msg.admin_list(&response::join(msg, &admins))
+        }
+        AdminOp::Add(user) => {
+            storage.add_admin(user).await?;
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::AdminAdd)
+                .with_detail(format!("<@{user}>"));
+            storage.record_audit(&entry).await?;
+            msg.admin_added(user)
+        }
+        AdminOp::Remove(user) => {
+            if utils::get_admin_ids().contains(user) {
+                return Err(AccessError::FixedAdmin.into());
+            }
+            storage.remove_admin(user).await?;
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::AdminRemove)
+                .with_detail(format!("<@{user}>"));
+            storage.record_audit(&entry).await?;
+            msg.admin_removed(user)
+        }
+    };
+
+    let _ = MessagePoster::new(channel_id_command, admin_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
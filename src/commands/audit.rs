@@ -0,0 +1,99 @@
+use std::{fmt::Write, sync::Arc};
+
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::TagArg,
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditEntry, AuditQuery, CLI_ACTOR},
+        tag_member::TagRole,
+    },
+    storage::Storage,
+};
+
+use super::access;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+// Slack shows the time in the time zone of each reader
+fn format_entry(entry: &AuditEntry) -> String {
+    let time = format!(
+        "<!date^{}^{{date_num}} {{time}}|{}>",
+        entry.created_at, entry.created_at
+    );
+    let actor = if entry.actor.0 == CLI_ACTOR {
+        "CLI".to_string()
+    } else {
+        format!("<@{}>", entry.actor)
+    };
+    let mut line = format!("{time} {actor} {}", entry.action);
+    if let (Some(tag_name), Some(owner_id)) = (&entry.tag_name, &entry.owner_id) {
+        if owner_id.0 == super::PUBLIC_TAGS {
+            let _ = write!(line, " {tag_name} (public)");
+        } else {
+            let _ = write!(line, " {tag_name} (<@{owner_id}>)");
+        }
+    }
+    for channel in &entry.channels {
+        let _ = write!(line, " <#{channel}>");
+    }
+    if !entry.detail.is_empty() {
+        let _ = write!(line, " {}", entry.detail);
+    }
+    line
+}
+
+pub async fn audit_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: Option<&TagArg>,
+    channel: Option<SlackChannelId>,
+    limit: Option<i64>,
+) -> anyhow::Result<()> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    // a tag the user may view shows every change, otherwise private tags of others are hidden
+    let query = if let Some(tag) = tag {
+        let owner_id = access::tag_owner(
+            &cli,
+            storage,
+            &user_id_command,
+            &tag.name,
+            tag.is_public,
+            TagRole::Viewer,
+        )
+        .await?;
+        AuditQuery {
+            tag: Some((tag.name.clone(), owner_id)),
+            channel,
+            viewer: None,
+            limit,
+        }
+    } else {
+        let is_admin = access::is_admin(storage, &user_id_command).await?;
+        AuditQuery {
+            tag: None,
+            channel,
+            viewer: (!is_admin).then(|| user_id_command.clone()),
+            limit,
+        }
+    };
+
+    let entries = storage.audit_list(&query).await?;
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let audit_text = if entries.is_empty() {
+        msg.no_audit().to_string()
+    } else {
+        let lines = entries.iter().map(format_entry).collect::<Vec<_>>();
+        msg.audit_list(&lines.join("\n"))
+    };
+    let _ = MessagePoster::new(channel_id_command, audit_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use std::{str::SplitWhitespace, sync::Arc};
+use std::sync::Arc;
 
 use anyhow::Context;
 use slack_morphism::{
@@ -9,7 +9,7 @@ use slack_morphism::{
     SlackApiTokenType, SlackChannelId, SlackUserId,
 };
 
-use crate::{post_message::MessagePoster, utils};
+use crate::{i18n, post_message::MessagePoster, response, storage::Storage, utils};
 
 use super::set_target_tags::set_targets;
 
@@ -38,24 +38,25 @@ async fn invite_user(
 }
 pub async fn create_retrieve_tags_channel(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     tags: &[String],
     channel_name: String,
     user_id: SlackUserId,
-    owner_id: SlackUserId,
+    is_public: bool,
 ) -> anyhow::Result<SlackChannelId> {
     let create_res = create_priv_channel(cli.clone(), channel_name).await?;
     let channel_id = create_res.channel.id;
 
-    invite_user(cli.clone(), user_id, channel_id.clone())
+    invite_user(cli.clone(), user_id.clone(), channel_id.clone())
         .await
         .context("failed to invite user to created channel")?;
 
-    set_targets(&channel_id, owner_id, tags, true)
+    set_targets(&cli, storage, &channel_id, &user_id, is_public, tags, true)
         .await
         .context("failed to set tags in created channel")?;
-    let set_text = format!(
-        "以降、本チャンネルは以下のタグに登録されたチャンネルのメッセージを収集します。{tags:#?}"
-    );
+    // only the creator is in the new channel, so it is told in their language
+    let msg = i18n::catalog(&cli, storage, &user_id).await;
+    let set_text = msg.tags_set(&response::names(msg, tags));
     let _ = MessagePoster::new(channel_id.clone(), set_text, cli)
         .post_message()
         .await?;
@@ -64,31 +65,24 @@ pub async fn create_retrieve_tags_channel(
 
 pub async fn create_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    channel_name: &str,
+    tags: &[String],
+    is_public: bool,
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (channel_name, owner_id) = match first_arg {
-        "--public" => (
-            args_iter.next().context("argument error")?.to_string(),
-            SlackUserId::new(super::PUBLIC_TAGS.to_string()),
-        ),
-        channel => (channel.to_string(), user_id_command.clone()),
-    };
-
-    let tags = args_iter
-        .map(std::string::ToString::to_string)
-        .collect::<Vec<String>>();
     let new_channel_id = create_retrieve_tags_channel(
         cli.clone(),
-        &tags,
-        channel_name,
+        storage,
+        tags,
+        channel_name.to_string(),
         user_id_command.clone(),
-        owner_id,
+        is_public,
     )
     .await?;
-    let create_text = format!("以下のタグに登録されたメッセージを収集する新しいチャンネル <#{new_channel_id}> を作成しました:{tags:#?}");
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let create_text = msg.channel_created(&new_channel_id, &response::names(msg, tags));
     let _ = MessagePoster::new(channel_id_command, create_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
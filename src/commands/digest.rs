@@ -0,0 +1,62 @@
+use std::{fmt::Write, sync::Arc};
+
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        delivery::{self, DeliveryMode, TimeZone},
+    },
+    storage::Storage,
+};
+
+pub async fn digest_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    mode: DeliveryMode,
+    time_zone: Option<&TimeZone>,
+) -> anyhow::Result<()> {
+    let mut detail = match mode {
+        DeliveryMode::Immediate => "off".to_string(),
+        DeliveryMode::Interval(minutes) => format!("every {minutes}"),
+        DeliveryMode::Daily(minute_of_day) => {
+            format!("daily {}", delivery::format_time_of_day(minute_of_day))
+        }
+    };
+    if let Some(time_zone) = time_zone {
+        let _ = write!(detail, " {time_zone}");
+    }
+
+    storage
+        .set_delivery_mode(&channel_id_command, &user_id_command, mode, time_zone)
+        .await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Digest)
+        .with_channels(vec![channel_id_command.clone()])
+        .with_detail(detail);
+    storage.record_audit(&entry).await?;
+    // the time zone set before is used when it is not given
+    let time_zone = storage
+        .schedule_list()
+        .await?
+        .remove(&channel_id_command)
+        .unwrap_or_default()
+        .time_zone;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let digest_text = match mode {
+        DeliveryMode::Immediate => msg.delivery_immediate().to_string(),
+        DeliveryMode::Interval(minutes) => msg.delivery_interval(minutes),
+        DeliveryMode::Daily(minute_of_day) => {
+            let time = delivery::format_time_of_day(minute_of_day);
+            msg.delivery_daily(&time, &time_zone.to_string())
+        }
+    };
+    let _ = MessagePoster::new(channel_id_command, digest_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use slack_morphism::{
+    prelude::{SlackApiConversationsOpenRequest, SlackApiFilesUploadRequest, SlackHyperClient},
+    SlackApiTokenType, SlackChannelId, SlackFileType, SlackUserId,
+};
+
+use crate::{
+    config_file::{ConfigFile, Format},
+    i18n::{self, Catalog},
+    post_message::MessagePoster,
+    storage::Storage,
+    utils,
+};
+
+use super::access;
+
+// Slack cuts texts longer than 40000 characters, and recommends less than 4000
+const MAX_EXPORT_CHARS: usize = 3800;
+
+// Admins export every tag, others their own tags
+pub async fn export_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    format: Format,
+) -> anyhow::Result<()> {
+    let mut configs = storage.tag_config_list().await?;
+    if !access::is_admin(storage, &user_id_command).await? {
+        configs.retain(|config| config.owner_id == user_id_command);
+    }
+
+    let text = ConfigFile::from_configs(&configs).to_text(format)?;
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    if let Some(export_text) = export_text(msg, &text) {
+        let _ = MessagePoster::new(channel_id_command, export_text, cli)
+            .post_ephemeral(user_id_command)
+            .await?;
+    } else {
+        // the notice is shown where the command was run as well, since the file goes elsewhere
+        let comment = msg.export_file(configs.len());
+        upload_export(&cli, user_id_command.clone(), text, format, comment.clone()).await?;
+        let _ = MessagePoster::new(channel_id_command, comment, cli)
+            .post_ephemeral(user_id_command)
+            .await?;
+    }
+    Ok(())
+}
+
+// The dump in one message, unless it is too long for one
+fn export_text(msg: &dyn Catalog, text: &str) -> Option<String> {
+    let export_text = msg.export(text);
+    (export_text.chars().count() <= MAX_EXPORT_CHARS).then_some(export_text)
+}
+
+// A long dump is uploaded as a snippet, which can be downloaded and imported as it is.
+// A file cannot be ephemeral, so it goes to the direct messages of the user instead.
+async fn upload_export(
+    cli: &Arc<SlackHyperClient>,
+    user: SlackUserId,
+    text: String,
+    format: Format,
+    comment: String,
+) -> anyhow::Result<()> {
+    let token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&token);
+    let open_req = SlackApiConversationsOpenRequest::new().with_users(vec![user]);
+    let dm = session
+        .conversations_open(&open_req)
+        .await
+        .context("failed to open the direct messages.")?
+        .channel
+        .id;
+    let req = SlackApiFilesUploadRequest::new()
+        .with_channels(vec![dm])
+        .with_content(text)
+        .with_filename(format!("bugyo.{}", format.extension()))
+        .with_filetype(SlackFileType(format.extension().to_string()))
+        .with_initial_comment(comment);
+    session
+        .files_upload(&req)
+        .await
+        .context("failed to upload the export.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::user_lang::Lang;
+
+    use super::*;
+
+    #[test]
+    fn export_text_test() {
+        let msg = i18n::messages(Lang::En);
+        assert_eq!(export_text(msg, "{}"), Some(msg.export("{}")));
+
+        let long_text = "a".repeat(MAX_EXPORT_CHARS);
+        assert_eq!(export_text(msg, &long_text), None);
+    }
+}
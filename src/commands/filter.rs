@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::{FilterOp, TagArg},
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        tag_filter::TagFilter,
+        tag_member::TagRole,
+    },
+    response,
+    storage::Storage,
+};
+
+use super::access;
+
+pub async fn filter_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    op: &FilterOp,
+) -> anyhow::Result<()> {
+    // listing only needs the tag shared as a viewer
+    let role = if *op == FilterOp::List {
+        TagRole::Viewer
+    } else {
+        TagRole::Editor
+    };
+
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(&cli, storage, &user_id_command, tag, is_public, role).await?;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let filter_text = match op {
+        FilterOp::Add(filters) => {
+            let filters =
+                operate_filter_args(storage, filters, tag, owner_id.clone(), true).await?;
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::FilterAdd)
+                .with_tag(tag, owner_id)
+                .with_detail(filters.join(", "));
+            storage.record_audit(&entry).await?;
+            msg.filters_added(&response::escape(tag), &response::names(msg, &filters))
+        }
+        FilterOp::Remove(filters) => {
+            let filters =
+                operate_filter_args(storage, filters, tag, owner_id.clone(), false).await?;
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::FilterRemove)
+                .with_tag(tag, owner_id)
+                .with_detail(filters.join(", "));
+            storage.record_audit(&entry).await?;
+            msg.filters_removed(&response::escape(tag), &response::names(msg, &filters))
+        }
+        FilterOp::List => {
+            let filters = storage
+                .filter_list(tag, owner_id)
+                .await?
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            msg.filter_list(&response::escape(tag), &response::names(msg, &filters))
+        }
+    };
+
+    let _ = MessagePoster::new(channel_id_command, filter_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
+
+async fn operate_filter_args(
+    storage: &dyn Storage,
+    filters: &[TagFilter],
+    tag: &str,
+    owner_id: SlackUserId,
+    add: bool,
+) -> anyhow::Result<Vec<String>> {
+    let filter_stream = futures::stream::iter(filters);
+    filter_stream
+        .map(|filter| async {
+            if add {
+                storage.add_filter(tag, owner_id.clone(), filter).await
+            } else {
+                storage.remove_filter(tag, owner_id.clone(), filter).await
+            }
+        })
+        .then(|s| s)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let filter_names = filters.iter().map(ToString::to_string).collect::<Vec<_>>();
+    Ok(filter_names)
+}
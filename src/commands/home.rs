@@ -0,0 +1,322 @@
+use std::sync::Arc;
+
+use slack_morphism::{
+    prelude::{
+        SlackActionBlockElement, SlackActionsBlock, SlackApiViewsPublishRequest, SlackBlock,
+        SlackBlockButtonElement, SlackBlockConfirmItem, SlackBlockMarkDownText,
+        SlackBlockPlainText, SlackBlockPlainTextOnly, SlackBlockText, SlackContextBlock,
+        SlackContextBlockElement, SlackDividerBlock, SlackHeaderBlock, SlackHomeView,
+        SlackHyperClient, SlackInteractionActionInfo, SlackSectionBlock, SlackView,
+    },
+    SlackActionId, SlackApiTokenType, SlackChannelId, SlackTriggerId, SlackUserId,
+};
+
+use crate::{
+    command_parser::TagArg,
+    i18n::{self, Catalog},
+    post_message::MessagePoster,
+    query::tag_config::TagConfig,
+    response,
+    storage::Storage,
+    utils,
+};
+
+use super::{
+    modal::{self, ModalContext},
+    operate, set_target_tags, PUBLIC_TAGS,
+};
+
+pub const EDIT_ACTION: &str = "home_edit";
+pub const UNSET_ACTION: &str = "home_unset";
+pub const DROP_ACTION: &str = "home_drop";
+
+// Slack rejects views with more blocks than this
+const MAX_BLOCKS: usize = 100;
+
+fn markdown(text: String) -> SlackBlockText {
+    SlackBlockText::MarkDown(SlackBlockMarkDownText::new(text))
+}
+
+fn plain(text: &str) -> SlackBlockPlainTextOnly {
+    SlackBlockPlainText::new(text.to_string()).into()
+}
+
+fn button(action: &str, text: &str, value: String) -> SlackBlockButtonElement {
+    SlackBlockButtonElement::new(SlackActionId::new(action.to_string()), plain(text))
+        .with_value(value)
+}
+
+// A unset button carries the channel and the tag, as `C01 user:major`
+fn unset_value(dist: &SlackChannelId, tag: &TagArg) -> String {
+    format!("{dist} {}", modal::tag_value(tag))
+}
+
+fn parse_unset_value(value: &str) -> anyhow::Result<(SlackChannelId, TagArg)> {
+    let (dist, tag) = value
+        .split_once(' ')
+        .ok_or_else(|| anyhow::anyhow!("invalid unset value `{value}`"))?;
+    Ok((
+        SlackChannelId::new(dist.to_string()),
+        modal::parse_tag_value(tag)?,
+    ))
+}
+
+fn tag_blocks(msg: &dyn Catalog, config: &TagConfig, is_public: bool) -> Vec<SlackBlock> {
+    let tag = TagArg {
+        name: config.tag_name.clone(),
+        is_public,
+    };
+    let value = modal::tag_value(&tag);
+
+    let mut blocks = vec![SlackBlock::Section(
+        SlackSectionBlock::new()
+            .with_text(markdown(msg.home_tag(
+                &config.tag_name,
+                &response::mentions(msg, &config.channels),
+            )))
+            .with_accessory(button(EDIT_ACTION, msg.edit(), value.clone()).into()),
+    )];
+    // public tags are set by everyone, so only the user's own tags can be unset from here
+    for dist in &config.dists {
+        let section = SlackSectionBlock::new().with_text(markdown(msg.home_dist(dist)));
+        blocks.push(SlackBlock::Section(if is_public {
+            section
+        } else {
+            section
+                .with_accessory(button(UNSET_ACTION, msg.unset(), unset_value(dist, &tag)).into())
+        }));
+    }
+    let confirm = SlackBlockConfirmItem::new(
+        plain(msg.drop_title()),
+        markdown(msg.drop_warning(&config.tag_name, config.channels.len(), config.dists.len())),
+        plain(msg.delete()),
+        plain(msg.cancel()),
+    );
+    blocks.push(SlackBlock::Actions(SlackActionsBlock::new(vec![
+        SlackActionBlockElement::Button(
+            button(DROP_ACTION, msg.delete(), value)
+                .with_style("danger".to_string())
+                .with_confirm(confirm),
+        ),
+    ])));
+    blocks.push(SlackBlock::Divider(SlackDividerBlock::new()));
+    blocks
+}
+
+fn context(text: &str) -> SlackBlock {
+    SlackBlock::Context(SlackContextBlock::new(vec![
+        SlackContextBlockElement::MarkDown(SlackBlockMarkDownText::new(text.to_string())),
+    ]))
+}
+
+// The user's own tags and the public tags, with their channels and where they are set;
+// the app's DM is kept in private_metadata, to tell the results of the buttons there
+pub fn build_home(
+    msg: &dyn Catalog,
+    configs: &[TagConfig],
+    user_id: &SlackUserId,
+    dm_channel: &SlackChannelId,
+) -> SlackView {
+    let mut blocks = Vec::new();
+    for (title, owner, is_public) in [
+        (msg.your_tags(), user_id.to_string(), false),
+        (msg.public_tags(), PUBLIC_TAGS.to_string(), true),
+    ] {
+        blocks.push(SlackBlock::Header(SlackHeaderBlock::new(
+            plain(title).into(),
+        )));
+        let tags = configs
+            .iter()
+            .filter(|config| config.owner_id.0 == owner)
+            .collect::<Vec<_>>();
+        if tags.is_empty() {
+            blocks.push(context(msg.no_tags()));
+        }
+        for config in tags {
+            blocks.extend(tag_blocks(msg, config, is_public));
+        }
+    }
+    if blocks.len() > MAX_BLOCKS {
+        blocks.truncate(MAX_BLOCKS - 1);
+        blocks.push(context(msg.too_many_tags()));
+    }
+
+    SlackView::Home(SlackHomeView::new(blocks).with_private_metadata(dm_channel.to_string()))
+}
+
+pub async fn publish_home(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user_id: SlackUserId,
+    dm_channel: &SlackChannelId,
+) -> anyhow::Result<()> {
+    let msg = i18n::catalog(&cli, storage, &user_id).await;
+    let configs = storage.tag_config_list().await?;
+    let view = build_home(msg, &configs, &user_id, dm_channel);
+
+    let token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&token);
+    session
+        .views_publish(&SlackApiViewsPublishRequest::new(user_id, view))
+        .await?;
+    Ok(())
+}
+
+// The buttons run the same commands as the slash commands, and tell the result in the DM
+pub async fn home_action(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    trigger_id: SlackTriggerId,
+    user_id: SlackUserId,
+    dm_channel: &SlackChannelId,
+    action: &SlackInteractionActionInfo,
+) -> anyhow::Result<()> {
+    let value = action.value.as_deref().unwrap_or_default();
+    match action.action_id.0.as_str() {
+        EDIT_ACTION => {
+            let context = ModalContext {
+                channel_id: dm_channel.clone(),
+                selected: Some(modal::parse_tag_value(value)?),
+                from_home: true,
+            };
+            // the view is published again when the modal is submitted
+            return modal::open_modal(cli, storage, trigger_id, user_id, &context).await;
+        }
+        UNSET_ACTION => {
+            let (dist, tag) = parse_unset_value(value)?;
+            set_target_tags::set_targets(
+                &cli,
+                storage,
+                &dist,
+                &user_id,
+                tag.is_public,
+                std::slice::from_ref(&tag.name),
+                false,
+            )
+            .await?;
+            let msg = i18n::catalog(&cli, storage, &user_id).await;
+            let unset_text = msg.tag_unset_in(&dist, &tag.name);
+            MessagePoster::new(dm_channel.clone(), unset_text, cli.clone())
+                .post_ephemeral(user_id.clone())
+                .await?;
+        }
+        DROP_ACTION => {
+            let tag = modal::parse_tag_value(value)?;
+            // the button asks for the confirmation itself
+            operate::drop_tag_command(
+                cli.clone(),
+                storage,
+                dm_channel.clone(),
+                user_id.clone(),
+                &tag,
+                true,
+            )
+            .await?;
+        }
+        _ => return Ok(()),
+    }
+    publish_home(cli, storage, user_id, dm_channel).await
+}
+
+#[cfg(test)]
+mod tests {
+    use slack_morphism::prelude::SlackSectionBlockElement;
+
+    use crate::query::{thread_mode::ThreadMode, user_lang::Lang};
+
+    use super::*;
+
+    fn channel(id: &str) -> SlackChannelId {
+        SlackChannelId::new(id.to_string())
+    }
+
+    fn config(tag_name: &str, owner_id: &str, dists: &[&str]) -> TagConfig {
+        TagConfig {
+            tag_name: tag_name.to_string(),
+            owner_id: SlackUserId::new(owner_id.to_string()),
+            bot: false,
+            file: false,
+            thread_mode: ThreadMode::Mirror,
+            channels: vec![channel("C01"), channel("C02")],
+            dists: dists.iter().map(|dist| channel(dist)).collect(),
+        }
+    }
+
+    fn home_blocks(configs: &[TagConfig]) -> Vec<SlackBlock> {
+        let user_id = SlackUserId::new("U01".to_string());
+        let msg = i18n::messages(Lang::Ja);
+        let SlackView::Home(home) = build_home(msg, configs, &user_id, &channel("D01")) else {
+            panic!("the view should be a home view");
+        };
+        assert_eq!(home.private_metadata, Some("D01".to_string()));
+        home.blocks
+    }
+
+    fn accessory_action(block: &SlackBlock) -> Option<(String, String)> {
+        let SlackBlock::Section(section) = block else {
+            return None;
+        };
+        match section.accessory.as_ref()? {
+            SlackSectionBlockElement::Button(button) => Some((
+                button.action_id.0.clone(),
+                button.value.clone().unwrap_or_default(),
+            )),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn unset_value_test() {
+        let tag = TagArg {
+            name: "release notes".to_string(),
+            is_public: false,
+        };
+        let (dist, parsed) = parse_unset_value(&unset_value(&channel("C03"), &tag)).unwrap();
+        assert_eq!(dist, channel("C03"));
+        assert_eq!(parsed, tag);
+        assert!(parse_unset_value("user:major").is_err());
+    }
+
+    #[test]
+    fn build_home_test() {
+        let blocks = home_blocks(&[
+            config("major", "U01", &["C03"]),
+            config("minor", "U02", &["C04"]),
+            config("news", PUBLIC_TAGS, &["C05"]),
+        ]);
+        // header, the tag, its dist, its buttons, a divider, and the same for the public tag
+        assert_eq!(blocks.len(), 10);
+
+        let actions = blocks
+            .iter()
+            .filter_map(accessory_action)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                (EDIT_ACTION.to_string(), "user:major".to_string()),
+                (UNSET_ACTION.to_string(), "C03 user:major".to_string()),
+                (EDIT_ACTION.to_string(), "public:news".to_string()),
+            ]
+        );
+        // tags of other users are not shown
+        assert!(!format!("{blocks:?}").contains("minor"));
+    }
+
+    #[test]
+    fn build_home_empty_test() {
+        let blocks = home_blocks(&[]);
+        assert_eq!(blocks.len(), 4);
+        assert!(matches!(blocks[1], SlackBlock::Context(_)));
+    }
+
+    #[test]
+    fn build_home_limit_test() {
+        let configs = (0..40)
+            .map(|i| config(&format!("tag{i}"), "U01", &[]))
+            .collect::<Vec<_>>();
+        let blocks = home_blocks(&configs);
+        assert_eq!(blocks.len(), MAX_BLOCKS);
+        assert!(matches!(blocks.last(), Some(SlackBlock::Context(_))));
+    }
+}
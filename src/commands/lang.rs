@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::LangOp,
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        user_lang::Lang,
+    },
+    storage::Storage,
+};
+
+async fn set_lang(
+    storage: &dyn Storage,
+    user_id_command: &SlackUserId,
+    lang: Option<Lang>,
+) -> anyhow::Result<()> {
+    storage.set_user_lang(user_id_command, lang).await?;
+    i18n::forget_lang(user_id_command);
+    let detail = lang.map_or_else(|| "auto".to_string(), |lang| lang.to_string());
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Lang).with_detail(detail);
+    storage.record_audit(&entry).await?;
+    Ok(())
+}
+
+// The reply is already in the language chosen by the command
+pub async fn lang_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    op: &LangOp,
+) -> anyhow::Result<()> {
+    match op {
+        LangOp::Show => {}
+        LangOp::Set(lang) => set_lang(storage, &user_id_command, Some(*lang)).await?,
+        LangOp::Auto => set_lang(storage, &user_id_command, None).await?,
+    }
+    let chosen = storage.user_lang(&user_id_command).await?.is_some();
+    let lang = i18n::user_lang(&cli, storage, &user_id_command).await;
+    let msg = i18n::messages(lang);
+    let lang_text = match op {
+        LangOp::Show => msg.lang_current(lang, chosen),
+        LangOp::Set(_) => msg.lang_set(lang),
+        LangOp::Auto => msg.lang_auto(lang),
+    };
+    let _ = MessagePoster::new(channel_id_command, lang_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,618 @@
+use std::sync::Arc;
+
+use slack_morphism::{
+    prelude::{
+        SlackApiViewsOpenRequest, SlackBlock, SlackBlockChoiceItem, SlackBlockId,
+        SlackBlockMultiConversationsSelectElement, SlackBlockMultiStaticSelectElement,
+        SlackBlockPlainText, SlackBlockPlainTextInputElement, SlackBlockPlainTextOnly,
+        SlackBlockRadioButtonsElement, SlackBlockStaticSelectElement, SlackBlockText,
+        SlackHyperClient, SlackInputBlock, SlackInputBlockElement, SlackModalView, SlackView,
+        SlackViewState,
+    },
+    SlackActionId, SlackApiTokenType, SlackCallbackId, SlackChannelId, SlackTriggerId, SlackUserId,
+};
+
+use crate::{
+    command_parser::TagArg,
+    i18n::{self, Catalog},
+    process_message::sender_profile::{self, GetterProfile},
+    storage::Storage,
+    utils,
+};
+
+use super::{access, operate, set_target_tags};
+
+pub const CALLBACK_ID: &str = "manage_tags";
+
+const TAG_BLOCK: &str = "tag";
+const NEW_TAG_BLOCK: &str = "new_tag";
+const VISIBILITY_BLOCK: &str = "visibility";
+const CHANNELS_BLOCK: &str = "channels";
+const BOT_BLOCK: &str = "retrieve_bot";
+const SET_BLOCK: &str = "set_tags";
+
+const HOME_PREFIX: &str = "home:";
+
+// Slack rejects selects with more options than this
+const MAX_OPTIONS: usize = 100;
+
+// What the user asked for in the modal, checked the same way as the slash commands
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ModalSubmission {
+    pub tag: Option<TagArg>,
+    pub channels: Vec<SlackChannelId>,
+    pub retrieve_bot: Option<bool>,
+    pub set_tags: Vec<TagArg>,
+}
+
+// Why a submitted modal could not be applied. These are shown to users, so they are
+// translated through the catalog, and the English here is what is logged.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ModalError {
+    #[error("choose a tag or give a new tag name, not both")]
+    BothTags,
+    #[error("choose the tag to change, or give a new tag name")]
+    NoTag,
+    #[error("choose the channels to add or the bot setting for the tag")]
+    NoChange,
+    #[error("nothing to change")]
+    Empty,
+}
+
+// A tag offered in the modal; a tag shared with the user is shown with the name of its owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModalTag {
+    pub tag: TagArg,
+    pub owner_name: Option<String>,
+}
+
+// Where the modal was opened: from a channel the picked tags are set on it, from App Home one
+// tag is edited and the result is told in the app's DM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModalContext {
+    pub channel_id: SlackChannelId,
+    pub selected: Option<TagArg>,
+    pub from_home: bool,
+}
+
+impl ModalContext {
+    // kept in private_metadata, as `C01` or `home:D01`
+    pub fn metadata(&self) -> String {
+        if self.from_home {
+            format!("{HOME_PREFIX}{}", self.channel_id)
+        } else {
+            self.channel_id.to_string()
+        }
+    }
+
+    pub fn from_metadata(metadata: &str) -> Self {
+        let (channel_id, from_home) = match metadata.strip_prefix(HOME_PREFIX) {
+            Some(channel_id) => (channel_id, true),
+            None => (metadata, false),
+        };
+        Self {
+            channel_id: SlackChannelId::new(channel_id.to_string()),
+            selected: None,
+            from_home,
+        }
+    }
+}
+
+// The value of a tag option keeps whether the tag is public, as `public:major` or `user:major`
+pub fn tag_value(tag: &TagArg) -> String {
+    let scope = if tag.is_public { "public" } else { "user" };
+    format!("{scope}:{}", tag.name)
+}
+
+pub fn parse_tag_value(value: &str) -> anyhow::Result<TagArg> {
+    let (scope, name) = value
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("invalid tag option `{value}`"))?;
+    let is_public = match scope {
+        "public" => true,
+        "user" => false,
+        _ => return Err(anyhow::anyhow!("invalid tag option `{value}`")),
+    };
+    Ok(TagArg {
+        name: name.to_string(),
+        is_public,
+    })
+}
+
+fn tag_option(
+    msg: &dyn Catalog,
+    modal_tag: &ModalTag,
+) -> SlackBlockChoiceItem<SlackBlockPlainTextOnly> {
+    let tag = &modal_tag.tag;
+    let text = if tag.is_public {
+        format!("{} (public)", tag.name)
+    } else if let Some(owner_name) = &modal_tag.owner_name {
+        msg.shared_tag(&tag.name, owner_name)
+    } else {
+        tag.name.clone()
+    };
+    SlackBlockChoiceItem::new(plain(&text), tag_value(tag))
+}
+
+fn choice(text: &str, value: &str) -> SlackBlockChoiceItem<SlackBlockText> {
+    SlackBlockChoiceItem::new(plain(text).into(), value.to_string())
+}
+
+fn input(block_id: &str, label: &str, element: SlackInputBlockElement) -> SlackBlock {
+    SlackBlock::Input(
+        SlackInputBlock::new(plain(label), element)
+            .with_block_id(SlackBlockId::new(block_id.to_string()))
+            .with_optional(true),
+    )
+}
+
+fn action_id(block_id: &str) -> SlackActionId {
+    SlackActionId::new(block_id.to_string())
+}
+
+fn plain(text: &str) -> SlackBlockPlainTextOnly {
+    SlackBlockPlainText::new(text.to_string()).into()
+}
+
+// The modal lists the user's own tags, the tags the user may edit through a share and the
+// public tags; the context is kept in private_metadata, to tell the user the result in its channel
+pub fn build_modal(msg: &dyn Catalog, tags: &[ModalTag], context: &ModalContext) -> SlackView {
+    let options = tags
+        .iter()
+        .take(MAX_OPTIONS)
+        .map(|tag| tag_option(msg, tag))
+        .collect::<Vec<_>>();
+    // the initial option has to be one of the options
+    let initial = context.selected.as_ref().and_then(|selected| {
+        let value = tag_value(selected);
+        options.iter().find(|option| option.value == value).cloned()
+    });
+
+    let mut blocks = Vec::new();
+    // a select without options is rejected, so the tag pickers need some tags
+    if !options.is_empty() {
+        let mut select = SlackBlockStaticSelectElement::new(action_id(TAG_BLOCK))
+            .with_placeholder(plain(msg.pick_tag()))
+            .with_options(options.clone());
+        select.initial_option = initial;
+        blocks.push(input(TAG_BLOCK, msg.tag_to_change(), select.into()));
+    }
+    blocks.push(input(
+        NEW_TAG_BLOCK,
+        msg.new_tag(),
+        SlackBlockPlainTextInputElement::new(action_id(NEW_TAG_BLOCK))
+            .with_placeholder(plain(msg.new_tag_placeholder()))
+            .into(),
+    ));
+    let private = choice(msg.private_tag(), "user");
+    blocks.push(input(
+        VISIBILITY_BLOCK,
+        msg.visibility(),
+        SlackBlockRadioButtonsElement::new(
+            action_id(VISIBILITY_BLOCK),
+            vec![private.clone(), choice(msg.public_tag(), "public")],
+        )
+        .with_initial_option(private)
+        .into(),
+    ));
+    blocks.push(input(
+        CHANNELS_BLOCK,
+        msg.channels_to_add(),
+        SlackBlockMultiConversationsSelectElement::new(action_id(CHANNELS_BLOCK))
+            .with_placeholder(plain(msg.pick_channels()))
+            .into(),
+    ));
+    let keep = choice(msg.keep(), "keep");
+    blocks.push(input(
+        BOT_BLOCK,
+        msg.bot_messages(),
+        SlackBlockRadioButtonsElement::new(
+            action_id(BOT_BLOCK),
+            vec![
+                keep.clone(),
+                choice(msg.collect(), "true"),
+                choice(msg.ignore(), "false"),
+            ],
+        )
+        .with_initial_option(keep)
+        .into(),
+    ));
+    if !context.from_home && !options.is_empty() {
+        blocks.push(input(
+            SET_BLOCK,
+            msg.tags_to_set(),
+            SlackBlockMultiStaticSelectElement::new(action_id(SET_BLOCK))
+                .with_placeholder(plain(msg.pick_tag()))
+                .with_options(options)
+                .into(),
+        ));
+    }
+
+    SlackView::Modal(
+        SlackModalView::new(plain("Channel Bugyo"), blocks)
+            .with_submit(plain(msg.save()))
+            .with_close(plain(msg.cancel()))
+            .with_callback_id(SlackCallbackId::new(CALLBACK_ID.to_string()))
+            .with_private_metadata(context.metadata()),
+    )
+}
+
+pub async fn open_modal(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    trigger_id: SlackTriggerId,
+    user_id_command: SlackUserId,
+    context: &ModalContext,
+) -> anyhow::Result<()> {
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let modal_tag = |name, is_public, owner_name| ModalTag {
+        tag: TagArg { name, is_public },
+        owner_name,
+    };
+    let mut tags = storage
+        .tag_list_user(user_id_command.clone())
+        .await?
+        .into_iter()
+        .map(|name| modal_tag(name, false, None))
+        .collect::<Vec<_>>();
+    // shared tags are picked by name like the user's own, and the commands find their owners
+    for (name, owner_id) in access::editable_shares(&cli, storage, &user_id_command).await? {
+        let owner_name = owner_name(&cli, &owner_id).await;
+        tags.push(modal_tag(name, false, Some(owner_name)));
+    }
+    tags.extend(
+        storage
+            .tag_list_public()
+            .await?
+            .into_iter()
+            .map(|name| modal_tag(name, true, None)),
+    );
+
+    let token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&token);
+    let req = SlackApiViewsOpenRequest::new(trigger_id, build_modal(msg, &tags, context));
+    session.views_open(&req).await?;
+    Ok(())
+}
+
+// A select shows plain text, where mentions are not expanded, so the owner is shown by name
+async fn owner_name(cli: &Arc<SlackHyperClient>, owner_id: &SlackUserId) -> String {
+    sender_profile::fetch_user_profile(cli.clone(), owner_id.clone())
+        .await
+        .and_then(|profile| profile.get_display_name())
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| owner_id.to_string())
+}
+
+// Read the submitted values; every input is optional, so the combinations are checked here
+pub fn parse_submission(state: &SlackViewState) -> anyhow::Result<ModalSubmission> {
+    let value = |block_id: &str| {
+        state
+            .values
+            .get(&SlackBlockId::new(block_id.to_string()))
+            .and_then(|actions| actions.get(&action_id(block_id)))
+    };
+
+    let selected_tag = value(TAG_BLOCK)
+        .and_then(|v| v.selected_option.as_ref())
+        .map(|option| parse_tag_value(&option.value))
+        .transpose()?;
+    let new_tag = value(NEW_TAG_BLOCK)
+        .and_then(|v| v.value.as_deref())
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    let is_public = value(VISIBILITY_BLOCK)
+        .and_then(|v| v.selected_option.as_ref())
+        .is_some_and(|option| option.value == "public");
+    let tag = match (selected_tag, new_tag) {
+        (Some(_), Some(_)) => return Err(ModalError::BothTags.into()),
+        (Some(tag), None) => Some(tag),
+        (None, Some(name)) => Some(TagArg {
+            name: name.to_string(),
+            is_public,
+        }),
+        (None, None) => None,
+    };
+
+    let channels = value(CHANNELS_BLOCK)
+        .and_then(|v| v.selected_conversations.as_ref())
+        .map(|conversations| {
+            conversations
+                .iter()
+                .map(|id| SlackChannelId::new(id.0.clone()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let retrieve_bot = match value(BOT_BLOCK)
+        .and_then(|v| v.selected_option.as_ref())
+        .map(|option| option.value.as_str())
+    {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None,
+    };
+    let set_tags = value(SET_BLOCK)
+        .and_then(|v| v.selected_options.as_ref())
+        .map(|options| {
+            options
+                .iter()
+                .map(|option| parse_tag_value(&option.value))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    if tag.is_none() && (!channels.is_empty() || retrieve_bot.is_some()) {
+        return Err(ModalError::NoTag.into());
+    }
+    let submission = ModalSubmission {
+        tag,
+        channels,
+        retrieve_bot,
+        set_tags,
+    };
+    if submission.tag.is_some()
+        && submission.channels.is_empty()
+        && submission.retrieve_bot.is_none()
+    {
+        return Err(ModalError::NoChange.into());
+    }
+    if submission == ModalSubmission::default() {
+        return Err(ModalError::Empty.into());
+    }
+    Ok(submission)
+}
+
+// The same commands as the slash commands run, so the checks, the audit log and the replies match
+pub async fn apply_submission(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id: SlackChannelId,
+    user_id: SlackUserId,
+    submission: &ModalSubmission,
+) -> anyhow::Result<()> {
+    if let Some(tag) = &submission.tag {
+        if !submission.channels.is_empty() {
+            operate::add_command(
+                cli.clone(),
+                storage,
+                channel_id.clone(),
+                user_id.clone(),
+                tag,
+                &submission.channels,
+            )
+            .await?;
+        }
+        if let Some(retrieve_bot) = submission.retrieve_bot {
+            operate::retreieve_bot_command(
+                cli.clone(),
+                storage,
+                channel_id.clone(),
+                user_id.clone(),
+                tag,
+                retrieve_bot,
+            )
+            .await?;
+        }
+    }
+
+    for is_public in [false, true] {
+        let tags = submission
+            .set_tags
+            .iter()
+            .filter(|tag| tag.is_public == is_public)
+            .map(|tag| tag.name.clone())
+            .collect::<Vec<_>>();
+        if !tags.is_empty() {
+            set_target_tags::set_command(
+                cli.clone(),
+                storage,
+                channel_id.clone(),
+                user_id.clone(),
+                &tags,
+                is_public,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::user_lang::Lang;
+
+    use super::*;
+
+    fn state(values: &serde_json::Value) -> SlackViewState {
+        serde_json::from_value(serde_json::json!({ "values": values })).unwrap()
+    }
+
+    fn tag(name: &str, is_public: bool) -> TagArg {
+        TagArg {
+            name: name.to_string(),
+            is_public,
+        }
+    }
+
+    #[test]
+    fn tag_value_test() {
+        for tag in [tag("major", false), tag("release: notes", true)] {
+            assert_eq!(parse_tag_value(&tag_value(&tag)).unwrap(), tag);
+        }
+        assert!(parse_tag_value("major").is_err());
+        assert!(parse_tag_value("team:major").is_err());
+    }
+
+    fn listed(name: &str, is_public: bool, owner_name: Option<&str>) -> ModalTag {
+        ModalTag {
+            tag: tag(name, is_public),
+            owner_name: owner_name.map(ToString::to_string),
+        }
+    }
+
+    fn ja() -> &'static dyn Catalog {
+        i18n::messages(Lang::Ja)
+    }
+
+    fn context(selected: Option<TagArg>, from_home: bool) -> ModalContext {
+        ModalContext {
+            channel_id: SlackChannelId::new("C01".to_string()),
+            selected,
+            from_home,
+        }
+    }
+
+    #[test]
+    fn metadata_test() {
+        for from_home in [false, true] {
+            let context = context(None, from_home);
+            assert_eq!(ModalContext::from_metadata(&context.metadata()), context);
+        }
+        assert_eq!(context(None, true).metadata(), "home:C01");
+    }
+
+    #[test]
+    fn build_modal_test() {
+        let SlackView::Modal(modal) =
+            build_modal(ja(), &[listed("major", false, None)], &context(None, false))
+        else {
+            panic!("the view should be a modal");
+        };
+        assert_eq!(modal.blocks.len(), 6);
+        assert_eq!(modal.private_metadata, Some("C01".to_string()));
+
+        // the tag pickers are left out when there are no tags to pick
+        let SlackView::Modal(modal) = build_modal(ja(), &[], &context(None, false)) else {
+            panic!("the view should be a modal");
+        };
+        assert_eq!(modal.blocks.len(), 4);
+    }
+
+    #[test]
+    fn build_modal_selected_test() {
+        let tags = [
+            listed("major", false, None),
+            listed("report", false, Some("Alice")),
+            listed("news", true, None),
+        ];
+        let SlackView::Modal(modal) =
+            build_modal(ja(), &tags, &context(Some(tag("news", true)), true))
+        else {
+            panic!("the view should be a modal");
+        };
+        // no tags are set from App Home
+        assert_eq!(modal.blocks.len(), 5);
+        let SlackBlock::Input(block) = &modal.blocks[0] else {
+            panic!("the first block should be the tag picker");
+        };
+        let SlackInputBlockElement::StaticSelect(select) = &block.element else {
+            panic!("the tag picker should be a static select");
+        };
+        assert_eq!(
+            select
+                .initial_option
+                .as_ref()
+                .map(|option| option.value.as_str()),
+            Some("public:news")
+        );
+
+        // a tag that is not listed is not selected
+        let SlackView::Modal(modal) =
+            build_modal(ja(), &tags, &context(Some(tag("minor", false)), true))
+        else {
+            panic!("the view should be a modal");
+        };
+        let SlackBlock::Input(block) = &modal.blocks[0] else {
+            panic!("the first block should be the tag picker");
+        };
+        let SlackInputBlockElement::StaticSelect(select) = &block.element else {
+            panic!("the tag picker should be a static select");
+        };
+        assert_eq!(select.initial_option, None);
+
+        // a shared tag is shown with its owner, and picked by its name like the user's own
+        let SlackView::Modal(modal) =
+            build_modal(ja(), &tags, &context(Some(tag("report", false)), true))
+        else {
+            panic!("the view should be a modal");
+        };
+        let SlackBlock::Input(block) = &modal.blocks[0] else {
+            panic!("the first block should be the tag picker");
+        };
+        let SlackInputBlockElement::StaticSelect(select) = &block.element else {
+            panic!("the tag picker should be a static select");
+        };
+        assert_eq!(
+            select.initial_option,
+            Some(SlackBlockChoiceItem::new(
+                plain("report (Alice さんが共有)"),
+                "user:report".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_submission_test() {
+        let submission = parse_submission(&state(&serde_json::json!({
+            "tag": { "tag": { "type": "static_select",
+                "selected_option": { "text": { "type": "plain_text", "text": "major" }, "value": "user:major" } } },
+            "new_tag": { "new_tag": { "type": "plain_text_input", "value": null } },
+            "channels": { "channels": { "type": "multi_conversations_select", "selected_conversations": ["C01", "C02"] } },
+            "retrieve_bot": { "retrieve_bot": { "type": "radio_buttons",
+                "selected_option": { "text": { "type": "plain_text", "text": "収集する" }, "value": "true" } } },
+            "set_tags": { "set_tags": { "type": "multi_static_select", "selected_options": [
+                { "text": { "type": "plain_text", "text": "news (public)" }, "value": "public:news" }] } }
+        })))
+        .unwrap();
+        assert_eq!(
+            submission,
+            ModalSubmission {
+                tag: Some(tag("major", false)),
+                channels: vec![
+                    SlackChannelId::new("C01".to_string()),
+                    SlackChannelId::new("C02".to_string())
+                ],
+                retrieve_bot: Some(true),
+                set_tags: vec![tag("news", true)],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_new_tag_test() {
+        let submission = parse_submission(&state(&serde_json::json!({
+            "new_tag": { "new_tag": { "type": "plain_text_input", "value": " release notes " } },
+            "visibility": { "visibility": { "type": "radio_buttons",
+                "selected_option": { "text": { "type": "plain_text", "text": "パブリックタグ" }, "value": "public" } } },
+            "channels": { "channels": { "type": "multi_conversations_select", "selected_conversations": ["C01"] } }
+        })))
+        .unwrap();
+        assert_eq!(submission.tag, Some(tag("release notes", true)));
+        assert_eq!(submission.retrieve_bot, None);
+    }
+
+    #[test]
+    fn parse_submission_error_test() {
+        // channels without a tag
+        assert!(parse_submission(&state(&serde_json::json!({
+            "channels": { "channels": { "type": "multi_conversations_select", "selected_conversations": ["C01"] } }
+        })))
+        .is_err());
+        // a tag without anything to change
+        assert!(parse_submission(&state(&serde_json::json!({
+            "new_tag": { "new_tag": { "type": "plain_text_input", "value": "major" } }
+        })))
+        .is_err());
+        // both a picked tag and a new tag
+        assert!(parse_submission(&state(&serde_json::json!({
+            "tag": { "tag": { "type": "static_select",
+                "selected_option": { "text": { "type": "plain_text", "text": "major" }, "value": "user:major" } } },
+            "new_tag": { "new_tag": { "type": "plain_text_input", "value": "minor" } },
+            "channels": { "channels": { "type": "multi_conversations_select", "selected_conversations": ["C01"] } }
+        })))
+        .is_err());
+        assert!(parse_submission(&state(&serde_json::json!({}))).is_err());
+    }
+}
@@ -1,63 +1,100 @@
-use std::{str::SplitWhitespace, sync::Arc};
+use std::sync::Arc;
 
-use anyhow::Context;
 use futures::{StreamExt, TryStreamExt};
 use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
 
-use crate::{post_message::MessagePoster, query::user_folder, utils};
+use crate::{
+    command_parser::TagArg,
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        tag_member::TagRole,
+        thread_mode::ThreadMode,
+    },
+    response,
+    storage::{Storage, TagError},
+};
+
+use super::access::{self, AccessError, OwnerAction};
 
 async fn operate_channel_list(
+    storage: &dyn Storage,
     channel_id: SlackChannelId,
     owner_id: SlackUserId,
     register: bool,
     tag: String,
 ) -> anyhow::Result<()> {
     if register {
-        user_folder::register_channel(&tag, channel_id, owner_id).await?;
+        storage.register_channel(&tag, channel_id, owner_id).await?;
     } else {
-        user_folder::unregister_channel(&tag, channel_id, owner_id).await?;
+        storage
+            .unregister_channel(&tag, channel_id, owner_id)
+            .await?;
     }
 
     Ok(())
 }
 
 async fn operate_ch_args(
-    ch_list: SplitWhitespace<'_>,
+    storage: &dyn Storage,
+    channels: &[SlackChannelId],
     owner_id: SlackUserId,
     register: bool,
     tag: String,
 ) -> anyhow::Result<()> {
-    let ch_id_list = ch_list.map(utils::channel_preprocess);
-    let channel_stream = futures::stream::iter(ch_id_list);
+    let channel_stream = futures::stream::iter(channels);
     channel_stream
         .map(|channel_id| async {
-            operate_channel_list(channel_id?, owner_id.clone(), register, tag.clone()).await
+            operate_channel_list(
+                storage,
+                channel_id.clone(),
+                owner_id.clone(),
+                register,
+                tag.clone(),
+            )
+            .await
         })
         .then(|s| s)
-        .try_collect()
+        .try_collect::<()>()
         .await?;
     Ok(())
 }
 
 pub async fn add_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    tag: &TagArg,
+    channels: &[SlackChannelId],
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (tag, owner_add) = match first_arg {
-        "--public" => {
-            let tag = args_iter.next().context("argument error")?;
-            (tag, SlackUserId::new(super::PUBLIC_TAGS.to_string()))
-        }
-        tag => (tag, user_id_command.clone()),
-    };
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_add = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
 
-    let channels = args_iter.clone().collect::<Vec<_>>();
+    let is_new_public = is_public && !storage.is_valid_tag_for_user(&owner_add, tag).await?;
 
-    operate_ch_args(args_iter, owner_add, true, tag.to_string()).await?;
-    let add_text = format!("タグ {tag} に {channels:#?} が追加されました");
+    operate_ch_args(storage, channels, owner_add.clone(), true, tag.to_string()).await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Add)
+        .with_tag(tag, owner_add.clone())
+        .with_channels(channels.to_vec());
+    storage.record_audit(&entry).await?;
+    // the creator of a public tag may change it later without admin rights
+    if is_new_public {
+        storage
+            .share_tag(tag, owner_add, &user_id_command.0, TagRole::Editor)
+            .await?;
+    }
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let add_text = msg.channels_added(&response::escape(tag), &response::mentions(msg, channels));
     let _ = MessagePoster::new(channel_id_command, add_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
@@ -67,58 +104,350 @@ pub async fn add_command(
 
 pub async fn delete_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    tag: &TagArg,
+    channels: &[SlackChannelId],
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (tag, owner_id) = match first_arg {
-        "--public" => {
-            let tag = args_iter.next().context("argument error")?;
-            (tag, SlackUserId::new(super::PUBLIC_TAGS.to_string()))
-        }
-        tag => (tag, user_id_command.clone()),
-    };
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
 
-    let channels = args_iter.clone().collect::<Vec<_>>();
+    operate_ch_args(storage, channels, owner_id.clone(), false, tag.to_string()).await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Delete)
+        .with_tag(tag, owner_id)
+        .with_channels(channels.to_vec());
+    storage.record_audit(&entry).await?;
 
-    operate_ch_args(args_iter, owner_id, false, tag.to_string()).await?;
-
-    let delete_text = format!("タグ {tag} から {channels:#?} が削除されました");
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let delete_text =
+        msg.channels_deleted(&response::escape(tag), &response::mentions(msg, channels));
     let _ = MessagePoster::new(channel_id_command, delete_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
     Ok(())
 }
-pub async fn retreieve_bot_command(
+// Delete the whole tag, after the user confirms what is lost with it
+pub async fn drop_tag_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    tag: &TagArg,
+    confirmed: bool,
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (tag, owner_id) = match first_arg {
-        "--public" => {
-            let tag = args_iter.next().context("argument error")?;
-            (tag, SlackUserId::new(super::PUBLIC_TAGS.to_string()))
-        }
-        tag => (tag, user_id_command.clone()),
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
+    // editors may change a shared tag, but only its owner may delete it
+    if !is_public && owner_id != user_id_command {
+        return Err(AccessError::OwnerOnly(OwnerAction::Drop).into());
+    }
+    let config = storage
+        .tag_config_list()
+        .await?
+        .into_iter()
+        .find(|config| config.tag_name == tag && config.owner_id == owner_id)
+        .ok_or_else(|| TagError::NotFound(tag.to_string()))?;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let public_arg = if is_public { "--public " } else { "" };
+    let drop_text = if confirmed {
+        let tag_id = storage.drop_tag(tag, owner_id.clone()).await?;
+        let entry = AuditEntry::new(user_id_command.clone(), AuditAction::DropTag)
+            .with_tag(tag, owner_id)
+            .with_tag_id(tag_id)
+            .with_channels(config.channels);
+        storage.record_audit(&entry).await?;
+        msg.tag_dropped(&response::escape(tag))
+    } else {
+        let command = format!("/channel_bugyo drop_tag {public_arg}{tag} --confirm");
+        format!(
+            "{}\n{}",
+            msg.drop_warning(
+                &response::escape(tag),
+                config.channels.len(),
+                config.dists.len()
+            ),
+            msg.drop_how_to(&response::escape(&command))
+        )
     };
+    let _ = MessagePoster::new(channel_id_command, drop_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
 
-    let do_retrieve_bot_str = args_iter.next().context("argument error")?;
-    let do_retrieve_bot = match do_retrieve_bot_str {
-        "true" => Ok(true),
-        "false" => Ok(false),
-        _ => Err(anyhow::anyhow!("argument should be true or false")),
-    }?;
+pub async fn retreieve_bot_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    do_retrieve_bot: bool,
+) -> anyhow::Result<()> {
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
 
-    user_folder::retrieve_bot(tag, owner_id, do_retrieve_bot).await?;
+    storage
+        .retrieve_bot(tag, owner_id.clone(), do_retrieve_bot)
+        .await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::RetrieveBot)
+        .with_tag(tag, owner_id)
+        .with_detail(do_retrieve_bot.to_string());
+    storage.record_audit(&entry).await?;
 
-    let retrieve_or_ignore = if do_retrieve_bot { "収集" } else { "無視" };
-    let retreieve_bot_text =
-        format!("以降、このタグはボットによるメッセージを{retrieve_or_ignore}します。");
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let retreieve_bot_text = msg.retrieve_bot(do_retrieve_bot).to_string();
     let _ = MessagePoster::new(channel_id_command, retreieve_bot_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
     Ok(())
 }
+
+pub async fn retrieve_file_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    do_retrieve_file: bool,
+) -> anyhow::Result<()> {
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
+
+    storage
+        .retrieve_file(tag, owner_id.clone(), do_retrieve_file)
+        .await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::RetrieveFile)
+        .with_tag(tag, owner_id)
+        .with_detail(do_retrieve_file.to_string());
+    storage.record_audit(&entry).await?;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let retrieve_file_text = msg.retrieve_file(do_retrieve_file).to_string();
+    let _ = MessagePoster::new(channel_id_command, retrieve_file_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
+
+pub async fn thread_mode_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    thread_mode: ThreadMode,
+) -> anyhow::Result<()> {
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
+
+    storage
+        .thread_mode(tag, owner_id.clone(), thread_mode)
+        .await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Thread)
+        .with_tag(tag, owner_id)
+        .with_detail(thread_mode.to_string());
+    storage.record_audit(&entry).await?;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let thread_text = msg.thread_mode(&response::escape(tag), thread_mode);
+    let _ = MessagePoster::new(channel_id_command, thread_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
+
+// What is done with a tag and another tag of the same owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagPairOp {
+    Rename,
+    Copy,
+    // the channels of the tag are added to the other tag, and the tag is deleted
+    Merge,
+}
+
+impl TagPairOp {
+    fn audit_action(self) -> AuditAction {
+        match self {
+            TagPairOp::Rename => AuditAction::Rename,
+            TagPairOp::Copy => AuditAction::Copy,
+            TagPairOp::Merge => AuditAction::Merge,
+        }
+    }
+
+    fn owner_action(self) -> OwnerAction {
+        match self {
+            TagPairOp::Rename => OwnerAction::Rename,
+            TagPairOp::Copy => OwnerAction::Copy,
+            TagPairOp::Merge => OwnerAction::Merge,
+        }
+    }
+}
+
+// rename, copy and merge take a tag and another tag of the same owner
+async fn tag_pair_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    other: &str,
+    op: TagPairOp,
+) -> anyhow::Result<()> {
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(
+        &cli,
+        storage,
+        &user_id_command,
+        tag,
+        is_public,
+        TagRole::Editor,
+    )
+    .await?;
+    // editors may change a shared tag, but only its owner may rename, copy or merge it
+    if !is_public && owner_id != user_id_command {
+        return Err(AccessError::OwnerOnly(op.owner_action()).into());
+    }
+    // the other tag is in the same namespace, so it is the user's own unless both are public,
+    // and a tag of the same name shared with the user is not looked at
+    if is_public {
+        access::tag_owner(
+            &cli,
+            storage,
+            &user_id_command,
+            other,
+            is_public,
+            TagRole::Editor,
+        )
+        .await?;
+    }
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let mut entry = AuditEntry::new(user_id_command.clone(), op.audit_action())
+        .with_tag(tag, owner_id.clone())
+        .with_detail(other);
+    let (tag_text, other_text) = (response::escape(tag), response::escape(other));
+    let pair_text = match op {
+        TagPairOp::Rename => {
+            storage.rename_tag(tag, other, owner_id).await?;
+            msg.tag_renamed(&tag_text, &other_text)
+        }
+        TagPairOp::Copy => {
+            storage.copy_tag(tag, other, owner_id).await?;
+            msg.tag_copied(&tag_text, &other_text)
+        }
+        TagPairOp::Merge => {
+            // the merged tag is gone, so its entry is kept by the id it had
+            let tag_id = storage.merge_tag(tag, other, owner_id).await?;
+            entry = entry.with_tag_id(tag_id);
+            msg.tag_merged(&tag_text, &other_text)
+        }
+    };
+    storage.record_audit(&entry).await?;
+
+    let _ = MessagePoster::new(channel_id_command, pair_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
+
+pub async fn rename_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    other: &str,
+) -> anyhow::Result<()> {
+    tag_pair_command(
+        cli,
+        storage,
+        channel_id_command,
+        user_id_command,
+        tag,
+        other,
+        TagPairOp::Rename,
+    )
+    .await
+}
+
+pub async fn copy_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    other: &str,
+) -> anyhow::Result<()> {
+    tag_pair_command(
+        cli,
+        storage,
+        channel_id_command,
+        user_id_command,
+        tag,
+        other,
+        TagPairOp::Copy,
+    )
+    .await
+}
+
+pub async fn merge_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    other: &str,
+) -> anyhow::Result<()> {
+    tag_pair_command(
+        cli,
+        storage,
+        channel_id_command,
+        user_id_command,
+        tag,
+        other,
+        TagPairOp::Merge,
+    )
+    .await
+}
@@ -0,0 +1,62 @@
+use std::{fmt::Write, sync::Arc};
+
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        delivery::{self, TimeZone},
+    },
+    storage::Storage,
+};
+
+pub async fn quiet_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    quiet_hours: Option<(i64, i64)>,
+    time_zone: Option<&TimeZone>,
+) -> anyhow::Result<()> {
+    let mut detail = match quiet_hours {
+        None => "off".to_string(),
+        Some((start, end)) => format!(
+            "{} {}",
+            delivery::format_time_of_day(start),
+            delivery::format_time_of_day(end)
+        ),
+    };
+    if let Some(time_zone) = time_zone {
+        let _ = write!(detail, " {time_zone}");
+    }
+
+    storage
+        .set_quiet_hours(&channel_id_command, quiet_hours, time_zone)
+        .await?;
+    let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Quiet)
+        .with_channels(vec![channel_id_command.clone()])
+        .with_detail(detail);
+    storage.record_audit(&entry).await?;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let quiet_text = match quiet_hours {
+        None => msg.quiet_off().to_string(),
+        Some((start, end)) => {
+            let time_zone = storage
+                .schedule_list()
+                .await?
+                .remove(&channel_id_command)
+                .unwrap_or_default()
+                .time_zone;
+            let start = delivery::format_time_of_day(start);
+            let end = delivery::format_time_of_day(end);
+            msg.quiet_hours(&start, &end, &time_zone.to_string())
+        }
+    };
+    let _ = MessagePoster::new(channel_id_command, quiet_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
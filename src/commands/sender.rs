@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::{SenderOp, TagArg},
+    i18n,
+    post_message::MessagePoster,
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        sender_rule::SenderRule,
+        tag_member::TagRole,
+    },
+    response,
+    storage::Storage,
+};
+
+use super::access;
+
+pub async fn sender_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    op: &SenderOp,
+) -> anyhow::Result<()> {
+    // listing only needs the tag shared as a viewer
+    let role = if *op == SenderOp::List {
+        TagRole::Viewer
+    } else {
+        TagRole::Editor
+    };
+
+    let (tag, is_public) = (tag.name.as_str(), tag.is_public);
+    let owner_id = access::tag_owner(&cli, storage, &user_id_command, tag, is_public, role).await?;
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let sender_text = match op {
+        SenderOp::Add(rules) => {
+            let rules = operate_rule_args(storage, rules, tag, owner_id.clone(), true).await?;
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::SenderAdd)
+                .with_tag(tag, owner_id)
+                .with_detail(rules.join(", "));
+            storage.record_audit(&entry).await?;
+            msg.rules_added(&response::escape(tag), &response::names(msg, &rules))
+        }
+        SenderOp::Remove(rules) => {
+            let rules = operate_rule_args(storage, rules, tag, owner_id.clone(), false).await?;
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::SenderRemove)
+                .with_tag(tag, owner_id)
+                .with_detail(rules.join(", "));
+            storage.record_audit(&entry).await?;
+            msg.rules_removed(&response::escape(tag), &response::names(msg, &rules))
+        }
+        SenderOp::List => {
+            let rules = storage
+                .rule_list(tag, owner_id)
+                .await?
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            msg.rule_list(&response::escape(tag), &response::names(msg, &rules))
+        }
+    };
+
+    let _ = MessagePoster::new(channel_id_command, sender_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
+
+async fn operate_rule_args(
+    storage: &dyn Storage,
+    rules: &[SenderRule],
+    tag: &str,
+    owner_id: SlackUserId,
+    add: bool,
+) -> anyhow::Result<Vec<String>> {
+    let rule_stream = futures::stream::iter(rules);
+    rule_stream
+        .map(|rule| async {
+            if add {
+                storage.add_rule(tag, owner_id.clone(), rule).await
+            } else {
+                storage.remove_rule(tag, owner_id.clone(), rule).await
+            }
+        })
+        .then(|s| s)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let rule_names = rules.iter().map(ToString::to_string).collect::<Vec<_>>();
+    Ok(rule_names)
+}
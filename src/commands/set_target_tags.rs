@@ -1,30 +1,44 @@
-use std::{str::SplitWhitespace, sync::Arc};
+use std::sync::Arc;
 
-use anyhow::Context;
 use futures::{StreamExt, TryStreamExt};
 
 use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
 
 use crate::{
+    i18n,
     post_message::MessagePoster,
-    query::{dist, user_folder::is_valid_tag_for_user},
+    query::{
+        audit_log::{AuditAction, AuditEntry},
+        tag_member::TagRole,
+    },
+    response,
+    storage::{Storage, TagError},
 };
 
+use super::access;
+
 pub async fn set_targets(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id: &SlackChannelId,
-    user_command: SlackUserId,
+    user_command: &SlackUserId,
+    is_public: bool,
     tags: &[String],
     set: bool,
 ) -> anyhow::Result<Vec<String>> {
     let tags_stream = futures::stream::iter(tags);
 
+    // each tag may be the user's own or shared with the user by its owner
     let authed_tags = tags_stream
         .map(|tag| async {
-            let is_valid = is_valid_tag_for_user(&user_command, tag).await?;
+            let owner_id =
+                access::tag_owner(cli, storage, user_command, tag, is_public, TagRole::Viewer)
+                    .await?;
+            let is_valid = storage.is_valid_tag_for_user(&owner_id, tag).await?;
             if is_valid {
-                anyhow::Ok(tag.clone())
+                anyhow::Ok((tag.clone(), owner_id))
             } else {
-                Err(anyhow::anyhow!("the tag does not exist"))
+                Err(TagError::NotFound(tag.clone()).into())
             }
         })
         .then(|s| s)
@@ -34,12 +48,22 @@ pub async fn set_targets(
     let authed_tags_stream = futures::stream::iter(authed_tags.iter());
 
     let tags = authed_tags_stream
-        .map(|tag| async {
-            if set {
-                dist::add_tag(channel_id.clone(), user_command.clone(), tag).await?;
+        .map(|(tag, owner_id)| async {
+            let action = if set {
+                storage
+                    .add_tag(channel_id.clone(), owner_id.clone(), tag)
+                    .await?;
+                AuditAction::Set
             } else {
-                dist::remove_tag(channel_id.clone(), user_command.clone(), tag).await?;
+                storage
+                    .remove_tag(channel_id.clone(), owner_id.clone(), tag)
+                    .await?;
+                AuditAction::Unset
             };
+            let entry = AuditEntry::new(user_command.clone(), action)
+                .with_tag(tag, owner_id.clone())
+                .with_channels(vec![channel_id.clone()]);
+            storage.record_audit(&entry).await?;
             anyhow::Ok(tag.clone())
         })
         .then(|s| s)
@@ -51,27 +75,24 @@ pub async fn set_targets(
 
 pub async fn set_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    tags: &[String],
+    is_public: bool,
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (head_tag, owner_id) = match first_arg {
-        "--public" => (None, SlackUserId::new(super::PUBLIC_TAGS.to_string())),
-        head_tag => (Some(head_tag), user_id_command.clone()),
-    };
-
-    let mut tags = args_iter
-        .map(std::string::ToString::to_string)
-        .collect::<Vec<String>>();
-    if let Some(head) = head_tag {
-        tags.insert(0, head.to_string());
-    };
-
-    let set_tags = set_targets(&channel_id_command, owner_id, &tags, true).await?;
-    let set_text = format!(
-        "以降、本チャンネルは以下のタグに登録されたチャンネルのメッセージを収集します。{set_tags:#?}"
-    );
+    let set_tags = set_targets(
+        &cli,
+        storage,
+        &channel_id_command,
+        &user_id_command,
+        is_public,
+        tags,
+        true,
+    )
+    .await?;
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let set_text = msg.tags_set(&response::names(msg, &set_tags));
     let _ = MessagePoster::new(channel_id_command, set_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
@@ -80,26 +101,24 @@ pub async fn set_command(
 
 pub async fn unset_command(
     cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
     channel_id_command: SlackChannelId,
     user_id_command: SlackUserId,
-    mut args_iter: SplitWhitespace<'_>,
+    tags: &[String],
+    is_public: bool,
 ) -> anyhow::Result<()> {
-    let first_arg = args_iter.next().context("argument error")?;
-    let (head_tag, owner_id) = match first_arg {
-        "--public" => (None, SlackUserId::new(super::PUBLIC_TAGS.to_string())),
-        head_tag => (Some(head_tag), user_id_command.clone()),
-    };
-
-    let mut tags = args_iter
-        .map(std::string::ToString::to_string)
-        .collect::<Vec<String>>();
-    if let Some(head) = head_tag {
-        tags.insert(0, head.to_string());
-    };
-
-    let set_tags = set_targets(&channel_id_command, owner_id, &tags, false).await?;
-    let set_text =
-        format!("以下のタグに登録されたチャンネルのメッセージの収集を停止します。{set_tags:#?}");
+    let set_tags = set_targets(
+        &cli,
+        storage,
+        &channel_id_command,
+        &user_id_command,
+        is_public,
+        tags,
+        false,
+    )
+    .await?;
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let set_text = msg.tags_unset(&response::names(msg, &set_tags));
     let _ = MessagePoster::new(channel_id_command, set_text, cli)
         .post_ephemeral(user_id_command)
         .await?;
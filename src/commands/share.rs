@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use slack_morphism::{prelude::SlackHyperClient, SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::{ShareOp, TagArg},
+    i18n,
+    post_message::MessagePoster,
+    query::audit_log::{AuditAction, AuditEntry},
+    response,
+    storage::Storage,
+};
+
+use super::access::{self, AccessError, OwnerAction};
+
+fn member_name(member_id: &str) -> String {
+    if member_id.starts_with('S') {
+        format!("<!subteam^{member_id}>")
+    } else {
+        format!("<@{member_id}>")
+    }
+}
+
+pub async fn share_command(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id_command: SlackChannelId,
+    user_id_command: SlackUserId,
+    tag: &TagArg,
+    op: &ShareOp,
+) -> anyhow::Result<()> {
+    // only the owner shares a tag, and admins choose the editors of public tags
+    let owner_id = if tag.is_public {
+        if !access::is_admin(storage, &user_id_command).await? {
+            return Err(AccessError::SharePublic.into());
+        }
+        SlackUserId::new(super::PUBLIC_TAGS.to_string())
+    } else {
+        user_id_command.clone()
+    };
+    let tag = tag.name.as_str();
+    if !storage.is_valid_tag_for_user(&owner_id, tag).await? {
+        return Err(AccessError::OwnerOnly(OwnerAction::Share).into());
+    }
+
+    let msg = i18n::catalog(&cli, storage, &user_id_command).await;
+    let share_text = match op {
+        ShareOp::List => {
+            let members = storage
+                .tag_shares(tag, &owner_id)
+                .await?
+                .into_iter()
+                .map(|share| format!("{} {}", member_name(&share.member_id), share.role))
+                .collect::<Vec<_>>();
+            msg.share_list(&response::escape(tag), &response::join(msg, &members))
+        }
+        ShareOp::Share(member_id, role) => {
+            storage
+                .share_tag(tag, owner_id.clone(), member_id, *role)
+                .await?;
+            let member = member_name(member_id);
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Share)
+                .with_tag(tag, owner_id)
+                .with_detail(format!("{member} {role}"));
+            storage.record_audit(&entry).await?;
+            msg.tag_shared(&response::escape(tag), &member, *role)
+        }
+        ShareOp::Unshare(member_id) => {
+            storage
+                .unshare_tag(tag, owner_id.clone(), member_id)
+                .await?;
+            let member = member_name(member_id);
+            let entry = AuditEntry::new(user_id_command.clone(), AuditAction::Unshare)
+                .with_tag(tag, owner_id)
+                .with_detail(member.clone());
+            storage.record_audit(&entry).await?;
+            msg.tag_unshared(&response::escape(tag), &member)
+        }
+    };
+
+    let _ = MessagePoster::new(channel_id_command, share_text, cli)
+        .post_ephemeral(user_id_command)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,380 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use slack_morphism::{SlackChannelId, SlackUserId};
+
+use crate::{
+    query::{
+        audit_log::{AuditAction, AuditEntry, CLI_ACTOR},
+        tag_config::TagConfig,
+        thread_mode::ThreadMode,
+    },
+    storage::Storage,
+};
+
+const FORMAT_VERSION: u32 = 1;
+
+// The backup of tags, as JSON or YAML:
+// { "version": 1, "tags": [{ "name": "major", "owner": "U012345" or "public",
+//   "channels": ["C01"], "retrieve_bot": false, "retrieve_file": true,
+//   "thread": "mirror", "dists": ["C02"] }] }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub version: u32,
+    #[serde(default)]
+    pub tags: Vec<TagEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagEntry {
+    pub name: String,
+    pub owner: String,
+    #[serde(default)]
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub retrieve_bot: bool,
+    #[serde(default = "default_retrieve_file")]
+    pub retrieve_file: bool,
+    #[serde(default = "default_thread")]
+    pub thread: String,
+    #[serde(default)]
+    pub dists: Vec<String>,
+}
+
+fn default_retrieve_file() -> bool {
+    true
+}
+
+fn default_thread() -> String {
+    ThreadMode::Mirror.to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    // also the file type Slack highlights the file with
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+// One step that brings the database closer to the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    AddChannel(SlackChannelId),
+    RetrieveBot(bool),
+    RetrieveFile(bool),
+    Thread(ThreadMode),
+    AddDist(SlackChannelId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub tag_name: String,
+    pub owner_id: SlackUserId,
+    pub kind: ChangeKind,
+}
+
+// The changes to apply; tags without channels cannot be created, so new ones are skipped
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ImportPlan {
+    pub changes: Vec<Change>,
+    pub skipped: Vec<(String, SlackUserId)>,
+}
+
+impl ConfigFile {
+    pub fn from_configs(configs: &[TagConfig]) -> Self {
+        let tags = configs
+            .iter()
+            .map(|config| TagEntry {
+                name: config.tag_name.clone(),
+                owner: config.owner_id.to_string(),
+                channels: config.channels.iter().map(ToString::to_string).collect(),
+                retrieve_bot: config.bot,
+                retrieve_file: config.file,
+                thread: config.thread_mode.to_string(),
+                dists: config.dists.iter().map(ToString::to_string).collect(),
+            })
+            .collect();
+        ConfigFile {
+            version: FORMAT_VERSION,
+            tags,
+        }
+    }
+
+    // JSON is told apart by its opening brace, anything else is read as YAML
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let file: ConfigFile = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text)?
+        } else {
+            serde_yaml::from_str(text)?
+        };
+        if file.version != FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "version should be {FORMAT_VERSION}, but it is {}",
+                file.version
+            ));
+        }
+        Ok(file)
+    }
+
+    pub fn to_text(&self, format: Format) -> anyhow::Result<String> {
+        let text = match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Yaml => serde_yaml::to_string(self)?,
+        };
+        Ok(text)
+    }
+
+    // Compare the file with the current tags. Nothing missing from the file is removed,
+    // so applying the same file twice changes nothing the second time.
+    pub fn plan(&self, current: &[TagConfig]) -> anyhow::Result<ImportPlan> {
+        let mut plan = ImportPlan::default();
+        for entry in &self.tags {
+            let owner_id = SlackUserId::new(entry.owner.clone());
+            let thread_mode = entry.thread.parse::<ThreadMode>()?;
+            let existing = current
+                .iter()
+                .find(|config| config.tag_name == entry.name && config.owner_id == owner_id);
+            if existing.is_none() && entry.channels.is_empty() {
+                plan.skipped.push((entry.name.clone(), owner_id));
+                continue;
+            }
+
+            // a new tag starts with the defaults of user_folder
+            let (channels, bot, file, current_mode, dists) = match existing {
+                Some(config) => (
+                    config.channels.clone(),
+                    config.bot,
+                    config.file,
+                    config.thread_mode,
+                    config.dists.clone(),
+                ),
+                None => (Vec::new(), false, true, ThreadMode::Mirror, Vec::new()),
+            };
+
+            let mut kinds = Vec::new();
+            for channel in &entry.channels {
+                let channel = SlackChannelId::new(channel.clone());
+                if !channels.contains(&channel) {
+                    kinds.push(ChangeKind::AddChannel(channel));
+                }
+            }
+            if entry.retrieve_bot != bot {
+                kinds.push(ChangeKind::RetrieveBot(entry.retrieve_bot));
+            }
+            if entry.retrieve_file != file {
+                kinds.push(ChangeKind::RetrieveFile(entry.retrieve_file));
+            }
+            if thread_mode != current_mode {
+                kinds.push(ChangeKind::Thread(thread_mode));
+            }
+            for dist in &entry.dists {
+                let dist = SlackChannelId::new(dist.clone());
+                if !dists.contains(&dist) {
+                    kinds.push(ChangeKind::AddDist(dist));
+                }
+            }
+
+            plan.changes.extend(kinds.into_iter().map(|kind| Change {
+                tag_name: entry.name.clone(),
+                owner_id: owner_id.clone(),
+                kind,
+            }));
+        }
+        Ok(plan)
+    }
+}
+
+impl ImportPlan {
+    pub async fn apply(&self, storage: &dyn Storage) -> anyhow::Result<()> {
+        for change in &self.changes {
+            let tag = change.tag_name.as_str();
+            let owner_id = change.owner_id.clone();
+            match &change.kind {
+                ChangeKind::AddChannel(channel) => {
+                    storage
+                        .register_channel(tag, channel.clone(), owner_id)
+                        .await?;
+                }
+                ChangeKind::RetrieveBot(bot) => storage.retrieve_bot(tag, owner_id, *bot).await?,
+                ChangeKind::RetrieveFile(file) => {
+                    storage.retrieve_file(tag, owner_id, *file).await?;
+                }
+                ChangeKind::Thread(thread_mode) => {
+                    storage.thread_mode(tag, owner_id, *thread_mode).await?;
+                }
+                ChangeKind::AddDist(dist) => storage.add_tag(dist.clone(), owner_id, tag).await?,
+            }
+            storage.record_audit(&change.audit_entry()).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Change {
+    // Each applied change is recorded with the command it stands for, as the detail
+    fn audit_entry(&self) -> AuditEntry {
+        let (channels, detail) = match &self.kind {
+            ChangeKind::AddChannel(channel) => (vec![channel.clone()], "add".to_string()),
+            ChangeKind::RetrieveBot(bot) => (Vec::new(), format!("retrieve_bot {bot}")),
+            ChangeKind::RetrieveFile(file) => (Vec::new(), format!("retrieve_file {file}")),
+            ChangeKind::Thread(thread_mode) => (Vec::new(), format!("thread {thread_mode}")),
+            ChangeKind::AddDist(dist) => (vec![dist.clone()], "set".to_string()),
+        };
+        AuditEntry::new(SlackUserId::new(CLI_ACTOR.to_string()), AuditAction::Import)
+            .with_tag(&self.tag_name, self.owner_id.clone())
+            .with_channels(channels)
+            .with_detail(detail)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = format!("{} ({})", self.tag_name, self.owner_id);
+        match &self.kind {
+            ChangeKind::AddChannel(channel) => write!(f, "{tag}: add channel {channel}"),
+            ChangeKind::RetrieveBot(bot) => write!(f, "{tag}: retrieve_bot {bot}"),
+            ChangeKind::RetrieveFile(file) => write!(f, "{tag}: retrieve_file {file}"),
+            ChangeKind::Thread(thread_mode) => write!(f, "{tag}: thread {thread_mode}"),
+            ChangeKind::AddDist(dist) => write!(f, "{tag}: set on {dist}"),
+        }
+    }
+}
+
+impl fmt::Display for ImportPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            writeln!(f, "no changes")?;
+        }
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        for (tag_name, owner_id) in &self.skipped {
+            writeln!(
+                f,
+                "{tag_name} ({owner_id}): skipped, a new tag needs channels"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current() -> Vec<TagConfig> {
+        vec![TagConfig {
+            tag_name: "major".to_string(),
+            owner_id: SlackUserId::new("U01".to_string()),
+            bot: false,
+            file: true,
+            thread_mode: ThreadMode::Mirror,
+            channels: vec![SlackChannelId::new("C01".to_string())],
+            dists: vec![SlackChannelId::new("Cdist".to_string())],
+        }]
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let file = ConfigFile::from_configs(&current());
+        for format in [Format::Json, Format::Yaml] {
+            let text = file.to_text(format).unwrap();
+            assert_eq!(ConfigFile::parse(&text).unwrap(), file);
+        }
+        // an exported file applies no changes
+        assert!(file.plan(&current()).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn defaults_test() {
+        let text = "
+version: 1
+tags:
+  - name: major
+    owner: U01
+    channels: [C01]
+";
+        let file = ConfigFile::parse(text).unwrap();
+        assert!(file.tags[0].retrieve_file);
+        assert_eq!(file.tags[0].thread, "mirror");
+
+        assert!(ConfigFile::parse("{\"version\": 2}").is_err());
+    }
+
+    #[test]
+    fn plan_test() {
+        let text = r#"{
+            "version": 1,
+            "tags": [
+                { "name": "major", "owner": "U01", "channels": ["C01", "C02"],
+                  "retrieve_bot": true, "thread": "root", "dists": ["Cdist"] },
+                { "name": "minor", "owner": "public", "channels": ["C03"], "dists": ["Cdist"] },
+                { "name": "empty", "owner": "U01" }
+            ]
+        }"#;
+        let plan = ConfigFile::parse(text).unwrap().plan(&current()).unwrap();
+
+        let kinds = plan
+            .changes
+            .iter()
+            .map(|change| (change.tag_name.as_str(), change.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    "major",
+                    ChangeKind::AddChannel(SlackChannelId::new("C02".to_string()))
+                ),
+                ("major", ChangeKind::RetrieveBot(true)),
+                ("major", ChangeKind::Thread(ThreadMode::Root)),
+                (
+                    "minor",
+                    ChangeKind::AddChannel(SlackChannelId::new("C03".to_string()))
+                ),
+                (
+                    "minor",
+                    ChangeKind::AddDist(SlackChannelId::new("Cdist".to_string()))
+                ),
+            ]
+        );
+        assert_eq!(
+            plan.skipped,
+            vec![("empty".to_string(), SlackUserId::new("U01".to_string()))]
+        );
+
+        let bad_thread =
+            r#"{"version": 1, "tags": [{"name": "a", "owner": "U01", "thread": "all"}]}"#;
+        assert!(ConfigFile::parse(bad_thread)
+            .unwrap()
+            .plan(&current())
+            .is_err());
+    }
+
+    #[test]
+    fn audit_entry_test() {
+        let change = Change {
+            tag_name: "major".to_string(),
+            owner_id: SlackUserId::new("U01".to_string()),
+            kind: ChangeKind::AddDist(SlackChannelId::new("Cdist".to_string())),
+        };
+        let entry = change.audit_entry();
+        assert_eq!(entry.actor.0, CLI_ACTOR);
+        assert_eq!(entry.action, AuditAction::Import);
+        assert_eq!(entry.tag_name, Some("major".to_string()));
+        assert_eq!(
+            entry.channels,
+            vec![SlackChannelId::new("Cdist".to_string())]
+        );
+        assert_eq!(entry.detail, "set");
+    }
+}
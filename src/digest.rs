@@ -0,0 +1,402 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use slack_morphism::{
+    prelude::{SlackApiChatPostMessageRequest, SlackHyperClient},
+    SlackChannelId, SlackMessageContent, SlackTs, SlackUserId,
+};
+
+use crate::{
+    i18n::{self, Catalog},
+    post_message::{self, SlackApiMessageRequest, SlackApiMessageResponse},
+    query::{
+        delivery::{self, DeliverySchedule},
+        delivery_queue::QueuedMessage,
+        user_lang::Lang,
+    },
+    storage::Storage,
+};
+
+const DELIVERY_CHECK_SECS: u64 = 60;
+// Messages claimed by a replica that stopped halfway are delivered again after this
+const CLAIM_SECS: i64 = 10 * 60;
+// Slack cuts texts longer than 40000 characters, and recommends less than 4000
+const MAX_DIGEST_CHARS: usize = 3800;
+// a long message is cut, so that one message does not take up the whole digest
+const MAX_DIGEST_LINE_CHARS: usize = 300;
+
+// What delivering needs from Slack
+#[async_trait]
+trait Courier: Send + Sync {
+    async fn lang(&self, storage: &dyn Storage, user: &SlackUserId) -> Lang;
+    // the channel and the ts of the posted message
+    async fn post(
+        &self,
+        req: SlackApiChatPostMessageRequest,
+    ) -> anyhow::Result<(SlackChannelId, SlackTs)>;
+}
+
+#[async_trait]
+impl Courier for Arc<SlackHyperClient> {
+    async fn lang(&self, storage: &dyn Storage, user: &SlackUserId) -> Lang {
+        i18n::user_lang(self, storage, user).await
+    }
+
+    async fn post(
+        &self,
+        req: SlackApiChatPostMessageRequest,
+    ) -> anyhow::Result<(SlackChannelId, SlackTs)> {
+        let res = post_message::send_req(self.clone(), SlackApiMessageRequest::PostMessage(req));
+        let SlackApiMessageResponse::PostMessage(posted) = res.await? else {
+            return Err(anyhow::anyhow!("the message was not posted to the channel"));
+        };
+        Ok((posted.channel, posted.ts))
+    }
+}
+
+// Deliver queued messages of every dist channel whose schedule is due.
+// Every replica runs this, and the messages each one claims are delivered only by it.
+pub async fn delivery_loop(cli: Arc<SlackHyperClient>, storage: Arc<dyn Storage>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(DELIVERY_CHECK_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(err) = deliver_due(&cli, storage.as_ref()).await {
+            println!("err:{err:#?}");
+        }
+    }
+}
+
+async fn deliver_due(courier: &dyn Courier, storage: &dyn Storage) -> anyhow::Result<()> {
+    let now = delivery::now();
+    let schedules = storage.schedule_list().await?;
+    let pending = storage.pending_dists().await?;
+
+    for dist in pending {
+        let schedule = schedules.get(&dist).cloned().unwrap_or_default();
+        if !schedule.is_due(now) {
+            continue;
+        }
+        // a dist channel that fails, e.g. one the bot was removed from, does not hold up the others
+        if let Err(err) = deliver_dist(courier, &dist, schedule, now, storage).await {
+            println!("err:{err:#?}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn deliver_dist(
+    courier: &dyn Courier,
+    dist: &SlackChannelId,
+    schedule: DeliverySchedule,
+    now: i64,
+    storage: &dyn Storage,
+) -> anyhow::Result<()> {
+    let queued = storage.claim_queued(dist, now, now + CLAIM_SECS).await?;
+    if queued.is_empty() {
+        return Ok(());
+    }
+    if schedule.is_digest() {
+        deliver_digest(courier, dist, &queued, storage).await?;
+        storage.mark_delivered(dist, now).await?;
+    } else {
+        // messages held during quiet hours are posted one by one, as if forwarded late
+        release_queued(courier, &queued, storage).await?;
+    }
+    Ok(())
+}
+
+async fn deliver_digest(
+    courier: &dyn Courier,
+    dist: &SlackChannelId,
+    queued: &[QueuedMessage],
+    storage: &dyn Storage,
+) -> anyhow::Result<()> {
+    // a digest has no single recipient, so it is written in the language of whoever set it up
+    let lang = match storage.delivery_set_by(dist).await? {
+        Some(user) => courier.lang(storage, &user).await,
+        None => Lang::default(),
+    };
+    for part in digest_parts(queued, i18n::messages(lang)) {
+        let req = SlackApiChatPostMessageRequest::new(
+            dist.clone(),
+            SlackMessageContent::new().with_text(part.text),
+        );
+        courier.post(req).await?;
+        // only the messages written in the posted part are removed, so that a failure halfway
+        // leaves the rest for the next delivery
+        storage.remove_delivered(&part.queue_ids).await?;
+    }
+
+    Ok(())
+}
+
+async fn release_queued(
+    courier: &dyn Courier,
+    queued: &[QueuedMessage],
+    storage: &dyn Storage,
+) -> anyhow::Result<()> {
+    for message in queued {
+        let (channel, ts) = courier.post(queued_to_req(message)).await?;
+        // keep track of the forwarded copy, so that later edits and deletions can be mirrored
+        storage
+            .register_forwarded(&message.source_channel, &message.source_ts, &channel, &ts)
+            .await?;
+        // removed one by one, so that a failure halfway does not post the earlier ones again
+        storage.remove_delivered(&[message.queue_id]).await?;
+    }
+
+    Ok(())
+}
+
+fn queued_to_req(message: &QueuedMessage) -> SlackApiChatPostMessageRequest {
+    let text = format!(" `<#{}>` {}", message.source_channel, message.text);
+    SlackApiChatPostMessageRequest::new(
+        message.dist.clone(),
+        SlackMessageContent::new().with_text(text),
+    )
+    .with_icon_url(message.icon_url.clone())
+    .with_username(message.sender_name.clone())
+}
+
+fn digest_line(message: &QueuedMessage) -> String {
+    let text = if message.text.chars().count() > MAX_DIGEST_LINE_CHARS {
+        let cut = message
+            .text
+            .chars()
+            .take(MAX_DIGEST_LINE_CHARS)
+            .collect::<String>();
+        format!("{cut}…")
+    } else {
+        message.text.clone()
+    };
+    format!("\n• *{}*: {text}", message.sender_name)
+}
+
+// One Slack message of a digest, with the queued messages written in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DigestPart {
+    text: String,
+    chars: usize,
+    queue_ids: Vec<i64>,
+}
+
+impl DigestPart {
+    fn new(header: &str) -> Self {
+        Self {
+            text: header.to_string(),
+            chars: header.chars().count(),
+            queue_ids: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, text: &str, queue_id: i64) {
+        self.text.push_str(text);
+        self.chars += text.chars().count();
+        self.queue_ids.push(queue_id);
+    }
+}
+
+// Group queued messages by the channel they came from, keeping the collected order.
+// The messages that do not fit in one Slack message go on in the next ones.
+fn digest_parts(queued: &[QueuedMessage], msg: &dyn Catalog) -> Vec<DigestPart> {
+    let mut groups: Vec<(&SlackChannelId, Vec<&QueuedMessage>)> = Vec::new();
+    for message in queued {
+        match groups
+            .iter_mut()
+            .find(|(source, _)| **source == message.source_channel)
+        {
+            Some((_, messages)) => messages.push(message),
+            None => groups.push((&message.source_channel, vec![message])),
+        }
+    }
+
+    let mut parts = vec![DigestPart::new(&msg.digest_header(queued.len()))];
+    for (source, messages) in &groups {
+        let heading = format!("\n\n<#{source}> {}", msg.digest_count(messages.len()));
+        // the heading is written again at the top of the next part
+        let mut has_heading = false;
+        for message in messages {
+            let line = digest_line(message);
+            let mut text = if has_heading {
+                line.clone()
+            } else {
+                format!("{heading}{line}")
+            };
+            let part = parts.last_mut().expect("parts start with the header");
+            if !part.queue_ids.is_empty() && part.chars + text.chars().count() > MAX_DIGEST_CHARS {
+                parts.push(DigestPart::new(msg.digest_continued()));
+                text = format!("{heading}{line}");
+            }
+            parts
+                .last_mut()
+                .expect("parts start with the header")
+                .push(&text, message.queue_id);
+            has_heading = true;
+        }
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::{query::delivery::DeliveryMode, storage::testing::storage_tests};
+
+    use super::*;
+
+    // Keeps what would be posted to Slack
+    #[derive(Default)]
+    struct RecordingCourier {
+        posted: Mutex<Vec<SlackApiChatPostMessageRequest>>,
+    }
+
+    #[async_trait]
+    impl Courier for RecordingCourier {
+        async fn lang(&self, _storage: &dyn Storage, _user: &SlackUserId) -> Lang {
+            Lang::En
+        }
+
+        async fn post(
+            &self,
+            req: SlackApiChatPostMessageRequest,
+        ) -> anyhow::Result<(SlackChannelId, SlackTs)> {
+            // the other delivery goes on while this one waits for Slack
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let mut posted = self.posted.lock().unwrap();
+            posted.push(req.clone());
+            Ok((req.channel, SlackTs::new(format!("1.{}", posted.len()))))
+        }
+    }
+
+    fn queued_message(source: &str, text: &str) -> QueuedMessage {
+        QueuedMessage {
+            queue_id: 0,
+            dist: SlackChannelId::new("Cdigest".to_string()),
+            source_channel: SlackChannelId::new(source.to_string()),
+            source_ts: SlackTs::new("1686700000.000100".to_string()),
+            sender_name: "someone".to_string(),
+            icon_url: "https://example.com/icon.png".to_string(),
+            text: text.to_string(),
+            queued_at: 12345,
+        }
+    }
+
+    #[tokio::test]
+    async fn digest_parts_test() {
+        let queued = vec![
+            queued_message("C01", "first"),
+            queued_message("C02", "second"),
+            queued_message("C01", "third"),
+        ];
+        for lang in [Lang::Ja, Lang::En] {
+            let msg = i18n::messages(lang);
+            let parts = digest_parts(&queued, msg);
+            assert_eq!(parts.len(), 1, "{lang}");
+            let text = &parts[0].text;
+
+            let c01 = text
+                .find(&format!("<#C01> {}", msg.digest_count(2)))
+                .unwrap();
+            let c02 = text
+                .find(&format!("<#C02> {}", msg.digest_count(1)))
+                .unwrap();
+            let third = text.find("third").unwrap();
+            assert!(text.starts_with(&msg.digest_header(3)), "{lang}");
+            assert!(c01 < third && third < c02, "{lang}");
+        }
+    }
+
+    #[tokio::test]
+    async fn digest_parts_limit_test() {
+        let long_text = "a".repeat(1000);
+        let queued = (0..100)
+            .map(|i| QueuedMessage {
+                queue_id: i,
+                ..queued_message(if i < 50 { "C01" } else { "C02" }, &long_text)
+            })
+            .collect::<Vec<_>>();
+        let msg = i18n::messages(Lang::default());
+        let parts = digest_parts(&queued, msg);
+
+        // every message is written once, in a part that fits in a Slack message
+        assert!(parts.len() > 1);
+        let queue_ids = parts
+            .iter()
+            .flat_map(|part| part.queue_ids.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(queue_ids, (0..100).collect::<Vec<_>>());
+        for part in &parts {
+            assert!(part.text.chars().count() <= MAX_DIGEST_CHARS);
+            assert_eq!(part.chars, part.text.chars().count());
+            assert!(!part.text.contains(&"a".repeat(MAX_DIGEST_LINE_CHARS + 1)));
+            assert_eq!(part.text.matches("• ").count(), part.queue_ids.len());
+        }
+        assert!(parts[0].text.starts_with(&msg.digest_header(100)));
+        // a channel that goes on in the next part is named again there
+        assert!(parts[1].text.starts_with(msg.digest_continued()));
+        assert!(parts[1]
+            .text
+            .contains(&format!("<#C01> {}", msg.digest_count(50))));
+    }
+
+    // Two replicas delivering at once post each message once
+    async fn test_deliver_once(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U00001".to_string());
+        let held = SlackChannelId::new("Cheld".to_string());
+        let digest = SlackChannelId::new("Cdigest".to_string());
+        storage
+            .set_delivery_mode(&digest, &user, DeliveryMode::Interval(30), None)
+            .await?;
+        storage.mark_delivered(&digest, 0).await?;
+        for i in 0..5 {
+            for dist in [&held, &digest] {
+                let mut message = queued_message("C01", &format!("message{i}"));
+                message.dist = dist.clone();
+                storage.enqueue(&message).await?;
+            }
+        }
+
+        let courier = RecordingCourier::default();
+        let (first, second) = tokio::join!(
+            deliver_due(&courier, storage),
+            deliver_due(&courier, storage)
+        );
+        first?;
+        second?;
+
+        let posted = courier.posted.into_inner().unwrap();
+        let texts = |dist: &SlackChannelId| {
+            posted
+                .iter()
+                .filter(|req| req.channel == *dist)
+                .filter_map(|req| req.content.text.clone())
+                .collect::<Vec<_>>()
+        };
+        let held_texts = texts(&held);
+        assert_eq!(held_texts.len(), 5);
+        let digest_texts = texts(&digest);
+        assert_eq!(digest_texts.len(), 1);
+        for i in 0..5 {
+            let message = format!("message{i}");
+            let count = |texts: &[String]| texts.iter().filter(|t| t.contains(&message)).count();
+            assert_eq!(count(&held_texts), 1, "{message}");
+            assert_eq!(count(&digest_texts), 1, "{message}");
+        }
+        assert!(storage.pending_dists().await?.is_empty());
+
+        Ok(())
+    }
+
+    storage_tests!(test_deliver_once);
+
+    #[tokio::test]
+    async fn queued_to_req_test() {
+        let req = queued_to_req(&queued_message("C01", "held"));
+        assert_eq!(req.channel, SlackChannelId::new("Cdigest".to_string()));
+        assert_eq!(req.content.text, Some(" `<#C01>` held".to_string()));
+        assert_eq!(req.username, Some("someone".to_string()));
+    }
+}
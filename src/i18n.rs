@@ -0,0 +1,305 @@
+mod en;
+mod ja;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use slack_morphism::{
+    prelude::{SlackApiUsersInfoRequest, SlackHyperClient},
+    SlackApiTokenType, SlackChannelId, SlackUserId,
+};
+
+use crate::{
+    command_parser::ParseError,
+    commands::{access::AccessError, modal::ModalError},
+    query::{tag_member::TagRole, thread_mode::ThreadMode, user_lang::Lang},
+    storage::{Storage, TagError},
+    utils,
+};
+
+// Everything the bot tells users, written once per language.
+// Lists that are already formatted, like channel mentions, are given as text.
+pub trait Catalog: Sync {
+    // help
+    fn help(&self, topic: &str) -> Option<&'static str>;
+    fn unknown_help(&self) -> &'static str;
+    fn undefined_command(&self) -> &'static str;
+
+    // lists
+    fn none(&self) -> &'static str;
+    fn private_tags(&self) -> &'static str;
+    fn shared_tags(&self) -> &'static str;
+    fn public_tags(&self) -> &'static str;
+    fn tag_list_summary(&self, private: usize, shared: usize, public: usize) -> String;
+    fn channel_list_title(&self, tag: &str) -> String;
+    fn channel_list_summary(&self, tag: &str, count: usize) -> String;
+    fn target_list_title(&self) -> &'static str;
+    fn target_list_summary(&self, count: usize) -> String;
+
+    // errors
+    fn database_error(&self) -> &'static str;
+    fn slack_error(&self) -> &'static str;
+    fn command_error(&self, err: &str) -> String;
+    fn parse_error(&self, err: &ParseError) -> String;
+    fn tag_error(&self, err: &TagError) -> String;
+    fn access_error(&self, err: &AccessError) -> String;
+    fn modal_error(&self, err: &ModalError) -> String;
+
+    // tags
+    fn channels_added(&self, tag: &str, channels: &str) -> String;
+    fn channels_deleted(&self, tag: &str, channels: &str) -> String;
+    fn tag_dropped(&self, tag: &str) -> String;
+    fn drop_warning(&self, tag: &str, channels: usize, dists: usize) -> String;
+    fn drop_how_to(&self, command: &str) -> String;
+    fn retrieve_bot(&self, retrieve: bool) -> &'static str;
+    fn retrieve_file(&self, retrieve: bool) -> &'static str;
+    fn thread_mode(&self, tag: &str, mode: ThreadMode) -> String;
+    fn tag_renamed(&self, tag: &str, new_name: &str) -> String;
+    fn tag_copied(&self, tag: &str, new_name: &str) -> String;
+    fn tag_merged(&self, tag: &str, into: &str) -> String;
+    fn filters_added(&self, tag: &str, filters: &str) -> String;
+    fn filters_removed(&self, tag: &str, filters: &str) -> String;
+    fn filter_list(&self, tag: &str, filters: &str) -> String;
+    fn rules_added(&self, tag: &str, rules: &str) -> String;
+    fn rules_removed(&self, tag: &str, rules: &str) -> String;
+    fn rule_list(&self, tag: &str, rules: &str) -> String;
+    fn share_list(&self, tag: &str, members: &str) -> String;
+    fn tag_shared(&self, tag: &str, member: &str, role: TagRole) -> String;
+    fn tag_unshared(&self, tag: &str, member: &str) -> String;
+    fn admin_list(&self, admins: &str) -> String;
+    fn admin_added(&self, user: &SlackUserId) -> String;
+    fn admin_removed(&self, user: &SlackUserId) -> String;
+    fn no_audit(&self) -> &'static str;
+    fn audit_list(&self, lines: &str) -> String;
+    fn export(&self, text: &str) -> String;
+    fn export_file(&self, tags: usize) -> String;
+
+    // dist channels
+    fn tags_set(&self, tags: &str) -> String;
+    fn tags_unset(&self, tags: &str) -> String;
+    fn tag_unset_in(&self, dist: &SlackChannelId, tag: &str) -> String;
+    fn channel_created(&self, channel: &SlackChannelId, tags: &str) -> String;
+    fn delivery_immediate(&self) -> &'static str;
+    fn delivery_interval(&self, minutes: i64) -> String;
+    fn delivery_daily(&self, time: &str, time_zone: &str) -> String;
+    fn quiet_off(&self) -> &'static str;
+    fn quiet_hours(&self, start: &str, end: &str, time_zone: &str) -> String;
+    fn digest_header(&self, count: usize) -> String;
+    fn digest_count(&self, count: usize) -> String;
+    fn digest_continued(&self) -> &'static str;
+
+    // lang
+    fn lang_set(&self, lang: Lang) -> String;
+    fn lang_auto(&self, lang: Lang) -> String;
+    fn lang_current(&self, lang: Lang, chosen: bool) -> String;
+
+    // App Home
+    fn your_tags(&self) -> &'static str;
+    fn no_tags(&self) -> &'static str;
+    fn too_many_tags(&self) -> &'static str;
+    fn home_tag(&self, tag: &str, channels: &str) -> String;
+    fn home_dist(&self, dist: &SlackChannelId) -> String;
+    fn edit(&self) -> &'static str;
+    fn unset(&self) -> &'static str;
+    fn delete(&self) -> &'static str;
+    fn cancel(&self) -> &'static str;
+    fn drop_title(&self) -> &'static str;
+
+    // the modal
+    fn tag_to_change(&self) -> &'static str;
+    fn pick_tag(&self) -> &'static str;
+    fn new_tag(&self) -> &'static str;
+    fn new_tag_placeholder(&self) -> &'static str;
+    fn visibility(&self) -> &'static str;
+    fn private_tag(&self) -> &'static str;
+    fn public_tag(&self) -> &'static str;
+    fn shared_tag(&self, tag: &str, owner: &str) -> String;
+    fn channels_to_add(&self) -> &'static str;
+    fn pick_channels(&self) -> &'static str;
+    fn bot_messages(&self) -> &'static str;
+    fn keep(&self) -> &'static str;
+    fn collect(&self) -> &'static str;
+    fn ignore(&self) -> &'static str;
+    fn tags_to_set(&self) -> &'static str;
+    fn save(&self) -> &'static str;
+}
+
+pub fn messages(lang: Lang) -> &'static dyn Catalog {
+    match lang {
+        Lang::Ja => &ja::Ja,
+        Lang::En => &en::En,
+    }
+}
+
+// Resolving asks the database and Slack, so the language of each user is kept for a while.
+// Another replica's lang command is seen once this expires.
+const LANG_TTL: Duration = Duration::from_mins(5);
+
+fn lang_cache() -> &'static Mutex<HashMap<SlackUserId, (Lang, Instant)>> {
+    static LANG_CACHE: OnceLock<Mutex<HashMap<SlackUserId, (Lang, Instant)>>> = OnceLock::new();
+    LANG_CACHE.get_or_init(Mutex::default)
+}
+
+fn cached_lang(user: &SlackUserId) -> Option<Lang> {
+    let cache = lang_cache().lock().ok()?;
+    cache
+        .get(user)
+        .filter(|(_, resolved_at)| resolved_at.elapsed() < LANG_TTL)
+        .map(|(lang, _)| *lang)
+}
+
+fn cache_lang(user: &SlackUserId, lang: Lang) {
+    if let Ok(mut cache) = lang_cache().lock() {
+        cache.retain(|_, (_, resolved_at)| resolved_at.elapsed() < LANG_TTL);
+        cache.insert(user.clone(), (lang, Instant::now()));
+    }
+}
+
+// Drop the language of the user, to be resolved again after the lang command
+pub fn forget_lang(user: &SlackUserId) {
+    if let Ok(mut cache) = lang_cache().lock() {
+        cache.remove(user);
+    }
+}
+
+// The language chosen with the lang command, else the one of the user's Slack locale
+async fn resolve_lang(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+) -> anyhow::Result<Lang> {
+    if let Some(lang) = storage.user_lang(user).await? {
+        return Ok(lang);
+    }
+    let token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&token);
+    let info_req = SlackApiUsersInfoRequest::new(user.clone()).with_include_locale(true);
+    let res = session.users_info(&info_req).await?;
+    Ok(res
+        .user
+        .locale
+        .and_then(|locale| Lang::from_locale(&locale.0))
+        .unwrap_or_default())
+}
+
+// A reply should not fail for its language, so the default is used when it cannot be known
+pub async fn user_lang(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+) -> Lang {
+    if let Some(lang) = cached_lang(user) {
+        return lang;
+    }
+    match resolve_lang(cli, storage, user).await {
+        Ok(lang) => {
+            cache_lang(user, lang);
+            lang
+        }
+        Err(err) => {
+            println!("err:{err:#?}");
+            Lang::default()
+        }
+    }
+}
+
+pub async fn catalog(
+    cli: &Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    user: &SlackUserId,
+) -> &'static dyn Catalog {
+    messages(user_lang(cli, storage, user).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The topics of `/channel_bugyo help`, every one of which has a text in every language
+    const HELP_TOPICS: [&str; 25] = [
+        "help",
+        "add",
+        "delete",
+        "drop_tag",
+        "rename",
+        "copy",
+        "merge",
+        "retrieve_bot",
+        "retrieve_file",
+        "thread",
+        "filter",
+        "sender",
+        "share",
+        "admin",
+        "audit",
+        "export",
+        "ch_list",
+        "tag_list",
+        "set",
+        "unset",
+        "digest",
+        "quiet",
+        "create_channel",
+        "target_list",
+        "lang",
+    ];
+
+    // the lines showing how to call a command, which are the same in every language
+    fn usage_lines(text: &str) -> Vec<&str> {
+        text.lines()
+            .filter(|line| line.starts_with("`/channel_bugyo"))
+            .collect()
+    }
+
+    #[test]
+    fn lang_cache_test() {
+        let user = SlackUserId::new("U0cache".to_string());
+        assert_eq!(cached_lang(&user), None);
+
+        cache_lang(&user, Lang::En);
+        assert_eq!(cached_lang(&user), Some(Lang::En));
+
+        forget_lang(&user);
+        assert_eq!(cached_lang(&user), None);
+    }
+
+    #[test]
+    fn help_test() {
+        let (ja, en) = (messages(Lang::Ja), messages(Lang::En));
+        for topic in HELP_TOPICS {
+            let (Some(ja_text), Some(en_text)) = (ja.help(topic), en.help(topic)) else {
+                panic!("the help of {topic} is missing");
+            };
+            assert_eq!(usage_lines(ja_text), usage_lines(en_text), "{topic}");
+        }
+        assert_eq!(ja.help("unknown"), None);
+        assert_eq!(en.help("unknown"), None);
+    }
+
+    #[test]
+    fn help_lists_topics_test() {
+        for lang in [Lang::Ja, Lang::En] {
+            let help = messages(lang).help("help").unwrap();
+            for topic in HELP_TOPICS.iter().skip(1) {
+                assert!(help.contains(topic), "{lang} {topic}");
+            }
+        }
+    }
+
+    #[test]
+    fn messages_test() {
+        let ja = messages(Lang::Ja);
+        let en = messages(Lang::En);
+        assert_eq!(ja.tag_dropped("major"), "タグ major を削除しました");
+        assert_eq!(en.tag_dropped("major"), "Deleted the tag major.");
+        assert_eq!(
+            en.drop_how_to("/channel_bugyo drop_tag major --confirm"),
+            "To delete it, run `/channel_bugyo drop_tag major --confirm`."
+        );
+        assert!(en.lang_set(Lang::En).is_ascii());
+        assert!(!ja.lang_set(Lang::Ja).is_ascii());
+    }
+}
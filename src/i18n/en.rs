@@ -0,0 +1,463 @@
+use slack_morphism::{SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::ParseError,
+    commands::{access::AccessError, modal::ModalError},
+    query::{tag_member::TagRole, thread_mode::ThreadMode, user_lang::Lang},
+    storage::TagError,
+};
+
+use super::Catalog;
+
+pub struct En;
+
+const HELP_TEXT: &str = "Channel Bugyo is a Slack application that gathers the messages of channels the way you like.
+It manages the channels to gather with tags, which put together the channels of a topic or a project.
+There are two kinds of tags: \"user tags\", which only their user can access, and \"public tags\", which everyone can access.
+The \"add\" command registers channels to a tag. With the option --public, as in add --public, it manages a public tag.
+Options can be written anywhere among the arguments. A tag name with spaces is quoted, as in `\"release notes\"`.
+Running `/channel_bugyo` without arguments opens a form to set up tags.
+The commands below are available, and the help of each is shown by calling it like `/channel_bugyo help add`.
+Available commands: `add, delete, drop_tag, rename, copy, merge, retrieve_bot, retrieve_file, thread, filter, sender, share, admin, audit, export, ch_list, tag_list, set, unset, digest, quiet, create_channel, target_list, lang`";
+
+const ADD_TEXT: &str = "Adds channels to the tag. By default it is a private tag, which only the user who registered it can access.
+`/channel_bugyo add [tag] [#channel_1] [#channel_2] [#channel_3] ...`
+`/channel_bugyo add --public [tag] [#channel_1] [#channel_2] [#channel_3] ...`";
+
+const DELETE_TEXT: &str = "Deletes channels from the tag. To delete the tag itself, use drop_tag.
+`/channel_bugyo delete [tag] [#channel_1] [#channel_2] [#channel_3] ...`
+`/channel_bugyo delete --public [tag] [#channel_1] [#channel_2] [#channel_3] ...`";
+
+const RETBOT_TEXT: &str = "Sets whether the tag gathers messages by bots. (false by default)
+If the second argument is true, messages by bots are gathered, and if it is false, they are ignored.
+`/channel_bugyo retrieve_bot [tag] [bool]`
+`/channel_bugyo retrieve_bot --public [tag] [bool]`";
+
+const RETFILE_TEXT: &str = "Sets whether the tag gathers messages with files. (true by default)
+If the second argument is true, messages are gathered with the links and the names of their files, and if it is false, messages with files are ignored.
+`/channel_bugyo retrieve_file [tag] [bool]`
+`/channel_bugyo retrieve_file --public [tag] [bool]`";
+
+const THREAD_TEXT: &str = "Sets how the tag forwards replies in threads. (mirror by default)
+mirror forwards replies as replies in the thread of the forwarded message, drop forwards only the replies also sent to the channel, and root forwards no replies.
+`/channel_bugyo thread [tag] [mirror|drop|root]`
+`/channel_bugyo thread --public [tag] [mirror|drop|root]`";
+
+const FILTER_TEXT: &str = "Narrows the messages the tag gathers by keywords or regular expressions.
+If there are include filters, only the messages matching one of them are gathered, and the messages matching an exclude filter are always ignored. A pattern between slashes, like `/^deploy/`, is a regular expression.
+`/channel_bugyo filter add [tag] [include|exclude] [pattern_1] [pattern_2] ...`
+`/channel_bugyo filter remove [tag] [include|exclude] [pattern_1] [pattern_2] ...`
+`/channel_bugyo filter list [tag]`
+`/channel_bugyo filter add --public [tag] [include|exclude] [pattern_1] [pattern_2] ...`";
+
+const DROP_TAG_TEXT: &str =
+    "Deletes the tag with its channels and sets. Without --confirm, it shows what would be deleted.
+`/channel_bugyo drop_tag [tag] --confirm`
+`/channel_bugyo drop_tag --public [tag] --confirm`";
+
+const RENAME_TEXT: &str =
+    "Renames the tag. Its channels, settings, gathering channels and shares are kept. Only the owner of the tag can rename it.
+`/channel_bugyo rename [tag] [new_tag]`
+`/channel_bugyo rename --public [tag] [new_tag]`";
+
+const COPY_TEXT: &str = "Creates a new tag with the channels, settings, filters and sender rules of the tag. The copy gathers nothing until it is set. Only the owner of the tag can copy it.
+`/channel_bugyo copy [tag] [new_tag]`
+`/channel_bugyo copy --public [tag] [new_tag]`";
+
+const MERGE_TEXT: &str = "Moves the channels, gathering channels, filters, sender rules and shares of the tag into another tag, and deletes the tag. The settings of the other tag are kept. Only the owner of the tag can merge it.
+`/channel_bugyo merge [tag] [into_tag]`
+`/channel_bugyo merge --public [tag] [into_tag]`";
+
+const SENDER_TEXT: &str = "Narrows the messages the tag gathers by their senders.
+If there are allow rules, only the messages by one of their senders are gathered, and the messages by the senders of deny rules are always ignored. A sender is a mention of a user, or the ID of a user or a bot.
+For bots, set the tag to gather messages by bots with retrieve_bot.
+`/channel_bugyo sender add [tag] [allow|deny] [@user|bot_id] ...`
+`/channel_bugyo sender remove [tag] [allow|deny] [@user|bot_id] ...`
+`/channel_bugyo sender list [tag]`
+`/channel_bugyo sender add --public [tag] [allow|deny] [@user|bot_id] ...`";
+
+const SHARE_TEXT: &str = "Shares your tag with a user or a user group.
+An editor can add and delete channels and change the settings, and a viewer can only list the channels and set the tag. off stops sharing.
+With only a tag, it shows whom the tag is shared with.
+`/channel_bugyo share [tag] [@user|@group] [editor|viewer|off]`
+`/channel_bugyo share [tag]`
+`/channel_bugyo share --public [tag] [@user|@group] [editor|off]`";
+
+const ADMIN_TEXT: &str = "Adds and removes admins of the workspace. Admins can change every public tag, and can name editors of public tags with share --public.
+Only admins can add and remove admins. The admins in the environment variable SLACK_ADMIN_IDS cannot be removed with the command.
+`/channel_bugyo admin add [@user]`
+`/channel_bugyo admin remove [@user]`
+`/channel_bugyo admin list`";
+
+const AUDIT_TEXT: &str = "Shows the changes of tags and gathering channels, newest first.
+With a tag, only the changes of the tag are shown, and with a #channel, only the changes about the channel. --limit sets how many. (20 by default, 100 at most)
+`/channel_bugyo audit [tag|#channel] [--limit N]`
+`/channel_bugyo audit --public [tag] [--limit N]`";
+
+const EXPORT_TEXT: &str = "Shows the settings of tags as JSON or YAML. Admins get every tag, and the other users get their own tags.
+Settings too long for a message are uploaded to the channel as a file. The output can be applied with import on the command line.
+`/channel_bugyo export [--json|--yaml]`";
+
+const CH_LS_TEXT: &str = "Lists the channels gathered by the tag.
+`/channel_bugyo ch_list [tag]`
+`/channel_bugyo ch_list --public [tag]`";
+
+const TAG_LS_TEXT: &str = "Lists the tags.
+`/channel_bugyo tag_list`";
+
+const SET_TEXT: &str = "Used in a channel Channel Bugyo is added to, it gathers the messages of the channels of the tags into the channel.
+`/channel_bugyo set [tag_1] [tag_2] [tag_3] ...`
+`/channel_bugyo set --public [tag_1] [tag_2] [tag_3] ...`";
+
+const UNSET_TEXT: &str =
+    "Stops gathering the tags set in this channel. The sets of other channels are not affected.
+`/channel_bugyo unset [tag_1] [tag_2] [tag_3] ...`
+`/channel_bugyo unset --public [tag_1] [tag_2] [tag_3] ...`";
+
+const DIGEST_TEXT: &str = "Posts the messages gathered into this channel together as a digest, instead of one by one.
+every posts them every given minutes, and daily posts them every day at the given time, grouped by the channel they came from. The time zone is given by its name like Asia/Tokyo. (the last one if omitted, UTC by default)
+A named time zone follows daylight saving time. An offset like +09:00 may be given instead, which stays fixed.
+off goes back to posting one by one.
+`/channel_bugyo digest every [minutes]`
+`/channel_bugyo digest daily [HH:MM] [time_zone]`
+`/channel_bugyo digest off`";
+
+const QUIET_TEXT: &str = "Holds the messages gathered during the given hours (quiet hours), and posts them together when they end.
+The time zone is given by its name like Asia/Tokyo. (the last one if omitted, UTC by default)
+A named time zone follows daylight saving time. An offset like +09:00 may be given instead, which stays fixed.
+off ends the quiet hours.
+`/channel_bugyo quiet [HH:MM] [HH:MM] [time_zone]`
+`/channel_bugyo quiet off`";
+
+const CREATE_TEXT: &str = "Creates a new private channel which gathers the tags.
+`/channel_bugyo create_channel [new_channel_name] [tag_1] [tag_2] [tag_3] ...`
+`/channel_bugyo create_channel --public [new_channel_name] [tag_1] [tag_2] [tag_3] ...`";
+
+const TARGET_LS_TEXT: &str = "Lists the tags this channel gathers.
+`/channel_bugyo target_list`";
+
+const LANG_TEXT: &str = "Sets the language of the replies of the bot. ja replies in Japanese, and en in English.
+auto clears the setting, and follows the language of Slack. Without a language, it shows the current one.
+`/channel_bugyo lang [ja|en|auto]`
+`/channel_bugyo lang`";
+
+const UNDEFINED_TEXT: &str = "This command is not defined.";
+
+impl Catalog for En {
+    fn help(&self, topic: &str) -> Option<&'static str> {
+        let text = match topic {
+            "help" => HELP_TEXT,
+            "add" => ADD_TEXT,
+            "delete" => DELETE_TEXT,
+            "drop_tag" => DROP_TAG_TEXT,
+            "rename" => RENAME_TEXT,
+            "copy" => COPY_TEXT,
+            "merge" => MERGE_TEXT,
+            "retrieve_bot" => RETBOT_TEXT,
+            "retrieve_file" => RETFILE_TEXT,
+            "thread" => THREAD_TEXT,
+            "filter" => FILTER_TEXT,
+            "sender" => SENDER_TEXT,
+            "share" => SHARE_TEXT,
+            "admin" => ADMIN_TEXT,
+            "audit" => AUDIT_TEXT,
+            "export" => EXPORT_TEXT,
+            "ch_list" => CH_LS_TEXT,
+            "tag_list" => TAG_LS_TEXT,
+            "set" => SET_TEXT,
+            "unset" => UNSET_TEXT,
+            "digest" => DIGEST_TEXT,
+            "quiet" => QUIET_TEXT,
+            "create_channel" => CREATE_TEXT,
+            "target_list" => TARGET_LS_TEXT,
+            "lang" => LANG_TEXT,
+            _ => return None,
+        };
+        Some(text)
+    }
+    fn unknown_help(&self) -> &'static str {
+        UNDEFINED_TEXT
+    }
+    fn undefined_command(&self) -> &'static str {
+        "These arguments of the command are not defined."
+    }
+
+    fn none(&self) -> &'static str {
+        "None"
+    }
+    fn private_tags(&self) -> &'static str {
+        "Private tags"
+    }
+    fn shared_tags(&self) -> &'static str {
+        "Shared tags"
+    }
+    fn public_tags(&self) -> &'static str {
+        "Public tags"
+    }
+    fn tag_list_summary(&self, private: usize, shared: usize, public: usize) -> String {
+        format!("There are {private} private, {shared} shared and {public} public tags.")
+    }
+    fn channel_list_title(&self, tag: &str) -> String {
+        format!("Channels of the tag {tag}")
+    }
+    fn channel_list_summary(&self, tag: &str, count: usize) -> String {
+        format!("The tag {tag} has {count} channels.")
+    }
+    fn target_list_title(&self) -> &'static str {
+        "Tags this channel gathers"
+    }
+    fn target_list_summary(&self, count: usize) -> String {
+        format!("This channel gathers {count} tags.")
+    }
+
+    fn database_error(&self) -> &'static str {
+        "The database failed. Please try again later."
+    }
+    fn slack_error(&self) -> &'static str {
+        "Could not talk to Slack. Please try again later."
+    }
+    fn command_error(&self, err: &str) -> String {
+        format!("Could not run the command.\n{err}")
+    }
+    // the errors are written in English where they are defined
+    fn parse_error(&self, err: &ParseError) -> String {
+        err.to_string()
+    }
+    fn tag_error(&self, err: &TagError) -> String {
+        err.to_string()
+    }
+    fn access_error(&self, err: &AccessError) -> String {
+        err.to_string()
+    }
+    fn modal_error(&self, err: &ModalError) -> String {
+        err.to_string()
+    }
+
+    fn channels_added(&self, tag: &str, channels: &str) -> String {
+        format!("Added {channels} to the tag {tag}.")
+    }
+    fn channels_deleted(&self, tag: &str, channels: &str) -> String {
+        format!("Deleted {channels} from the tag {tag}.")
+    }
+    fn tag_dropped(&self, tag: &str) -> String {
+        format!("Deleted the tag {tag}.")
+    }
+    fn drop_warning(&self, tag: &str, channels: usize, dists: usize) -> String {
+        format!("Deleting the tag {tag} removes its {channels} channels and its sets in {dists} channels.")
+    }
+    fn drop_how_to(&self, command: &str) -> String {
+        format!("To delete it, run `{command}`.")
+    }
+    fn retrieve_bot(&self, retrieve: bool) -> &'static str {
+        if retrieve {
+            "From now on, this tag gathers messages by bots."
+        } else {
+            "From now on, this tag ignores messages by bots."
+        }
+    }
+    fn retrieve_file(&self, retrieve: bool) -> &'static str {
+        if retrieve {
+            "From now on, this tag gathers messages with files."
+        } else {
+            "From now on, this tag ignores messages with files."
+        }
+    }
+    fn thread_mode(&self, tag: &str, mode: ThreadMode) -> String {
+        let mode = match mode {
+            ThreadMode::Mirror => "forwards replies in threads as replies in the forwarded thread",
+            ThreadMode::Drop => "forwards only the replies in threads also sent to the channel",
+            ThreadMode::Root => "forwards no replies in threads, only the messages in the channel",
+        };
+        format!("From now on, the tag {tag} {mode}.")
+    }
+    fn tag_renamed(&self, tag: &str, new_name: &str) -> String {
+        format!("Renamed the tag {tag} to {new_name}.")
+    }
+    fn tag_copied(&self, tag: &str, new_name: &str) -> String {
+        format!("Copied the tag {tag} into the new tag {new_name}.")
+    }
+    fn tag_merged(&self, tag: &str, into: &str) -> String {
+        format!("Merged the tag {tag} into the tag {into}.")
+    }
+    fn filters_added(&self, tag: &str, filters: &str) -> String {
+        format!("Added the filters {filters} to the tag {tag}.")
+    }
+    fn filters_removed(&self, tag: &str, filters: &str) -> String {
+        format!("Removed the filters {filters} from the tag {tag}.")
+    }
+    fn filter_list(&self, tag: &str, filters: &str) -> String {
+        format!("The filters of the tag {tag} are: {filters}")
+    }
+    fn rules_added(&self, tag: &str, rules: &str) -> String {
+        format!("Added the sender rules {rules} to the tag {tag}.")
+    }
+    fn rules_removed(&self, tag: &str, rules: &str) -> String {
+        format!("Removed the sender rules {rules} from the tag {tag}.")
+    }
+    fn rule_list(&self, tag: &str, rules: &str) -> String {
+        format!("The sender rules of the tag {tag} are: {rules}")
+    }
+    fn share_list(&self, tag: &str, members: &str) -> String {
+        format!("The tag {tag} is shared with: {members}")
+    }
+    fn tag_shared(&self, tag: &str, member: &str, role: TagRole) -> String {
+        format!("Shared the tag {tag} with {member} as {role}.")
+    }
+    fn tag_unshared(&self, tag: &str, member: &str) -> String {
+        format!("Stopped sharing the tag {tag} with {member}.")
+    }
+    fn admin_list(&self, admins: &str) -> String {
+        format!("The admins are: {admins}")
+    }
+    fn admin_added(&self, user: &SlackUserId) -> String {
+        format!("Added <@{user}> to the admins.")
+    }
+    fn admin_removed(&self, user: &SlackUserId) -> String {
+        format!("Removed <@{user}> from the admins.")
+    }
+    fn no_audit(&self) -> &'static str {
+        "There are no changes."
+    }
+    fn audit_list(&self, lines: &str) -> String {
+        format!("The changes are, newest first:\n{lines}")
+    }
+    fn export(&self, text: &str) -> String {
+        format!("The settings of the tags are:\n```\n{text}\n```")
+    }
+    fn export_file(&self, tags: usize) -> String {
+        format!("The settings of {tags} tags are too long for a message, so they are sent to you as a file in a direct message.")
+    }
+
+    fn tags_set(&self, tags: &str) -> String {
+        format!(
+            "From now on, this channel gathers the messages of the channels of these tags. {tags}"
+        )
+    }
+    fn tags_unset(&self, tags: &str) -> String {
+        format!("Stopped gathering the messages of the channels of these tags. {tags}")
+    }
+    fn tag_unset_in(&self, dist: &SlackChannelId, tag: &str) -> String {
+        format!("Stopped gathering the tag {tag} in <#{dist}>.")
+    }
+    fn channel_created(&self, channel: &SlackChannelId, tags: &str) -> String {
+        format!("Created the new channel <#{channel}>, which gathers the messages of these tags: {tags}")
+    }
+    fn delivery_immediate(&self) -> &'static str {
+        "From now on, this channel posts the gathered messages one by one."
+    }
+    fn delivery_interval(&self, minutes: i64) -> String {
+        format!("From now on, this channel posts the gathered messages as a digest every {minutes} minutes.")
+    }
+    fn delivery_daily(&self, time: &str, time_zone: &str) -> String {
+        format!("From now on, this channel posts the gathered messages as a digest every day at {time} ({time_zone}).")
+    }
+    fn quiet_off(&self) -> &'static str {
+        "Ended the quiet hours of this channel."
+    }
+    fn quiet_hours(&self, start: &str, end: &str, time_zone: &str) -> String {
+        format!("From now on, this channel holds the messages gathered from {start} to {end} ({time_zone}), and posts them together afterwards.")
+    }
+    fn digest_header(&self, count: usize) -> String {
+        format!("Here is the digest of the gathered messages. ({count})")
+    }
+    fn digest_count(&self, count: usize) -> String {
+        format!("({count})")
+    }
+    fn digest_continued(&self) -> &'static str {
+        "The digest continues."
+    }
+
+    fn lang_set(&self, lang: Lang) -> String {
+        format!("From now on, the bot replies in English ({lang}).")
+    }
+    fn lang_auto(&self, lang: Lang) -> String {
+        format!("Cleared the language. Following the language of Slack, the bot replies in English ({lang}).")
+    }
+    fn lang_current(&self, lang: Lang, chosen: bool) -> String {
+        if chosen {
+            format!("The bot replies in English ({lang}).")
+        } else {
+            format!("Following the language of Slack, the bot replies in English ({lang}).")
+        }
+    }
+
+    fn your_tags(&self) -> &'static str {
+        "Your tags"
+    }
+    fn no_tags(&self) -> &'static str {
+        "There are no tags. Create one with `/channel_bugyo add`."
+    }
+    fn too_many_tags(&self) -> &'static str {
+        "Some tags are not shown. See them with `/channel_bugyo tag_list`."
+    }
+    fn home_tag(&self, tag: &str, channels: &str) -> String {
+        format!("*{tag}*\nChannels: {channels}")
+    }
+    fn home_dist(&self, dist: &SlackChannelId) -> String {
+        format!("Gathered in: <#{dist}>")
+    }
+    fn edit(&self) -> &'static str {
+        "Edit"
+    }
+    fn unset(&self) -> &'static str {
+        "Unset"
+    }
+    fn delete(&self) -> &'static str {
+        "Delete"
+    }
+    fn cancel(&self) -> &'static str {
+        "Cancel"
+    }
+    fn drop_title(&self) -> &'static str {
+        "Delete the tag"
+    }
+
+    fn tag_to_change(&self) -> &'static str {
+        "Tag to change"
+    }
+    fn pick_tag(&self) -> &'static str {
+        "Select a tag"
+    }
+    fn new_tag(&self) -> &'static str {
+        "New tag"
+    }
+    fn new_tag_placeholder(&self) -> &'static str {
+        "The name of the tag to create"
+    }
+    fn visibility(&self) -> &'static str {
+        "Who can access the new tag"
+    }
+    fn private_tag(&self) -> &'static str {
+        "Private tag"
+    }
+    fn public_tag(&self) -> &'static str {
+        "Public tag"
+    }
+    fn shared_tag(&self, tag: &str, owner: &str) -> String {
+        format!("{tag} (shared by {owner})")
+    }
+    fn channels_to_add(&self) -> &'static str {
+        "Channels to add to the tag"
+    }
+    fn pick_channels(&self) -> &'static str {
+        "Select channels"
+    }
+    fn bot_messages(&self) -> &'static str {
+        "Messages by bots"
+    }
+    fn keep(&self) -> &'static str {
+        "Keep"
+    }
+    fn collect(&self) -> &'static str {
+        "Gather"
+    }
+    fn ignore(&self) -> &'static str {
+        "Ignore"
+    }
+    fn tags_to_set(&self) -> &'static str {
+        "Tags to gather in this channel"
+    }
+    fn save(&self) -> &'static str {
+        "Save"
+    }
+}
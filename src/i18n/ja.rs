@@ -0,0 +1,546 @@
+use slack_morphism::{SlackChannelId, SlackUserId};
+
+use crate::{
+    command_parser::{Expected, ParseError},
+    commands::{
+        access::{AccessError, OwnerAction},
+        modal::ModalError,
+    },
+    query::{tag_member::TagRole, thread_mode::ThreadMode, user_lang::Lang},
+    storage::TagError,
+};
+
+use super::Catalog;
+
+pub struct Ja;
+
+const HELP_TEXT:&str = "Channel Bugyo は、カスタマイズ可能なチャンネルメッセージ集約を行うためのSlack用アプリケーションです。
+このアプリでは、収集したいチャンネルを管理するためにタグを使用します。タグにより、特定のトピックやプロジェクトごとに関連するチャンネルをまとめることができます。
+タグには、ユーザーのみがアクセスできる「ユーザータグ」と、誰でもアクセスできる「パブリックタグ」という2つの種類があります。
+「add」コマンドを使用すると、特定のタグにチャンネルを登録できます。例えば、add --publicというオプションを追加すると、パブリックなタグの管理が可能です。
+オプションは引数のどの位置に書いても構いません。空白を含むタグ名は `\"release notes\"` のようにダブルクォートで囲んで指定します。
+引数を付けずに `/channel_bugyo` を実行すると、タグを設定する画面が開きます。
+使用可能なコマンドとして以下が存在し、 `/channel_bugyo help add` のように呼び出すことで、コマンドごとのヘルプを閲覧可能です。
+使用可能なコマンド： `add, delete, drop_tag, rename, copy, merge, retrieve_bot, retrieve_file, thread, filter, sender, share, admin, audit, export, ch_list, tag_list, set, unset, digest, quiet, create_channel, target_list, lang`";
+
+const ADD_TEXT:&str = "指定したタグにチャンネルを追加します。デフォルトではプライベートタグとして、登録したユーザのみがアクセス可能です.。
+`/channel_bugyo add [tag] [#channel_1] [#channel_2] [#channel_3] ...`
+`/channel_bugyo add --public [tag] [#channel_1] [#channel_2] [#channel_3] ...`";
+
+const DELETE_TEXT: &str =
+    "指定したタグからチャンネルを削除します。タグ自体を削除するには drop_tag を使用してください。
+`/channel_bugyo delete [tag] [#channel_1] [#channel_2] [#channel_3] ...`
+`/channel_bugyo delete --public [tag] [#channel_1] [#channel_2] [#channel_3] ...`";
+
+const RETBOT_TEXT:&str = "指定したタグがボットによるメッセージを収集するかを設定します。（初期値は false)
+第二引数が true であれば、ボットメッセージを収集するようになり、false であれば、ボットメッセージを無視します。
+`/channel_bugyo retrieve_bot [tag] [bool]`
+`/channel_bugyo retrieve_bot --public [tag] [bool]`";
+
+const RETFILE_TEXT:&str = "指定したタグがファイルを含むメッセージを収集するかを設定します。（初期値は true)
+第二引数が true であれば、ファイルのリンクとファイル名を添えてメッセージを収集し、false であれば、ファイルを含むメッセージを無視します。
+`/channel_bugyo retrieve_file [tag] [bool]`
+`/channel_bugyo retrieve_file --public [tag] [bool]`";
+
+const THREAD_TEXT: &str = "指定したタグにおいて、スレッドへの返信をどのように転送するかを設定します。（初期値は mirror)
+mirror であれば、返信を転送先のスレッドに返信として転送し、drop であれば、チャンネルにも投稿された返信のみを転送し、root であれば、返信を転送しません。
+`/channel_bugyo thread [tag] [mirror|drop|root]`
+`/channel_bugyo thread --public [tag] [mirror|drop|root]`";
+
+const FILTER_TEXT: &str = "指定したタグが収集するメッセージを、キーワードまたは正規表現で絞り込みます。
+include フィルタが存在する場合はいずれかに一致するメッセージのみを、exclude フィルタに一致するメッセージは常に無視します。 `/^deploy/` のようにスラッシュで囲むと正規表現として扱います。
+`/channel_bugyo filter add [tag] [include|exclude] [pattern_1] [pattern_2] ...`
+`/channel_bugyo filter remove [tag] [include|exclude] [pattern_1] [pattern_2] ...`
+`/channel_bugyo filter list [tag]`
+`/channel_bugyo filter add --public [tag] [include|exclude] [pattern_1] [pattern_2] ...`";
+
+const DROP_TAG_TEXT: &str = "指定したタグを、登録されたチャンネルと set とともに削除します。--confirm を付けずに実行すると、削除される内容を表示します。
+`/channel_bugyo drop_tag [tag] --confirm`
+`/channel_bugyo drop_tag --public [tag] --confirm`";
+
+const RENAME_TEXT: &str = "指定したタグの名前を変更します。チャンネル・設定・収集先チャンネル・共有はそのまま引き継がれます。タグの所有者のみが実行できます。
+`/channel_bugyo rename [tag] [new_tag]`
+`/channel_bugyo rename --public [tag] [new_tag]`";
+
+const COPY_TEXT: &str = "指定したタグのチャンネル・設定・フィルタ・送信者ルールをコピーし、新たなタグを作成します。コピーしたタグは、set するまで収集を行いません。タグの所有者のみが実行できます。
+`/channel_bugyo copy [tag] [new_tag]`
+`/channel_bugyo copy --public [tag] [new_tag]`";
+
+const MERGE_TEXT: &str = "指定したタグのチャンネル・収集先チャンネル・フィルタ・送信者ルール・共有を、統合先のタグに移し、元のタグを削除します。統合先のタグの設定はそのまま残ります。タグの所有者のみが実行できます。
+`/channel_bugyo merge [tag] [into_tag]`
+`/channel_bugyo merge --public [tag] [into_tag]`";
+
+const SENDER_TEXT: &str = "指定したタグが収集するメッセージを、送信者によって絞り込みます。
+allow ルールが存在する場合はいずれかの送信者によるメッセージのみを、deny ルールの送信者によるメッセージは常に無視します。送信者はユーザへのメンション、またはユーザ ID・ボット ID で指定します。
+ボットを対象とする場合は、retrieve_bot により、ボットによるメッセージを収集するよう設定してください。
+`/channel_bugyo sender add [tag] [allow|deny] [@user|bot_id] ...`
+`/channel_bugyo sender remove [tag] [allow|deny] [@user|bot_id] ...`
+`/channel_bugyo sender list [tag]`
+`/channel_bugyo sender add --public [tag] [allow|deny] [@user|bot_id] ...`";
+
+const SHARE_TEXT: &str = "自分のタグを、指定したユーザまたはユーザグループと共有します。
+editor であればチャンネルの追加・削除や設定の変更まで、viewer であればチャンネルの一覧と set のみが可能になります。off であれば共有を解除します。
+tag を指定するのみであれば、共有先の一覧を表示します。
+`/channel_bugyo share [tag] [@user|@group] [editor|viewer|off]`
+`/channel_bugyo share [tag]`
+`/channel_bugyo share --public [tag] [@user|@group] [editor|off]`";
+
+const ADMIN_TEXT: &str = "ワークスペースの管理者を追加・削除します。管理者はすべてのパブリックタグを変更でき、share --public によりパブリックタグの editor を指定できます。
+管理者の追加・削除は管理者のみが行えます。環境変数 SLACK_ADMIN_IDS で指定された管理者はコマンドでは削除できません。
+`/channel_bugyo admin add [@user]`
+`/channel_bugyo admin remove [@user]`
+`/channel_bugyo admin list`";
+
+const AUDIT_TEXT: &str = "タグと収集先チャンネルの変更履歴を、新しい順に表示します。
+tag を指定すればそのタグの、#channel を指定すればそのチャンネルに関する変更のみを表示します。--limit で件数を指定できます。（初期値は 20、最大 100）
+`/channel_bugyo audit [tag|#channel] [--limit N]`
+`/channel_bugyo audit --public [tag] [--limit N]`";
+
+const EXPORT_TEXT: &str = "タグの設定を JSON または YAML として表示します。管理者はすべてのタグを、それ以外のユーザは自分のタグを出力します。
+1 件のメッセージに収まらない場合は、チャンネルにファイルとしてアップロードします。出力したファイルは、コマンドラインの import で適用できます。
+`/channel_bugyo export [--json|--yaml]`";
+
+const CH_LS_TEXT: &str = "指定したタグの収集対象チャンネルの一覧を表示します。
+`/channel_bugyo ch_list [tag]`
+`/channel_bugyo ch_list --public [tag]`";
+
+const TAG_LS_TEXT: &str = "存在するタグの一覧を表示します。
+`/channel_bugyo tag_list`";
+const SET_TEXT:&str = "Channel Bugyo が追加されているチャンネルにおいて使用することで、そのチャンネルに、指定したタグで収集対象となっているチャンネルのメッセージを収集します。
+`/channel_bugyo set [tag_1] [tag_2] [tag_3] ...`
+`/channel_bugyo set --public [tag_1] [tag_2] [tag_3] ...`";
+
+const UNSET_TEXT: &str = "このチャンネルに set されているタグを収集対象から外します。他のチャンネルの set には影響しません。
+`/channel_bugyo unset [tag_1] [tag_2] [tag_3] ...`
+`/channel_bugyo unset --public [tag_1] [tag_2] [tag_3] ...`";
+
+const DIGEST_TEXT: &str = "本チャンネルに収集したメッセージを、都度ではなくダイジェストとしてまとめて投稿します。
+every であれば指定した分ごとに、daily であれば毎日指定した時刻に、収集元のチャンネルごとにまとめて投稿します。時刻のタイムゾーンは Asia/Tokyo のように名前で指定します。（省略時は前回の設定、初期値は UTC)
+名前で指定したタイムゾーンは夏時間に追従します。+09:00 のように UTC からの固定の時差で指定することもできます。
+off であれば、都度投稿に戻します。
+`/channel_bugyo digest every [minutes]`
+`/channel_bugyo digest daily [HH:MM] [time_zone]`
+`/channel_bugyo digest off`";
+
+const QUIET_TEXT: &str =
+    "指定した時間帯（おやすみ時間）に収集したメッセージを保留し、終了後にまとめて投稿します。
+時刻のタイムゾーンは Asia/Tokyo のように名前で指定します。（省略時は前回の設定、初期値は UTC)
+名前で指定したタイムゾーンは夏時間に追従します。+09:00 のように UTC からの固定の時差で指定することもできます。
+off であれば、おやすみ時間を解除します。
+`/channel_bugyo quiet [HH:MM] [HH:MM] [time_zone]`
+`/channel_bugyo quiet off`";
+
+const CREATE_TEXT: &str = "指定したタグを収集対象とする新たなプライベートチャンネルを作成します。
+`/channel_bugyo create_channel [new_channel_name] [tag_1] [tag_2] [tag_3] ...`
+`/channel_bugyo create_channel --public [new_channel_name] [tag_1] [tag_2] [tag_3] ...`";
+
+const TARGET_LS_TEXT: &str = "現在チャンネルが収集対象としているタグの一覧を表示します。
+`/channel_bugyo target_list`";
+
+const LANG_TEXT: &str = "ボットの返信の言語を設定します。ja であれば日本語、en であれば英語で返信します。
+auto であれば設定を解除し、Slack の言語設定に従います。言語を指定しなければ、現在の言語を表示します。
+`/channel_bugyo lang [ja|en|auto]`
+`/channel_bugyo lang`";
+
+const UNDEFINED_TEXT: &str = "このコマンドは未定義です。";
+
+impl Catalog for Ja {
+    fn help(&self, topic: &str) -> Option<&'static str> {
+        let text = match topic {
+            "help" => HELP_TEXT,
+            "add" => ADD_TEXT,
+            "delete" => DELETE_TEXT,
+            "drop_tag" => DROP_TAG_TEXT,
+            "rename" => RENAME_TEXT,
+            "copy" => COPY_TEXT,
+            "merge" => MERGE_TEXT,
+            "retrieve_bot" => RETBOT_TEXT,
+            "retrieve_file" => RETFILE_TEXT,
+            "thread" => THREAD_TEXT,
+            "filter" => FILTER_TEXT,
+            "sender" => SENDER_TEXT,
+            "share" => SHARE_TEXT,
+            "admin" => ADMIN_TEXT,
+            "audit" => AUDIT_TEXT,
+            "export" => EXPORT_TEXT,
+            "ch_list" => CH_LS_TEXT,
+            "tag_list" => TAG_LS_TEXT,
+            "set" => SET_TEXT,
+            "unset" => UNSET_TEXT,
+            "digest" => DIGEST_TEXT,
+            "quiet" => QUIET_TEXT,
+            "create_channel" => CREATE_TEXT,
+            "target_list" => TARGET_LS_TEXT,
+            "lang" => LANG_TEXT,
+            _ => return None,
+        };
+        Some(text)
+    }
+    fn unknown_help(&self) -> &'static str {
+        UNDEFINED_TEXT
+    }
+    fn undefined_command(&self) -> &'static str {
+        "このコマンド引数は未定義です。"
+    }
+
+    fn none(&self) -> &'static str {
+        "なし"
+    }
+    fn private_tags(&self) -> &'static str {
+        "プライベートタグ"
+    }
+    fn shared_tags(&self) -> &'static str {
+        "共有されたタグ"
+    }
+    fn public_tags(&self) -> &'static str {
+        "パブリックタグ"
+    }
+    fn tag_list_summary(&self, private: usize, shared: usize, public: usize) -> String {
+        format!("タグはプライベート {private} 件、共有 {shared} 件、パブリック {public} 件です")
+    }
+    fn channel_list_title(&self, tag: &str) -> String {
+        format!("タグ {tag} に登録されたチャンネル")
+    }
+    fn channel_list_summary(&self, tag: &str, count: usize) -> String {
+        format!("タグ {tag} に登録されたチャンネルは {count} 件です")
+    }
+    fn target_list_title(&self) -> &'static str {
+        "このチャンネルが収集しているタグ"
+    }
+    fn target_list_summary(&self, count: usize) -> String {
+        format!("このチャンネルが収集しているタグは {count} 件です")
+    }
+
+    fn database_error(&self) -> &'static str {
+        "データベースの処理に失敗しました。時間をおいて再度お試しください。"
+    }
+    fn slack_error(&self) -> &'static str {
+        "Slack との通信に失敗しました。時間をおいて再度お試しください。"
+    }
+    fn command_error(&self, err: &str) -> String {
+        format!("コマンドを実行できませんでした。\n{err}")
+    }
+    fn parse_error(&self, err: &ParseError) -> String {
+        match err {
+            ParseError::UnclosedQuote => "引用符が閉じられていません".to_string(),
+            ParseError::NeedsValue(flag) => format!("{flag} には値を指定してください"),
+            ParseError::Missing { name, usage } => {
+                format!("{name} が指定されていません\n使い方: `/channel_bugyo {usage}`")
+            }
+            ParseError::Empty(name) => format!("{name} を空にすることはできません"),
+            ParseError::UnknownOption { flag, usage } => {
+                format!("{flag} というオプションはありません\n使い方: `/channel_bugyo {usage}`")
+            }
+            ParseError::UnexpectedArgument { arg, usage } => {
+                format!("不要な引数 `{arg}` があります\n使い方: `/channel_bugyo {usage}`")
+            }
+            ParseError::Invalid {
+                name,
+                value,
+                expected,
+            } => format!("{name} の `{value}` は正しくありません。{}", expected_text(expected)),
+            ParseError::NoChannelsToDelete => {
+                "削除するチャンネルを指定してください。タグ自体を削除するには drop_tag を使用してください".to_string()
+            }
+            ParseError::BothFormats => {
+                "--json と --yaml はどちらか一方のみ指定してください".to_string()
+            }
+            ParseError::SameStartAndEnd => "開始と終了には異なる時刻を指定してください".to_string(),
+        }
+    }
+    fn tag_error(&self, err: &TagError) -> String {
+        match err {
+            TagError::NotFound(tag) => format!("タグ {tag} は存在しません"),
+            TagError::AlreadyExists(tag) => format!("タグ {tag} はすでに存在します"),
+            TagError::MergeIntoItself => "タグを同じタグに統合することはできません".to_string(),
+        }
+    }
+    fn access_error(&self, err: &AccessError) -> String {
+        match err {
+            AccessError::Viewer(tag) => {
+                format!("タグ {tag} は閲覧者として共有されているため、編集できません")
+            }
+            AccessError::Ambiguous(tag) => format!(
+                "タグ {tag} という名前のタグが複数共有されています。所有者にどちらかの名前を変更するよう依頼してください"
+            ),
+            AccessError::PublicTag(tag) => {
+                format!("パブリックタグ {tag} を変更できるのは管理者と編集者のみです")
+            }
+            AccessError::OwnerOnly(action) => {
+                let action = match action {
+                    OwnerAction::Drop => "削除",
+                    OwnerAction::Rename => "名前の変更",
+                    OwnerAction::Copy => "コピー",
+                    OwnerAction::Merge => "統合",
+                    OwnerAction::Share => "共有",
+                };
+                format!("タグの{action}ができるのはタグの所有者のみです")
+            }
+            AccessError::SharePublic => "パブリックタグを共有できるのは管理者のみです".to_string(),
+            AccessError::AdminOnly => "管理者を変更できるのは管理者のみです".to_string(),
+            AccessError::FixedAdmin => {
+                "SLACK_ADMIN_IDS で指定された管理者はコマンドで削除できません".to_string()
+            }
+        }
+    }
+    fn modal_error(&self, err: &ModalError) -> String {
+        match err {
+            ModalError::BothTags => {
+                "タグの選択と新しいタグ名の入力は、どちらか一方のみにしてください".to_string()
+            }
+            ModalError::NoTag => "変更するタグを選ぶか、新しいタグ名を入力してください".to_string(),
+            ModalError::NoChange => {
+                "タグに追加するチャンネルか、ボットの設定を選んでください".to_string()
+            }
+            ModalError::Empty => "変更する内容がありません".to_string(),
+        }
+    }
+
+    fn channels_added(&self, tag: &str, channels: &str) -> String {
+        format!("タグ {tag} に {channels} が追加されました")
+    }
+    fn channels_deleted(&self, tag: &str, channels: &str) -> String {
+        format!("タグ {tag} から {channels} が削除されました")
+    }
+    fn tag_dropped(&self, tag: &str) -> String {
+        format!("タグ {tag} を削除しました")
+    }
+    fn drop_warning(&self, tag: &str, channels: usize, dists: usize) -> String {
+        format!("タグ {tag} を削除すると、登録された {channels} 個のチャンネルと、{dists} 個のチャンネルへの set がすべて解除されます。")
+    }
+    fn drop_how_to(&self, command: &str) -> String {
+        format!("削除する場合は `{command}` を実行してください。")
+    }
+    fn retrieve_bot(&self, retrieve: bool) -> &'static str {
+        if retrieve {
+            "以降、このタグはボットによるメッセージを収集します。"
+        } else {
+            "以降、このタグはボットによるメッセージを無視します。"
+        }
+    }
+    fn retrieve_file(&self, retrieve: bool) -> &'static str {
+        if retrieve {
+            "以降、このタグはファイルを含むメッセージを収集します。"
+        } else {
+            "以降、このタグはファイルを含むメッセージを無視します。"
+        }
+    }
+    fn thread_mode(&self, tag: &str, mode: ThreadMode) -> String {
+        let mode = match mode {
+            ThreadMode::Mirror => "スレッドの返信を転送先のスレッドに返信として転送",
+            ThreadMode::Drop => "スレッドの返信のうち、チャンネルにも投稿されたもののみを転送",
+            ThreadMode::Root => "スレッドの返信を転送せず、チャンネルへの投稿のみを転送",
+        };
+        format!("以降、タグ {tag} は{mode}します。")
+    }
+    fn tag_renamed(&self, tag: &str, new_name: &str) -> String {
+        format!("タグ {tag} の名前を {new_name} に変更しました")
+    }
+    fn tag_copied(&self, tag: &str, new_name: &str) -> String {
+        format!("タグ {tag} をコピーし、タグ {new_name} を作成しました")
+    }
+    fn tag_merged(&self, tag: &str, into: &str) -> String {
+        format!("タグ {tag} をタグ {into} に統合しました")
+    }
+    fn filters_added(&self, tag: &str, filters: &str) -> String {
+        format!("タグ {tag} にフィルタ {filters} が追加されました")
+    }
+    fn filters_removed(&self, tag: &str, filters: &str) -> String {
+        format!("タグ {tag} からフィルタ {filters} が削除されました")
+    }
+    fn filter_list(&self, tag: &str, filters: &str) -> String {
+        format!("タグ {tag} に設定されたフィルタは以下です。 {filters}")
+    }
+    fn rules_added(&self, tag: &str, rules: &str) -> String {
+        format!("タグ {tag} に送信者ルール {rules} が追加されました")
+    }
+    fn rules_removed(&self, tag: &str, rules: &str) -> String {
+        format!("タグ {tag} から送信者ルール {rules} が削除されました")
+    }
+    fn rule_list(&self, tag: &str, rules: &str) -> String {
+        format!("タグ {tag} に設定された送信者ルールは以下です。 {rules}")
+    }
+    fn share_list(&self, tag: &str, members: &str) -> String {
+        format!("タグ {tag} の共有先は以下です。 {members}")
+    }
+    fn tag_shared(&self, tag: &str, member: &str, role: TagRole) -> String {
+        format!("タグ {tag} を {member} に {role} として共有しました")
+    }
+    fn tag_unshared(&self, tag: &str, member: &str) -> String {
+        format!("タグ {tag} の {member} への共有を解除しました")
+    }
+    fn admin_list(&self, admins: &str) -> String {
+        format!("管理者は以下です。 {admins}")
+    }
+    fn admin_added(&self, user: &SlackUserId) -> String {
+        format!("<@{user}> を管理者に追加しました")
+    }
+    fn admin_removed(&self, user: &SlackUserId) -> String {
+        format!("<@{user}> を管理者から削除しました")
+    }
+    fn no_audit(&self) -> &'static str {
+        "変更履歴はありません。"
+    }
+    fn audit_list(&self, lines: &str) -> String {
+        format!("変更履歴は以下です。(新しい順)\n{lines}")
+    }
+    fn export(&self, text: &str) -> String {
+        format!("タグの設定は以下です。\n```\n{text}\n```")
+    }
+    fn export_file(&self, tags: usize) -> String {
+        format!(
+            "{tags} 件のタグの設定は 1 件のメッセージには長すぎるため、ファイルとしてダイレクトメッセージで送ります。"
+        )
+    }
+
+    fn tags_set(&self, tags: &str) -> String {
+        format!(
+            "以降、本チャンネルは以下のタグに登録されたチャンネルのメッセージを収集します。{tags}"
+        )
+    }
+    fn tags_unset(&self, tags: &str) -> String {
+        format!("以下のタグに登録されたチャンネルのメッセージの収集を停止します。{tags}")
+    }
+    fn tag_unset_in(&self, dist: &SlackChannelId, tag: &str) -> String {
+        format!("<#{dist}> でのタグ {tag} の収集を停止しました")
+    }
+    fn channel_created(&self, channel: &SlackChannelId, tags: &str) -> String {
+        format!("以下のタグに登録されたメッセージを収集する新しいチャンネル <#{channel}> を作成しました:{tags}")
+    }
+    fn delivery_immediate(&self) -> &'static str {
+        "以降、本チャンネルは収集したメッセージを都度投稿します。"
+    }
+    fn delivery_interval(&self, minutes: i64) -> String {
+        format!("以降、本チャンネルは収集したメッセージを {minutes} 分ごとにダイジェストとして投稿します。")
+    }
+    fn delivery_daily(&self, time: &str, time_zone: &str) -> String {
+        format!("以降、本チャンネルは収集したメッセージを毎日 {time} ({time_zone}) にダイジェストとして投稿します。")
+    }
+    fn quiet_off(&self) -> &'static str {
+        "本チャンネルのおやすみ時間を解除しました。"
+    }
+    fn quiet_hours(&self, start: &str, end: &str, time_zone: &str) -> String {
+        format!("以降、本チャンネルは {start} から {end} ({time_zone}) の間に収集したメッセージを保留し、終了後にまとめて投稿します。")
+    }
+    fn digest_header(&self, count: usize) -> String {
+        format!("収集したメッセージのダイジェストです。({count}件)")
+    }
+    fn digest_count(&self, count: usize) -> String {
+        format!("({count}件)")
+    }
+    fn digest_continued(&self) -> &'static str {
+        "ダイジェストの続きです。"
+    }
+
+    fn lang_set(&self, lang: Lang) -> String {
+        format!("以降、日本語 ({lang}) で返信します。")
+    }
+    fn lang_auto(&self, lang: Lang) -> String {
+        format!("言語の設定を解除しました。Slack の言語設定に従い、日本語 ({lang}) で返信します。")
+    }
+    fn lang_current(&self, lang: Lang, chosen: bool) -> String {
+        if chosen {
+            format!("日本語 ({lang}) で返信しています。")
+        } else {
+            format!("Slack の言語設定に従い、日本語 ({lang}) で返信しています。")
+        }
+    }
+
+    fn your_tags(&self) -> &'static str {
+        "あなたのタグ"
+    }
+    fn no_tags(&self) -> &'static str {
+        "タグはありません。`/channel_bugyo add` で作成できます。"
+    }
+    fn too_many_tags(&self) -> &'static str {
+        "表示しきれないタグがあります。`/channel_bugyo tag_list` で確認してください。"
+    }
+    fn home_tag(&self, tag: &str, channels: &str) -> String {
+        format!("*{tag}*\n登録チャンネル: {channels}")
+    }
+    fn home_dist(&self, dist: &SlackChannelId) -> String {
+        format!("収集先: <#{dist}>")
+    }
+    fn edit(&self) -> &'static str {
+        "編集"
+    }
+    fn unset(&self) -> &'static str {
+        "解除"
+    }
+    fn delete(&self) -> &'static str {
+        "削除"
+    }
+    fn cancel(&self) -> &'static str {
+        "キャンセル"
+    }
+    fn drop_title(&self) -> &'static str {
+        "タグの削除"
+    }
+
+    fn tag_to_change(&self) -> &'static str {
+        "変更するタグ"
+    }
+    fn pick_tag(&self) -> &'static str {
+        "タグを選択"
+    }
+    fn new_tag(&self) -> &'static str {
+        "新しいタグ"
+    }
+    fn new_tag_placeholder(&self) -> &'static str {
+        "新しく作成するタグの名前"
+    }
+    fn visibility(&self) -> &'static str {
+        "新しいタグの公開範囲"
+    }
+    fn private_tag(&self) -> &'static str {
+        "プライベートタグ"
+    }
+    fn public_tag(&self) -> &'static str {
+        "パブリックタグ"
+    }
+    fn shared_tag(&self, tag: &str, owner: &str) -> String {
+        format!("{tag} ({owner} さんが共有)")
+    }
+    fn channels_to_add(&self) -> &'static str {
+        "タグに追加するチャンネル"
+    }
+    fn pick_channels(&self) -> &'static str {
+        "チャンネルを選択"
+    }
+    fn bot_messages(&self) -> &'static str {
+        "ボットによるメッセージ"
+    }
+    fn keep(&self) -> &'static str {
+        "変更しない"
+    }
+    fn collect(&self) -> &'static str {
+        "収集する"
+    }
+    fn ignore(&self) -> &'static str {
+        "無視する"
+    }
+    fn tags_to_set(&self) -> &'static str {
+        "このチャンネルで収集するタグ"
+    }
+    fn save(&self) -> &'static str {
+        "保存"
+    }
+}
+
+// what an invalid argument should have been
+fn expected_text(expected: &Expected) -> String {
+    match expected {
+        Expected::OneOf(words) => format!("{} のいずれかを指定してください", words.join("、")),
+        Expected::Like(example) => format!("{example} の形式で指定してください"),
+        Expected::PositiveNumber => "正の数を指定してください".to_string(),
+        Expected::ChannelMention => {
+            "#general のようにチャンネルをメンションしてください".to_string()
+        }
+        Expected::UserMention => "@someone のようにユーザーをメンションしてください".to_string(),
+        Expected::MemberMention => "ユーザーかユーザーグループをメンションしてください".to_string(),
+        Expected::Sender => {
+            "ユーザーをメンションするか、ユーザーまたはボットの ID を指定してください".to_string()
+        }
+        Expected::Regex(reason) => format!("正規表現として解釈できません\n{reason}"),
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use slack_morphism::{
+    prelude::{
+        SlackClientEventsUserState, SlackHyperClient, SlackInteractionBlockActionsEvent,
+        SlackInteractionEvent, SlackInteractionViewSubmissionEvent, SlackView,
+    },
+    SlackChannelId, SlackUserId,
+};
+
+use crate::{
+    commands::{
+        home,
+        modal::{self, ModalContext},
+    },
+    response,
+    state::{self, AppState},
+};
+
+// acknowledge at once, which also closes the modal, and apply the submission afterwards
+pub async fn spawned_interaction_handler(
+    event: SlackInteractionEvent,
+    cli: Arc<SlackHyperClient>,
+    state: SlackClientEventsUserState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match event {
+        SlackInteractionEvent::ViewSubmission(event) => {
+            tokio::spawn(async move {
+                let _ = view_submission_catch_error(event, cli, state).await;
+            });
+        }
+        SlackInteractionEvent::BlockActions(event) => {
+            tokio::spawn(async move {
+                let _ = block_actions_catch_error(event, cli, state).await;
+            });
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// the buttons of App Home; the view keeps the app's DM, where the result is told
+async fn block_actions_catch_error(
+    event: SlackInteractionBlockActionsEvent,
+    cli: Arc<SlackHyperClient>,
+    state: SlackClientEventsUserState,
+) -> anyhow::Result<()> {
+    let Some(SlackView::Home(view)) = &event.view else {
+        return Ok(());
+    };
+    let channel_id = SlackChannelId::new(
+        view.private_metadata
+            .clone()
+            .context("the channel of the home view is missing")?,
+    );
+    let user_id = event
+        .user
+        .as_ref()
+        .context("the user is missing")?
+        .id
+        .clone();
+
+    let app_state = state::app_state(&state).await?;
+    match block_actions_handler(&event, cli.clone(), &app_state, &channel_id, &user_id).await {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let storage = app_state.storage.as_ref();
+            response::post_error(cli, storage, channel_id, user_id, &err).await
+        }
+    }
+}
+
+async fn block_actions_handler(
+    event: &SlackInteractionBlockActionsEvent,
+    cli: Arc<SlackHyperClient>,
+    app_state: &AppState,
+    channel_id: &SlackChannelId,
+    user_id: &SlackUserId,
+) -> anyhow::Result<()> {
+    // a click on a button carries just that button
+    let result = match event.actions.as_ref().and_then(|actions| actions.first()) {
+        Some(action) => {
+            home::home_action(
+                cli,
+                app_state.storage.as_ref(),
+                event.trigger_id.clone(),
+                user_id.clone(),
+                channel_id,
+                action,
+            )
+            .await
+        }
+        None => Ok(()),
+    };
+
+    // the routes are reloaded even when the action failed halfway
+    app_state.routing.invalidate().await;
+
+    result
+}
+
+async fn view_submission_catch_error(
+    event: SlackInteractionViewSubmissionEvent,
+    cli: Arc<SlackHyperClient>,
+    state: SlackClientEventsUserState,
+) -> anyhow::Result<()> {
+    let SlackView::Modal(view) = &event.view.view else {
+        return Ok(());
+    };
+    if view.callback_id.as_ref().map(|id| id.0.as_str()) != Some(modal::CALLBACK_ID) {
+        return Ok(());
+    }
+    // the result is told in the channel the modal was opened from
+    let context = ModalContext::from_metadata(
+        view.private_metadata
+            .as_deref()
+            .context("the channel of the modal is missing")?,
+    );
+    let user_id = event.user.id.clone();
+
+    let app_state = state::app_state(&state).await?;
+    match view_submission_handler(&event, cli.clone(), &app_state, &context, &user_id).await {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let storage = app_state.storage.as_ref();
+            response::post_error(cli, storage, context.channel_id, user_id, &err).await
+        }
+    }
+}
+
+async fn view_submission_handler(
+    event: &SlackInteractionViewSubmissionEvent,
+    cli: Arc<SlackHyperClient>,
+    app_state: &AppState,
+    context: &ModalContext,
+    user_id: &SlackUserId,
+) -> anyhow::Result<()> {
+    let view_state = event
+        .view
+        .state_params
+        .state
+        .as_ref()
+        .context("the state of the modal is missing")?;
+    let submission = modal::parse_submission(view_state)?;
+
+    let result = modal::apply_submission(
+        cli.clone(),
+        app_state.storage.as_ref(),
+        context.channel_id.clone(),
+        user_id.clone(),
+        &submission,
+    )
+    .await;
+
+    // the routes are reloaded even when the submission failed halfway
+    app_state.routing.invalidate().await;
+
+    // App Home is shown again behind the closed modal, so it has to show the change
+    if context.from_home {
+        home::publish_home(
+            cli,
+            app_state.storage.as_ref(),
+            user_id.clone(),
+            &context.channel_id,
+        )
+        .await?;
+    }
+
+    result
+}
@@ -1,10 +1,20 @@
 #![warn(clippy::pedantic)]
+mod cli;
 mod command_event_handler;
+mod command_parser;
 mod commands;
+mod config_file;
+mod digest;
+mod i18n;
+mod interaction_event_handler;
 mod post_message;
 mod process_message;
 mod push_event_handler;
 mod query;
+mod response;
+mod routing_cache;
+mod state;
+mod storage;
 mod utils;
 
 use slack_morphism::prelude::*;
@@ -13,11 +23,21 @@ use std::sync::Arc;
 async fn socket_mode_process() -> anyhow::Result<()> {
     let app_token = utils::get_token(&SlackApiTokenType::App)?;
     let client = Arc::new(SlackClient::new(SlackClientHyperConnector::new()));
+    // a single storage is shared by every handler, sqlite or PostgreSQL by DATABASE_URL
+    let storage = storage::connect(&query::db_url()?).await?;
+    let routing = routing_cache::RoutingCache::default();
+    routing.warm_up(storage.as_ref()).await?;
     let socket_mode_callbacks = SlackSocketModeListenerCallbacks::new()
         .with_push_events(push_event_handler::push_event_handler)
-        .with_command_events(command_event_handler::spawned_command_handler);
+        .with_command_events(command_event_handler::spawned_command_handler)
+        .with_interaction_events(interaction_event_handler::spawned_interaction_handler);
     let listner_environment = Arc::new(
-        SlackClientEventsListenerEnvironment::new(client.clone()).with_error_handler(error_handler),
+        SlackClientEventsListenerEnvironment::new(client.clone())
+            .with_error_handler(error_handler)
+            .with_user_state(state::AppState {
+                storage: storage.clone(),
+                routing,
+            }),
     );
     let socket_mode_listner = SlackClientSocketModeListener::new(
         &SlackClientSocketModeConfig::new(),
@@ -25,6 +45,8 @@ async fn socket_mode_process() -> anyhow::Result<()> {
         socket_mode_callbacks,
     );
 
+    tokio::spawn(digest::delivery_loop(client.clone(), storage));
+
     socket_mode_listner.listen_for(&app_token).await?;
     socket_mode_listner.serve().await;
     Ok(())
@@ -42,7 +64,13 @@ fn error_handler(
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    socket_mode_process().await?;
+    // backups run against DATABASE_URL without connecting to Slack
+    let mut args = std::env::args();
+    match args.nth(1).as_deref() {
+        Some("export") => cli::export(args).await?,
+        Some("import") => cli::import(args).await?,
+        _ => socket_mode_process().await?,
+    }
 
     Ok(())
 }
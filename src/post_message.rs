@@ -4,8 +4,9 @@ use anyhow::Context;
 use rsb_derive::Builder;
 use slack_morphism::{
     prelude::{
-        SlackApiChatPostEphemeralRequest, SlackApiChatPostEphemeralResponse,
-        SlackApiChatPostMessageRequest, SlackApiChatPostMessageResponse, SlackHyperClient,
+        SlackApiChatDeleteRequest, SlackApiChatPostEphemeralRequest,
+        SlackApiChatPostEphemeralResponse, SlackApiChatPostMessageRequest,
+        SlackApiChatPostMessageResponse, SlackApiChatUpdateRequest, SlackHyperClient,
     },
     SlackApiTokenType, SlackChannelId, SlackMessageContent, SlackUserId,
 };
@@ -19,7 +20,7 @@ pub enum SlackApiMessageRequest {
 }
 #[derive(Debug, Clone)]
 pub enum SlackApiMessageResponse {
-    PostMessage(SlackApiChatPostMessageResponse),
+    PostMessage(Box<SlackApiChatPostMessageResponse>),
     PostEphemeral(SlackApiChatPostEphemeralResponse),
 }
 #[derive(Debug, Clone, Builder)]
@@ -35,12 +36,12 @@ impl MessagePoster {
         let session = self.cli.open_session(&token);
         let content = SlackMessageContent::new().with_text(self.text.clone());
         let req = SlackApiChatPostMessageRequest::new(self.channel.clone(), content);
-        let message_res = SlackApiMessageResponse::PostMessage(
+        let message_res = SlackApiMessageResponse::PostMessage(Box::new(
             session
                 .chat_post_message(&req)
                 .await
                 .context("failed to post message.")?,
-        );
+        ));
         Ok(message_res)
     }
     pub async fn post_ephemeral(
@@ -68,18 +69,44 @@ pub async fn send_req(
     let app_token = utils::get_token(&SlackApiTokenType::Bot)?;
     let session = cli.open_session(&app_token);
     let message_res: SlackApiMessageResponse = match req {
-        SlackApiMessageRequest::PostMessage(req) => SlackApiMessageResponse::PostMessage(
+        SlackApiMessageRequest::PostMessage(req) => SlackApiMessageResponse::PostMessage(Box::new(
             session
                 .chat_post_message(&req)
                 .await
                 .context("failed to post message.")?,
-        ),
-        // SlackApiMessageRequest::PostEphemeral(req) => SlackApiMessageResponse::PostEphemeral(
-        //     session
-        //         .chat_post_ephemeral(&req)
-        //         .await
-        //         .context("failed to post message.")?,
-        // ),
+        )), // SlackApiMessageRequest::PostEphemeral(req) => SlackApiMessageResponse::PostEphemeral(
+            //     session
+            //         .chat_post_ephemeral(&req)
+            //         .await
+            //         .context("failed to post message.")?,
+            // ),
     };
     Ok(message_res)
 }
+
+// Edits and deletions only tell whether they succeeded
+pub async fn update_message(
+    cli: Arc<SlackHyperClient>,
+    req: SlackApiChatUpdateRequest,
+) -> anyhow::Result<()> {
+    let app_token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&app_token);
+    session
+        .chat_update(&req)
+        .await
+        .context("failed to update message.")?;
+    Ok(())
+}
+
+pub async fn delete_message(
+    cli: Arc<SlackHyperClient>,
+    req: SlackApiChatDeleteRequest,
+) -> anyhow::Result<()> {
+    let app_token = utils::get_token(&SlackApiTokenType::Bot)?;
+    let session = cli.open_session(&app_token);
+    session
+        .chat_delete(&req)
+        .await
+        .context("failed to delete message.")?;
+    Ok(())
+}
@@ -3,13 +3,54 @@ pub mod sender_profile;
 use anyhow::Context;
 use regex::Regex;
 
+use serde_json::{json, Value};
 use slack_morphism::{
-    prelude::{SlackApiChatPostMessageRequest, SlackMessageEvent},
-    SlackChannelId, SlackMessageContent,
+    prelude::{
+        SlackApiChatPostMessageRequest, SlackApiChatUpdateRequest, SlackBlock,
+        SlackBlockMarkDownText, SlackBlockText, SlackContextBlock, SlackContextBlockElement,
+        SlackMessageEvent, SlackMessageEventType, SlackSectionBlock,
+    },
+    SlackChannelId, SlackFile, SlackMessageContent, SlackTs,
 };
 
+use crate::query::{dist_target_map::MessageSummary, thread_mode::ThreadPosition};
+
 use self::sender_profile::SenderProfile;
 
+pub fn summarize(msg_eve: &SlackMessageEvent) -> MessageSummary {
+    let text = msg_eve
+        .content
+        .as_ref()
+        .and_then(|content| content.text.clone())
+        .unwrap_or_default();
+    MessageSummary {
+        position: thread_position(msg_eve),
+        has_files: has_files(msg_eve),
+        text,
+    }
+}
+
+fn thread_position(msg_eve: &SlackMessageEvent) -> ThreadPosition {
+    let is_reply = msg_eve
+        .origin
+        .thread_ts
+        .as_ref()
+        .is_some_and(|thread_ts| *thread_ts != msg_eve.origin.ts);
+    match (is_reply, &msg_eve.subtype) {
+        (false, _) => ThreadPosition::Root,
+        (true, Some(SlackMessageEventType::ThreadBroadcast)) => ThreadPosition::BroadcastReply,
+        (true, _) => ThreadPosition::Reply,
+    }
+}
+
+fn has_files(msg_eve: &SlackMessageEvent) -> bool {
+    msg_eve
+        .content
+        .as_ref()
+        .and_then(|content| content.files.as_ref())
+        .is_some_and(|files| !files.is_empty())
+}
+
 pub fn message_event_to_req(
     msg_eve: SlackMessageEvent,
     channel_to: SlackChannelId,
@@ -29,18 +70,168 @@ pub fn message_event_to_req(
     Ok(post_req)
 }
 
+pub fn message_changed_to_req(
+    msg_eve: SlackMessageEvent,
+    channel_to: SlackChannelId,
+    forwarded_ts: SlackTs,
+) -> anyhow::Result<SlackApiChatUpdateRequest> {
+    let edited = msg_eve.message.context("cannot get edited message")?;
+    let raw_content = edited.content.context("cannot get message content")?;
+    let channel_from = msg_eve
+        .origin
+        .channel
+        .context("cannot specify where the message from")?;
+    let new_content = process_message(&raw_content, &channel_from)?;
+
+    let update_req = SlackApiChatUpdateRequest::new(channel_to, new_content, forwarded_ts);
+
+    Ok(update_req)
+}
+
 fn process_message(
     msg_content: &SlackMessageContent,
     channel_from: &SlackChannelId,
 ) -> anyhow::Result<SlackMessageContent> {
-    let raw_txt = msg_content.text.clone().context("cannot get message")?;
-    let raw_txt_no_mention = escape_mention(&raw_txt)?;
-    let new_txt = format!(" `<#{channel_from}>` {raw_txt_no_mention}");
-    let new_content = msg_content.clone().with_text(new_txt).without_blocks();
+    let (raw_txt_no_mention, files_txt) = process_text(msg_content)?;
+    let new_txt = format!(" `<#{channel_from}>` {raw_txt_no_mention}{files_txt}");
+    let new_content = msg_content.clone().with_text(new_txt).without_files();
+
+    // the text is kept as a fallback for notifications when blocks are present
+    let new_content = match &msg_content.blocks {
+        Some(blocks) if !blocks.is_empty() => {
+            let new_blocks = process_blocks(blocks, channel_from, &files_txt)?;
+            new_content.with_blocks(new_blocks)
+        }
+        _ => new_content.without_blocks(),
+    };
 
     Ok(new_content)
 }
 
+// Plain text of the message, for deliveries that cannot keep blocks such as digests
+pub fn message_event_to_text(msg_eve: &SlackMessageEvent) -> anyhow::Result<String> {
+    let raw_content = msg_eve
+        .content
+        .as_ref()
+        .context("cannot get message content")?;
+    let (raw_txt_no_mention, files_txt) = process_text(raw_content)?;
+    Ok(format!("{raw_txt_no_mention}{files_txt}"))
+}
+
+// Plain text of the edited message, for the copies still waiting in the queue
+pub fn message_changed_to_text(msg_eve: &SlackMessageEvent) -> anyhow::Result<String> {
+    let raw_content = msg_eve
+        .message
+        .as_ref()
+        .and_then(|edited| edited.content.as_ref())
+        .context("cannot get message content")?;
+    let (raw_txt_no_mention, files_txt) = process_text(raw_content)?;
+    Ok(format!("{raw_txt_no_mention}{files_txt}"))
+}
+
+fn process_text(msg_content: &SlackMessageContent) -> anyhow::Result<(String, String)> {
+    let files = msg_content.files.clone().unwrap_or_default();
+    let raw_txt = match msg_content.text.clone() {
+        Some(txt) => txt,
+        None if !files.is_empty() => String::new(),
+        None => return Err(anyhow::anyhow!("cannot get message")),
+    };
+    let raw_txt_no_mention = escape_mention(&raw_txt)?;
+    let files_txt = files.iter().map(file_to_text).collect::<String>();
+    Ok((raw_txt_no_mention, files_txt))
+}
+
+fn process_blocks(
+    blocks: &[SlackBlock],
+    channel_from: &SlackChannelId,
+    files_txt: &str,
+) -> anyhow::Result<Vec<SlackBlock>> {
+    let source_block = SlackBlock::Context(SlackContextBlock::new(vec![
+        SlackContextBlockElement::MarkDown(SlackBlockMarkDownText::new(format!(
+            "<#{channel_from}>"
+        ))),
+    ]));
+
+    let rewritten_blocks = blocks
+        .iter()
+        .map(|block| {
+            let new_block = match block {
+                SlackBlock::RichText(rich_text) => {
+                    SlackBlock::RichText(escape_rich_text_mention(rich_text))
+                }
+                SlackBlock::Section(section) => match &section.text {
+                    Some(SlackBlockText::MarkDown(md)) => {
+                        let new_md = SlackBlockMarkDownText::new(escape_mention(&md.text)?);
+                        SlackBlock::Section(
+                            section.clone().with_text(SlackBlockText::MarkDown(new_md)),
+                        )
+                    }
+                    _ => block.clone(),
+                },
+                _ => block.clone(),
+            };
+            anyhow::Ok(new_block)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let files_block = (!files_txt.is_empty()).then(|| {
+        SlackBlock::Section(SlackSectionBlock::new().with_text(SlackBlockText::MarkDown(
+            SlackBlockMarkDownText::new(files_txt.trim_start().to_string()),
+        )))
+    });
+
+    let new_blocks = std::iter::once(source_block)
+        .chain(rewritten_blocks)
+        .chain(files_block)
+        .collect::<Vec<_>>();
+
+    Ok(new_blocks)
+}
+
+// Replace user elements in a rich_text tree with plain text, as escape_mention does
+fn escape_rich_text_mention(rich_text: &Value) -> Value {
+    match rich_text {
+        Value::Object(element) if element.get("type") == Some(&json!("user")) => {
+            let user_id = element
+                .get("user_id")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let mut text_element = json!({ "type": "text", "text": format!("@{user_id}") });
+            if let Some(style) = element.get("style") {
+                text_element["style"] = style.clone();
+            }
+            text_element
+        }
+        Value::Object(element) => Value::Object(
+            element
+                .iter()
+                .map(|(key, value)| (key.clone(), escape_rich_text_mention(value)))
+                .collect(),
+        ),
+        Value::Array(elements) => {
+            Value::Array(elements.iter().map(escape_rich_text_mention).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+// Show a shared file as a line with its name, type and permalink
+fn file_to_text(file: &SlackFile) -> String {
+    let name = file
+        .name
+        .clone()
+        .or_else(|| file.title.clone())
+        .unwrap_or_else(|| "file".to_string());
+    let file_type = file
+        .filetype
+        .as_ref()
+        .map_or(String::new(), |filetype| format!(" ({filetype})"));
+    match &file.permalink {
+        Some(permalink) => format!("\n:paperclip: <{permalink}|{name}>{file_type}"),
+        None => format!("\n:paperclip: {name}{file_type}"),
+    }
+}
+
 fn escape_mention(txt: &str) -> anyhow::Result<String> {
     let re = Regex::new(r"<@([A-Z0-9]+)>")?;
     let new_txt = re
@@ -64,4 +255,28 @@ mod tests {
         let new = escape_mention(test_txt).unwrap();
         assert_eq!("test mention @U12345T435T test".to_string(), new);
     }
+
+    #[tokio::test]
+    async fn escape_rich_text_mention_test() {
+        let rich_text = json!({
+            "type": "rich_text",
+            "elements": [{
+                "type": "rich_text_section",
+                "elements": [
+                    { "type": "text", "text": "test mention " },
+                    { "type": "user", "user_id": "U12345T435T", "style": { "bold": true } },
+                    { "type": "text", "text": " test" }
+                ]
+            }]
+        });
+        let new = escape_rich_text_mention(&rich_text);
+        let mention = &new["elements"][0]["elements"][1];
+        assert_eq!(mention["type"], "text");
+        assert_eq!(mention["text"], "@U12345T435T");
+        assert_eq!(mention["style"]["bold"], true);
+        assert_eq!(
+            new["elements"][0]["elements"][0],
+            rich_text["elements"][0]["elements"][0]
+        );
+    }
 }
@@ -93,7 +93,7 @@ impl GetterProfile for SlackApiUsersProfileGetResponse {
     }
 
     fn get_display_name(&self) -> anyhow::Result<String> {
-        let real_name = self.profile.real_name.clone().unwrap_or(String::new());
+        let real_name = self.profile.real_name.clone().unwrap_or_default();
         let display_name = self
             .profile
             .display_name
@@ -115,7 +115,7 @@ impl GetterProfile for SlackApiBotsInfoResponse {
             .icons
             .clone()
             .context("failed to get icon images:bot")?;
-        let icon = icons.resolutions.get(0).cloned().unwrap_or((
+        let icon = icons.resolutions.first().cloned().unwrap_or((
             512,
             "https://avatars.slack-edge.com/2023-03-18/4975228596980_b7f6572d76d9104bbc72_512.png"
                 .to_string(),
@@ -126,6 +126,6 @@ impl GetterProfile for SlackApiBotsInfoResponse {
     }
     fn get_display_name(&self) -> anyhow::Result<String> {
         let bot_name = &self.bot.name;
-        Ok(bot_name.to_string())
+        Ok(bot_name.clone())
     }
 }
@@ -1,85 +1,307 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Context;
 use slack_morphism::{
     prelude::{
-        events::SlackEventCallbackBody::*, SlackApiChatPostMessageRequest,
-        SlackClientEventsUserState, SlackHyperClient, SlackPushEventCallback,
+        events::SlackEventCallbackBody::*, SlackApiChatDeleteRequest,
+        SlackApiChatPostMessageRequest, SlackClientEventsUserState, SlackHyperClient,
+        SlackMessageEvent, SlackMessageEventType, SlackPushEventCallback,
     },
-    SlackMessageContent,
+    SlackChannelId, SlackMessageContent,
 };
 use tokio_stream::StreamExt;
 
 use crate::{
+    commands::home,
     post_message::{self, SlackApiMessageRequest, SlackApiMessageResponse},
-    process_message::{self, sender_profile::fetch_profile},
-    query::dist_target_map,
+    process_message::{
+        self,
+        sender_profile::{fetch_profile, SenderProfile},
+    },
+    query::{
+        delivery, delivery_queue::QueuedMessage, dist_target_map, thread_mode::ThreadPosition,
+    },
+    state::{self, AppState},
+    storage::Storage,
 };
 
 pub async fn push_event_handler(
     event: SlackPushEventCallback,
     cli: Arc<SlackHyperClient>,
-    _state: SlackClientEventsUserState,
+    state: SlackClientEventsUserState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let app_state = state::app_state(&state).await?;
     match event.event {
-        Message(msg_event) => {
-            let channel_id_from = msg_event
-                .clone()
-                .origin
-                .channel
-                .context("cannot get channel id")?;
-            let sender = msg_event.clone().sender;
-
-            let is_target =
-                dist_target_map::is_target_for_some(channel_id_from.clone(), sender.clone())
-                    .await?;
-            if !is_target {
-                return Ok(());
+        Message(msg_event) => match msg_event.subtype {
+            Some(SlackMessageEventType::MessageChanged) => {
+                update_forwarded(msg_event, cli, app_state.storage.as_ref()).await?;
             }
-
-            let dists = dist_target_map::target_to_dists(channel_id_from, sender.clone()).await?;
-
-            let sender_profile = fetch_profile(cli.clone(), sender).await?;
-
-            let message_reqs = dists
-                .iter()
-                .map(|dist| {
-                    process_message::message_event_to_req(
-                        msg_event.clone(),
-                        dist.clone(),
-                        sender_profile.clone(),
-                    )
-                    .unwrap_or_else(|err| {
-                        let err_message = err.to_string();
-                        SlackApiChatPostMessageRequest::new(
-                            dist.clone(),
-                            SlackMessageContent::new().with_text(err_message),
-                        )
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            let req_stream = futures::stream::iter(message_reqs);
-            let ress = req_stream
-                .map(|msg_req| {
-                    let cli_clone = Arc::clone(&cli);
-                    async move {
-                        let res = post_message::send_req(
-                            cli_clone,
-                            SlackApiMessageRequest::PostMessage(msg_req.clone()),
-                        )
-                        .await?;
-                        anyhow::Ok::<SlackApiMessageResponse>(res)
-                    }
-                })
-                .then(|s| s)
-                .collect::<Vec<_>>()
-                .await;
+            Some(SlackMessageEventType::MessageDeleted) => {
+                delete_forwarded(msg_event, cli, app_state.storage.as_ref()).await?;
+            }
+            _ => forward_message(msg_event, cli, &app_state).await?,
+        },
+        // the view is built anew each time, so it never shows stale tags
+        AppHomeOpened(home_event) if home_event.tab == "home" => {
+            home::publish_home(
+                cli,
+                app_state.storage.as_ref(),
+                home_event.user,
+                &home_event.channel,
+            )
+            .await?;
         }
         MemberJoinedChannel(_join_event) => {}
         MemberLeftChannel(_left_event) => {}
         _ => {}
+    }
+
+    Ok(())
+}
+
+async fn forward_message(
+    msg_event: SlackMessageEvent,
+    cli: Arc<SlackHyperClient>,
+    app_state: &AppState,
+) -> anyhow::Result<()> {
+    let storage = app_state.storage.as_ref();
+    let channel_id_from = msg_event
+        .clone()
+        .origin
+        .channel
+        .context("cannot get channel id")?;
+    let sender = msg_event.clone().sender;
+
+    // channels nobody collects are rejected from the cached routes, without a query
+    let routes = app_state
+        .routing
+        .channel_routes(&channel_id_from, storage)
+        .await?;
+    let is_target = dist_target_map::is_target_for_some(&routes, &sender)?;
+    if !is_target {
+        return Ok(());
+    }
+
+    let summary = process_message::summarize(&msg_event);
+    let position = summary.position;
+    let dists = dist_target_map::target_to_dists(&routes, &sender, &summary);
+    if dists.is_empty() {
+        return Ok(());
+    }
+
+    // replies are posted under the parent forwarded to the same dist channel
+    let forwarded_parents = match (position, msg_event.origin.thread_ts.clone()) {
+        (ThreadPosition::Reply | ThreadPosition::BroadcastReply, Some(thread_ts)) => storage
+            .forwarded_list(&channel_id_from, &thread_ts)
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+        _ => HashMap::new(),
+    };
+
+    let sender_profile = fetch_profile(cli.clone(), sender).await?;
+
+    // dist channels in digest mode or in quiet hours receive the message later, from the delivery loop
+    let now = delivery::now();
+    let schedules = app_state.routing.schedules(storage).await?;
+    let (later_dists, dists): (Vec<_>, Vec<_>) = dists.into_iter().partition(|dist| {
+        schedules
+            .get(dist)
+            .is_some_and(|schedule| schedule.is_digest() || schedule.is_quiet(now))
+    });
+    let message_reqs = dists
+        .iter()
+        .map(|dist| {
+            let msg_req = process_message::message_event_to_req(
+                msg_event.clone(),
+                dist.clone(),
+                sender_profile.clone(),
+            )
+            .unwrap_or_else(|err| {
+                let err_message = err.to_string();
+                SlackApiChatPostMessageRequest::new(
+                    dist.clone(),
+                    SlackMessageContent::new().with_text(err_message),
+                )
+            });
+            match forwarded_parents.get(dist) {
+                Some(parent_ts) => msg_req
+                    .with_thread_ts(parent_ts.clone())
+                    .with_reply_broadcast(position == ThreadPosition::BroadcastReply),
+                None => msg_req,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let req_stream = futures::stream::iter(message_reqs);
+    let ress = req_stream
+        .map(|msg_req| {
+            let cli_clone = Arc::clone(&cli);
+            async move {
+                let res = post_message::send_req(
+                    cli_clone,
+                    SlackApiMessageRequest::PostMessage(msg_req.clone()),
+                )
+                .await?;
+                anyhow::Ok::<SlackApiMessageResponse>(res)
+            }
+        })
+        .then(|s| s)
+        .collect::<Vec<_>>()
+        .await;
+
+    // keep track of the forwarded copies, so that later edits and deletions can be mirrored
+    // a mapping that fails to be saved only loses the mirroring of that copy, so the rest go on
+    let source_ts = msg_event.origin.ts.clone();
+    for res in ress {
+        if let Ok(SlackApiMessageResponse::PostMessage(posted)) = res {
+            if let Err(err) = storage
+                .register_forwarded(&channel_id_from, &source_ts, &posted.channel, &posted.ts)
+                .await
+            {
+                println!("err:{err:#?}");
+            }
+        }
+    }
+
+    // the queue is filled after the immediate posts, which its failures must not hold back
+    queue_message(
+        &msg_event,
+        &channel_id_from,
+        &sender_profile,
+        later_dists,
+        storage,
+    )
+    .await;
+
+    Ok(())
+}
+
+// Failures are only logged: a message that cannot be queued is not delivered to that dist
+async fn queue_message(
+    msg_event: &SlackMessageEvent,
+    channel_id_from: &SlackChannelId,
+    sender_profile: &SenderProfile,
+    dists: Vec<SlackChannelId>,
+    storage: &dyn Storage,
+) {
+    if dists.is_empty() {
+        return;
+    }
+
+    let text = match process_message::message_event_to_text(msg_event) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("err:{err:#?}");
+            return;
+        }
     };
+    let queued_at = delivery::now();
+    for dist in dists {
+        let message = QueuedMessage {
+            queue_id: 0,
+            dist,
+            source_channel: channel_id_from.clone(),
+            source_ts: msg_event.origin.ts.clone(),
+            sender_name: sender_profile.name.clone(),
+            icon_url: sender_profile.icon_url.to_string(),
+            text: text.clone(),
+            queued_at,
+        };
+        if let Err(err) = storage.enqueue(&message).await {
+            println!("err:{err:#?}");
+        }
+    }
+}
+
+async fn update_forwarded(
+    msg_event: SlackMessageEvent,
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+) -> anyhow::Result<()> {
+    let channel_id_from = msg_event
+        .clone()
+        .origin
+        .channel
+        .context("cannot get channel id")?;
+    let source_ts = msg_event
+        .clone()
+        .message
+        .context("cannot get edited message")?
+        .ts;
+
+    // copies still waiting in the queue are delivered with the edited text
+    let text = process_message::message_changed_to_text(&msg_event)?;
+    storage
+        .update_source(&channel_id_from, &source_ts, &text)
+        .await?;
+
+    let forwarded = storage.forwarded_list(&channel_id_from, &source_ts).await?;
+
+    let update_reqs = forwarded
+        .into_iter()
+        .map(|(dist, forwarded_ts)| {
+            process_message::message_changed_to_req(msg_event.clone(), dist, forwarded_ts)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let req_stream = futures::stream::iter(update_reqs);
+    let _ress = req_stream
+        .map(|update_req| {
+            let cli_clone = Arc::clone(&cli);
+            async move { post_message::update_message(cli_clone, update_req).await }
+        })
+        .then(|s| s)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(())
+}
+
+async fn delete_forwarded(
+    msg_event: SlackMessageEvent,
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+) -> anyhow::Result<()> {
+    let channel_id_from = msg_event
+        .clone()
+        .origin
+        .channel
+        .context("cannot get channel id")?;
+    let source_ts = msg_event.deleted_ts.context("cannot get deleted message")?;
+
+    // the message must not be delivered later from the queue either
+    storage.remove_source(&channel_id_from, &source_ts).await?;
+
+    let forwarded = storage.forwarded_list(&channel_id_from, &source_ts).await?;
+    if forwarded.is_empty() {
+        return Ok(());
+    }
+
+    let req_stream = futures::stream::iter(forwarded);
+    let ress = req_stream
+        .map(|(dist, forwarded_ts)| {
+            let cli_clone = Arc::clone(&cli);
+            async move {
+                let req = SlackApiChatDeleteRequest::new(dist.clone(), forwarded_ts);
+                (dist, post_message::delete_message(cli_clone, req).await)
+            }
+        })
+        .then(|s| s)
+        .collect::<Vec<_>>()
+        .await;
+
+    // a copy that failed to be deleted is kept, so that a later deletion can retry it
+    for (dist, res) in ress {
+        match res {
+            Ok(()) => {
+                storage
+                    .unregister_forwarded(&channel_id_from, &source_ts, &dist)
+                    .await?;
+            }
+            Err(err) => println!("err:{err:#?}"),
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,461 @@
+use std::{fmt, str::FromStr};
+
+use slack_morphism::{SlackChannelId, SlackUserId};
+use sqlx::{Pool, Sqlite};
+
+use super::delivery;
+
+// The actor of the changes made by `slack-some-things import`, which has no Slack user
+pub const CLI_ACTOR: &str = "cli";
+
+// The commands that change tags, distributions, admins and preferences
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditAction {
+    Add,
+    Delete,
+    RetrieveBot,
+    RetrieveFile,
+    Thread,
+    Rename,
+    Copy,
+    Merge,
+    DropTag,
+    Set,
+    Unset,
+    Digest,
+    Quiet,
+    FilterAdd,
+    FilterRemove,
+    SenderAdd,
+    SenderRemove,
+    Share,
+    Unshare,
+    AdminAdd,
+    AdminRemove,
+    Lang,
+    Import,
+}
+
+// Who changed what and when; digest and quiet change a dist channel, not a tag.
+// Entries are kept with the id of the tag, so the history of a tag follows it through renames, and
+// tag_name is the name the tag had at the time. The id is looked up by the name when it is not given,
+// which a tag deleted by the change no longer has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub actor: SlackUserId,
+    pub action: AuditAction,
+    pub tag_id: Option<i64>,
+    pub tag_name: Option<String>,
+    pub owner_id: Option<SlackUserId>,
+    pub channels: Vec<SlackChannelId>,
+    pub detail: String,
+    pub created_at: i64,
+}
+
+// Which entries to return, newest first; `viewer` hides the private tags of other users
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AuditQuery {
+    pub tag: Option<(String, SlackUserId)>,
+    pub channel: Option<SlackChannelId>,
+    pub viewer: Option<SlackUserId>,
+    pub limit: i64,
+}
+
+impl FromStr for AuditAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "add" => Ok(AuditAction::Add),
+            "delete" => Ok(AuditAction::Delete),
+            "retrieve_bot" => Ok(AuditAction::RetrieveBot),
+            "retrieve_file" => Ok(AuditAction::RetrieveFile),
+            "thread" => Ok(AuditAction::Thread),
+            "rename" => Ok(AuditAction::Rename),
+            "copy" => Ok(AuditAction::Copy),
+            "merge" => Ok(AuditAction::Merge),
+            "drop_tag" => Ok(AuditAction::DropTag),
+            "set" => Ok(AuditAction::Set),
+            "unset" => Ok(AuditAction::Unset),
+            "digest" => Ok(AuditAction::Digest),
+            "quiet" => Ok(AuditAction::Quiet),
+            "filter_add" => Ok(AuditAction::FilterAdd),
+            "filter_remove" => Ok(AuditAction::FilterRemove),
+            "sender_add" => Ok(AuditAction::SenderAdd),
+            "sender_remove" => Ok(AuditAction::SenderRemove),
+            "share" => Ok(AuditAction::Share),
+            "unshare" => Ok(AuditAction::Unshare),
+            "admin_add" => Ok(AuditAction::AdminAdd),
+            "admin_remove" => Ok(AuditAction::AdminRemove),
+            "lang" => Ok(AuditAction::Lang),
+            "import" => Ok(AuditAction::Import),
+            _ => Err(anyhow::anyhow!("unknown audit action {s}")),
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            AuditAction::Add => "add",
+            AuditAction::Delete => "delete",
+            AuditAction::RetrieveBot => "retrieve_bot",
+            AuditAction::RetrieveFile => "retrieve_file",
+            AuditAction::Thread => "thread",
+            AuditAction::Rename => "rename",
+            AuditAction::Copy => "copy",
+            AuditAction::Merge => "merge",
+            AuditAction::DropTag => "drop_tag",
+            AuditAction::Set => "set",
+            AuditAction::Unset => "unset",
+            AuditAction::Digest => "digest",
+            AuditAction::Quiet => "quiet",
+            AuditAction::FilterAdd => "filter_add",
+            AuditAction::FilterRemove => "filter_remove",
+            AuditAction::SenderAdd => "sender_add",
+            AuditAction::SenderRemove => "sender_remove",
+            AuditAction::Share => "share",
+            AuditAction::Unshare => "unshare",
+            AuditAction::AdminAdd => "admin_add",
+            AuditAction::AdminRemove => "admin_remove",
+            AuditAction::Lang => "lang",
+            AuditAction::Import => "import",
+        };
+        write!(f, "{action}")
+    }
+}
+
+impl AuditEntry {
+    pub fn new(actor: SlackUserId, action: AuditAction) -> Self {
+        AuditEntry {
+            audit_id: 0,
+            actor,
+            action,
+            tag_id: None,
+            tag_name: None,
+            owner_id: None,
+            channels: Vec::new(),
+            detail: String::new(),
+            created_at: delivery::now(),
+        }
+    }
+
+    pub fn with_tag(mut self, tag_name: &str, owner_id: SlackUserId) -> Self {
+        self.tag_name = Some(tag_name.to_string());
+        self.owner_id = Some(owner_id);
+        self
+    }
+
+    pub fn with_tag_id(mut self, tag_id: i64) -> Self {
+        self.tag_id = Some(tag_id);
+        self
+    }
+
+    // The name of the tag after the change, by which its id is looked up
+    pub fn current_tag_name(&self) -> Option<&str> {
+        match self.action {
+            AuditAction::Rename => Some(&self.detail),
+            _ => self.tag_name.as_deref(),
+        }
+    }
+
+    pub fn with_channels(mut self, channels: Vec<SlackChannelId>) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self
+    }
+}
+
+// channels are kept in one column separated by spaces
+pub fn join_channels(channels: &[SlackChannelId]) -> String {
+    channels
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn split_channels(channels: &str) -> Vec<SlackChannelId> {
+    channels
+        .split_whitespace()
+        .map(|channel| SlackChannelId::new(channel.to_string()))
+        .collect()
+}
+
+pub async fn record_with_pool(entry: &AuditEntry, pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let actor_str = entry.actor.to_string();
+    let action_str = entry.action.to_string();
+    let owner_str = entry.owner_id.as_ref().map(ToString::to_string);
+    let channels_str = join_channels(&entry.channels);
+    let current_name = entry.current_tag_name();
+
+    let _query = sqlx::query!(
+        "
+        INSERT INTO audit_log
+            (actor_id, action, tag_name, owner_id, channels, detail, created_at, tag_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7,
+            COALESCE($8, (SELECT tag_id FROM user_folder WHERE tag_name = $9 AND owner_id = $4)))
+        ;",
+        actor_str,
+        action_str,
+        entry.tag_name,
+        owner_str,
+        channels_str,
+        entry.detail,
+        entry.created_at,
+        entry.tag_id,
+        current_name
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn audit_list_with_pool(
+    query: &AuditQuery,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<AuditEntry>> {
+    let (tag_name, owner_str) = match &query.tag {
+        Some((tag_name, owner_id)) => (Some(tag_name.clone()), Some(owner_id.to_string())),
+        None => (None, None),
+    };
+    let channel_str = query.channel.as_ref().map(ToString::to_string);
+    let viewer_str = query.viewer.as_ref().map(ToString::to_string);
+
+    let entries = sqlx::query!(
+        "
+    SELECT audit_id, actor_id, action, tag_id, tag_name, owner_id, channels, detail, created_at
+    FROM audit_log
+    WHERE ($1 IS NULL
+        OR tag_id = (SELECT tag_id FROM user_folder WHERE tag_name = $1 AND owner_id = $2)
+        OR (tag_name = $1 AND owner_id = $2
+            AND NOT EXISTS (SELECT 1 FROM user_folder WHERE tag_name = $1 AND owner_id = $2)))
+    AND ($3 IS NULL OR (' ' || channels || ' ') LIKE ('% ' || $3 || ' %'))
+    AND ($4 IS NULL OR actor_id = $4 OR owner_id = $4 OR owner_id = 'public')
+    ORDER BY audit_id DESC
+    LIMIT $5
+    ",
+        tag_name,
+        owner_str,
+        channel_str,
+        viewer_str,
+        query.limit
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        anyhow::Ok(AuditEntry {
+            audit_id: r.audit_id,
+            actor: SlackUserId::new(r.actor_id),
+            action: r.action.parse::<AuditAction>()?,
+            tag_id: r.tag_id,
+            tag_name: r.tag_name,
+            owner_id: r.owner_id.map(SlackUserId::new),
+            channels: split_channels(&r.channels),
+            detail: r.detail,
+            created_at: r.created_at,
+        })
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn action_test() {
+        for action in [
+            "add",
+            "delete",
+            "retrieve_bot",
+            "thread",
+            "unset",
+            "quiet",
+            "filter_remove",
+            "share",
+            "admin_add",
+            "import",
+        ] {
+            assert_eq!(action.parse::<AuditAction>().unwrap().to_string(), action);
+        }
+        assert!("list".parse::<AuditAction>().is_err());
+    }
+
+    async fn test_record_list(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let other = SlackUserId::new("U00002".to_string());
+        let channel_a = SlackChannelId::new("C0a".to_string());
+        let channel_b = SlackChannelId::new("C0ab".to_string());
+
+        let add = AuditEntry::new(owner_id.clone(), AuditAction::Add)
+            .with_tag("test_a", owner_id.clone())
+            .with_channels(vec![channel_a.clone(), channel_b.clone()]);
+        storage.record_audit(&add).await?;
+        let set = AuditEntry::new(other.clone(), AuditAction::Set)
+            .with_tag("test_pub", SlackUserId::new("public".to_string()))
+            .with_channels(vec![channel_b.clone()]);
+        storage.record_audit(&set).await?;
+        let digest = AuditEntry::new(other.clone(), AuditAction::Digest)
+            .with_channels(vec![channel_b.clone()])
+            .with_detail("every 60");
+        storage.record_audit(&digest).await?;
+
+        let all = AuditQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        let entries = storage.audit_list(&all).await?;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].action, AuditAction::Digest);
+        assert_eq!(entries[0].detail, "every 60");
+        assert_eq!(
+            entries[2].channels,
+            vec![channel_a.clone(), channel_b.clone()]
+        );
+
+        let limited = AuditQuery {
+            limit: 1,
+            ..all.clone()
+        };
+        assert_eq!(storage.audit_list(&limited).await?.len(), 1);
+
+        let by_tag = AuditQuery {
+            tag: Some(("test_a".to_string(), owner_id.clone())),
+            ..all.clone()
+        };
+        let entries = storage.audit_list(&by_tag).await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor, owner_id);
+
+        // channel ids only match as a whole
+        let by_channel = AuditQuery {
+            channel: Some(channel_a),
+            ..all.clone()
+        };
+        assert_eq!(storage.audit_list(&by_channel).await?.len(), 1);
+
+        // the private tag of U00001 is hidden from U00003
+        let by_viewer = AuditQuery {
+            viewer: Some(SlackUserId::new("U00003".to_string())),
+            ..all
+        };
+        let entries = storage.audit_list(&by_viewer).await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, AuditAction::Set);
+
+        Ok(())
+    }
+
+    async fn test_follow_rename(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let channel = SlackChannelId::new("C0a".to_string());
+
+        let add = AuditEntry::new(owner_id.clone(), AuditAction::Add)
+            .with_tag("test_a", owner_id.clone())
+            .with_channels(vec![channel.clone()]);
+        storage.record_audit(&add).await?;
+        storage
+            .rename_tag("test_a", "renamed", owner_id.clone())
+            .await?;
+        let rename = AuditEntry::new(owner_id.clone(), AuditAction::Rename)
+            .with_tag("test_a", owner_id.clone())
+            .with_detail("renamed");
+        storage.record_audit(&rename).await?;
+        // a new tag under the old name has a history of its own
+        storage
+            .register_channel("test_a", channel.clone(), owner_id.clone())
+            .await?;
+        let add_new = AuditEntry::new(owner_id.clone(), AuditAction::Add)
+            .with_tag("test_a", owner_id.clone())
+            .with_channels(vec![channel]);
+        storage.record_audit(&add_new).await?;
+
+        let renamed = AuditQuery {
+            tag: Some(("renamed".to_string(), owner_id.clone())),
+            limit: 10,
+            ..Default::default()
+        };
+        let entries = storage.audit_list(&renamed).await?;
+        let actions = entries.iter().map(|entry| entry.action).collect::<Vec<_>>();
+        assert_eq!(actions, [AuditAction::Rename, AuditAction::Add]);
+        // the entries keep the name the tag had then
+        assert_eq!(entries[1].tag_name.as_deref(), Some("test_a"));
+
+        let new_tag = AuditQuery {
+            tag: Some(("test_a".to_string(), owner_id)),
+            ..renamed
+        };
+        let entries = storage.audit_list(&new_tag).await?;
+        assert_eq!(
+            entries,
+            [AuditEntry {
+                audit_id: entries[0].audit_id,
+                tag_id: entries[0].tag_id,
+                ..add_new
+            }]
+        );
+        assert!(entries[0].tag_id.is_some());
+
+        Ok(())
+    }
+
+    async fn test_deleted_tags(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let channel = SlackChannelId::new("C0a".to_string());
+
+        for tag in ["gone", "merged"] {
+            storage
+                .register_channel(tag, channel.clone(), owner_id.clone())
+                .await?;
+            let add = AuditEntry::new(owner_id.clone(), AuditAction::Add)
+                .with_tag(tag, owner_id.clone())
+                .with_channels(vec![channel.clone()]);
+            storage.record_audit(&add).await?;
+        }
+        let tag_id = storage.drop_tag("gone", owner_id.clone()).await?;
+        let drop_tag = AuditEntry::new(owner_id.clone(), AuditAction::DropTag)
+            .with_tag("gone", owner_id.clone())
+            .with_tag_id(tag_id);
+        storage.record_audit(&drop_tag).await?;
+        let tag_id = storage
+            .merge_tag("merged", "test_a", owner_id.clone())
+            .await?;
+        let merge = AuditEntry::new(owner_id.clone(), AuditAction::Merge)
+            .with_tag("merged", owner_id.clone())
+            .with_tag_id(tag_id)
+            .with_detail("test_a");
+        storage.record_audit(&merge).await?;
+
+        // the history of a tag that no longer exists is found by its name
+        for (tag, last) in [
+            ("gone", AuditAction::DropTag),
+            ("merged", AuditAction::Merge),
+        ] {
+            let query = AuditQuery {
+                tag: Some((tag.to_string(), owner_id.clone())),
+                limit: 10,
+                ..Default::default()
+            };
+            let entries = storage.audit_list(&query).await?;
+            let actions = entries.iter().map(|entry| entry.action).collect::<Vec<_>>();
+            assert_eq!(actions, [last, AuditAction::Add]);
+            assert_eq!(entries[0].tag_id, entries[1].tag_id);
+        }
+
+        Ok(())
+    }
+
+    storage_tests!(test_record_list, test_follow_rename, test_deleted_tags);
+}
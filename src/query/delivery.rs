@@ -0,0 +1,280 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+// How collected messages are delivered to a dist channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DeliveryMode {
+    #[default]
+    Immediate,
+    // post a digest every given minutes
+    Interval(i64),
+    // post a digest every day at the given minute of the day
+    Daily(i64),
+}
+
+// The time zone times of the day are read in. A name of the IANA time zone database such as
+// Asia/Tokyo follows daylight saving time, and an offset such as +09:00 stays fixed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TimeZone {
+    // offset from UTC in minutes
+    Fixed(i64),
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DeliverySchedule {
+    pub mode: DeliveryMode,
+    pub time_zone: TimeZone,
+    // unix time of the last delivered digest
+    pub last_delivered_at: i64,
+    // minutes of the day when quiet hours start and end, in the channel's time zone
+    pub quiet_hours: Option<(i64, i64)>,
+}
+
+impl DeliveryMode {
+    pub fn from_columns(mode: &str, mode_minutes: i64) -> anyhow::Result<Self> {
+        match mode {
+            "immediate" => Ok(DeliveryMode::Immediate),
+            "interval" => Ok(DeliveryMode::Interval(mode_minutes)),
+            "daily" => Ok(DeliveryMode::Daily(mode_minutes)),
+            _ => Err(anyhow::anyhow!("unknown delivery mode: {mode}")),
+        }
+    }
+
+    pub fn to_columns(self) -> (&'static str, i64) {
+        match self {
+            DeliveryMode::Immediate => ("immediate", 0),
+            DeliveryMode::Interval(minutes) => ("interval", minutes),
+            DeliveryMode::Daily(minute_of_day) => ("daily", minute_of_day),
+        }
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        TimeZone::Fixed(0)
+    }
+}
+
+impl TimeZone {
+    // Offset from UTC in minutes at the unix time `now`
+    pub fn utc_offset(&self, now: i64) -> i64 {
+        match self {
+            TimeZone::Fixed(minutes) => *minutes,
+            TimeZone::Named(name) => tzdb::tz_by_name(name)
+                .and_then(|tz| tz.find_local_time_type(now).ok())
+                .map_or(0, |local| i64::from(local.ut_offset()) / 60),
+        }
+    }
+}
+
+// Written as it is stored, e.g. Asia/Tokyo or UTC+09:00
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeZone::Fixed(minutes) => write!(f, "UTC{}", format_utc_offset(*minutes)),
+            TimeZone::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = anyhow::Error;
+
+    // Parse `Asia/Tokyo`, `+09:00` or `UTC+09:00`
+    fn from_str(zone: &str) -> Result<Self, Self::Err> {
+        let offset = zone.strip_prefix("UTC").unwrap_or(zone);
+        if offset.starts_with(['+', '-']) {
+            return parse_utc_offset(offset).map(TimeZone::Fixed);
+        }
+        tzdb::tz_by_name(zone)
+            .map(|_| TimeZone::Named(zone.to_string()))
+            .with_context(|| format!("unknown time zone: {zone}"))
+    }
+}
+
+impl DeliverySchedule {
+    pub fn is_digest(&self) -> bool {
+        self.mode != DeliveryMode::Immediate
+    }
+
+    // Whether messages collected at `now` are held until quiet hours end
+    pub fn is_quiet(&self, now: i64) -> bool {
+        let Some((start, end)) = self.quiet_hours else {
+            return false;
+        };
+        let minute_of_day = (now + self.time_zone.utc_offset(now) * 60).rem_euclid(DAY_SECS) / 60;
+        if start <= end {
+            start <= minute_of_day && minute_of_day < end
+        } else {
+            start <= minute_of_day || minute_of_day < end
+        }
+    }
+
+    // Whether the queued messages of the channel should be delivered at `now`
+    pub fn is_due(&self, now: i64) -> bool {
+        if self.is_quiet(now) {
+            return false;
+        }
+        match self.mode {
+            DeliveryMode::Immediate => true,
+            DeliveryMode::Interval(minutes) => minutes * 60 <= now - self.last_delivered_at,
+            DeliveryMode::Daily(minute_of_day) => {
+                let offset = self.time_zone.utc_offset(now) * 60;
+                let local_now = now + offset;
+                let mut scheduled = local_now - local_now.rem_euclid(DAY_SECS) + minute_of_day * 60;
+                if local_now < scheduled {
+                    scheduled -= DAY_SECS;
+                }
+                self.last_delivered_at < scheduled - offset
+            }
+        }
+    }
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
+
+// Parse `HH:MM` into the minute of the day
+pub fn parse_time_of_day(time: &str) -> anyhow::Result<i64> {
+    let (hour, minute) = time.split_once(':').context("time should be HH:MM")?;
+    let hour = hour.parse::<i64>().context("time should be HH:MM")?;
+    let minute = minute.parse::<i64>().context("time should be HH:MM")?;
+    if !(0..24).contains(&hour) || !(0..60).contains(&minute) {
+        return Err(anyhow::anyhow!("time should be HH:MM"));
+    }
+    Ok(hour * 60 + minute)
+}
+
+// Parse `+09:00` or `-05:30` into minutes from UTC
+pub fn parse_utc_offset(offset: &str) -> anyhow::Result<i64> {
+    let (sign, time) = match (offset.strip_prefix('+'), offset.strip_prefix('-')) {
+        (Some(time), _) => (1, time),
+        (_, Some(time)) => (-1, time),
+        _ => return Err(anyhow::anyhow!("time zone should be like +09:00")),
+    };
+    let minutes = parse_time_of_day(time).context("time zone should be like +09:00")?;
+    Ok(sign * minutes)
+}
+
+pub fn format_time_of_day(minute_of_day: i64) -> String {
+    format!("{:02}:{:02}", minute_of_day / 60, minute_of_day % 60)
+}
+
+pub fn format_utc_offset(utc_offset: i64) -> String {
+    let sign = if utc_offset < 0 { '-' } else { '+' };
+    format!("{sign}{}", format_time_of_day(utc_offset.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn interval_due_test() {
+        let schedule = DeliverySchedule {
+            mode: DeliveryMode::Interval(30),
+            time_zone: TimeZone::Fixed(0),
+            last_delivered_at: 1_000_000,
+            quiet_hours: None,
+        };
+        assert!(!schedule.is_due(1_000_000 + 29 * 60));
+        assert!(schedule.is_due(1_000_000 + 30 * 60));
+    }
+
+    #[tokio::test]
+    async fn daily_due_test() {
+        // 09:00 in +09:00 is 00:00 UTC
+        let schedule = DeliverySchedule {
+            mode: DeliveryMode::Daily(9 * 60),
+            time_zone: TimeZone::Fixed(9 * 60),
+            last_delivered_at: 10 * DAY_SECS - 60,
+            quiet_hours: None,
+        };
+        assert!(!schedule.is_due(10 * DAY_SECS - 1));
+        assert!(schedule.is_due(10 * DAY_SECS));
+
+        let delivered = DeliverySchedule {
+            last_delivered_at: 10 * DAY_SECS,
+            ..schedule
+        };
+        assert!(!delivered.is_due(11 * DAY_SECS - 1));
+        assert!(delivered.is_due(11 * DAY_SECS));
+    }
+
+    #[tokio::test]
+    async fn quiet_hours_test() {
+        // 22:00 to 07:00 in +09:00 is 13:00 to 22:00 UTC
+        let schedule = DeliverySchedule {
+            time_zone: TimeZone::Fixed(9 * 60),
+            quiet_hours: Some((22 * 60, 7 * 60)),
+            ..DeliverySchedule::default()
+        };
+        let hour = 60 * 60;
+        assert!(!schedule.is_quiet(10 * DAY_SECS + 12 * hour));
+        assert!(schedule.is_quiet(10 * DAY_SECS + 13 * hour));
+        assert!(schedule.is_quiet(10 * DAY_SECS + 21 * hour));
+        assert!(!schedule.is_quiet(10 * DAY_SECS + 22 * hour));
+        assert!(!schedule.is_due(10 * DAY_SECS + 13 * hour));
+        assert!(schedule.is_due(10 * DAY_SECS + 22 * hour));
+    }
+
+    #[tokio::test]
+    async fn named_time_zone_test() {
+        // 09:00 in New York is 14:00 UTC in winter and 13:00 UTC in summer
+        let schedule = DeliverySchedule {
+            mode: DeliveryMode::Daily(9 * 60),
+            time_zone: "America/New_York".parse().unwrap(),
+            ..DeliverySchedule::default()
+        };
+        let hour = 60 * 60;
+        // 2024-01-15 and 2024-07-15 at 00:00 UTC
+        let (winter, summer) = (1_705_276_800, 1_721_001_600);
+        assert_eq!(schedule.time_zone.utc_offset(winter), -300);
+        assert_eq!(schedule.time_zone.utc_offset(summer), -240);
+        let winter_schedule = DeliverySchedule {
+            last_delivered_at: winter,
+            ..schedule.clone()
+        };
+        assert!(!winter_schedule.is_due(winter + 14 * hour - 1));
+        assert!(winter_schedule.is_due(winter + 14 * hour));
+        let summer_schedule = DeliverySchedule {
+            last_delivered_at: summer,
+            ..schedule
+        };
+        assert!(!summer_schedule.is_due(summer + 13 * hour - 1));
+        assert!(summer_schedule.is_due(summer + 13 * hour));
+    }
+
+    #[tokio::test]
+    async fn parse_test() {
+        assert_eq!(parse_time_of_day("09:30").unwrap(), 570);
+        assert!(parse_time_of_day("24:00").is_err());
+        assert_eq!(parse_utc_offset("+09:00").unwrap(), 540);
+        assert_eq!(parse_utc_offset("-05:30").unwrap(), -330);
+        assert!(parse_utc_offset("09:00").is_err());
+        assert_eq!(format_time_of_day(570), "09:30");
+        assert_eq!(format_utc_offset(-330), "-05:30");
+        assert_eq!("+09:00".parse::<TimeZone>().unwrap(), TimeZone::Fixed(540));
+        assert_eq!(
+            "UTC-05:30".parse::<TimeZone>().unwrap(),
+            TimeZone::Fixed(-330)
+        );
+        assert_eq!(
+            "Asia/Tokyo".parse::<TimeZone>().unwrap(),
+            TimeZone::Named("Asia/Tokyo".to_string())
+        );
+        assert!("Asia/Nowhere".parse::<TimeZone>().is_err());
+        assert_eq!(TimeZone::Fixed(540).to_string(), "UTC+09:00");
+    }
+}
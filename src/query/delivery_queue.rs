@@ -0,0 +1,262 @@
+use slack_morphism::{SlackChannelId, SlackTs};
+use sqlx::{Pool, Sqlite};
+
+// A collected message waiting to be delivered to a dist channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedMessage {
+    pub queue_id: i64,
+    pub dist: SlackChannelId,
+    pub source_channel: SlackChannelId,
+    pub source_ts: SlackTs,
+    pub sender_name: String,
+    pub icon_url: String,
+    pub text: String,
+    pub queued_at: i64,
+}
+
+pub async fn enqueue_with_pool(message: &QueuedMessage, pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let dist_str = message.dist.to_string();
+    let source_channel_str = message.source_channel.to_string();
+    let source_ts_str = message.source_ts.to_string();
+
+    let _query = sqlx::query!(
+        "
+        INSERT INTO delivery_queue
+            (dist_channel_id, source_channel_id, source_ts, sender_name, icon_url, text, queued_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ;",
+        dist_str,
+        source_channel_str,
+        source_ts_str,
+        message.sender_name,
+        message.icon_url,
+        message.text,
+        message.queued_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Return dist channels that have queued messages
+pub async fn pending_dists_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<SlackChannelId>> {
+    let dists = sqlx::query!(
+        "
+    SELECT DISTINCT dist_channel_id
+    FROM delivery_queue
+    "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| SlackChannelId::new(r.dist_channel_id))
+    .collect::<Vec<_>>();
+
+    Ok(dists)
+}
+
+// Take the queued messages of the dist channel that nobody else is delivering, until
+// `claimed_until`. Claiming is one statement, so two replicas never get the same message.
+pub async fn claim_queued_with_pool(
+    dist: &SlackChannelId,
+    now: i64,
+    claimed_until: i64,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<QueuedMessage>> {
+    let dist_str = dist.to_string();
+
+    // sqlx cannot tell that the returned columns are NOT NULL, so it is told with `!`
+    let mut claimed = sqlx::query!(
+        r#"
+    UPDATE delivery_queue
+    SET claimed_until = $1
+    WHERE dist_channel_id = $2 AND claimed_until <= $3
+    RETURNING queue_id AS "queue_id!", dist_channel_id AS "dist_channel_id!",
+        source_channel_id AS "source_channel_id!", source_ts AS "source_ts!",
+        sender_name AS "sender_name!", icon_url AS "icon_url!", text AS "text!",
+        queued_at AS "queued_at!"
+    "#,
+        claimed_until,
+        dist_str,
+        now
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| QueuedMessage {
+        queue_id: r.queue_id,
+        dist: SlackChannelId::new(r.dist_channel_id),
+        source_channel: SlackChannelId::new(r.source_channel_id),
+        source_ts: SlackTs::new(r.source_ts),
+        sender_name: r.sender_name,
+        icon_url: r.icon_url,
+        text: r.text,
+        queued_at: r.queued_at,
+    })
+    .collect::<Vec<_>>();
+    claimed.sort_by_key(|message| message.queue_id);
+
+    Ok(claimed)
+}
+
+// Remove the delivered messages
+pub async fn remove_delivered_with_pool(
+    queue_ids: &[i64],
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+    for queue_id in queue_ids {
+        let _query = sqlx::query!("DELETE FROM delivery_queue WHERE queue_id = $1;", queue_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Remove every queued copy of the source message, e.g. when it was deleted before delivery
+pub async fn remove_source_with_pool(
+    source_channel: &SlackChannelId,
+    source_ts: &SlackTs,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let source_channel_str = source_channel.to_string();
+    let source_ts_str = source_ts.to_string();
+
+    let _query = sqlx::query!(
+        "DELETE FROM delivery_queue WHERE source_channel_id = $1 AND source_ts = $2;",
+        source_channel_str,
+        source_ts_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Rewrite every queued copy of the source message with its edited text
+pub async fn update_source_with_pool(
+    source_channel: &SlackChannelId,
+    source_ts: &SlackTs,
+    text: &str,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let source_channel_str = source_channel.to_string();
+    let source_ts_str = source_ts.to_string();
+
+    let _query = sqlx::query!(
+        "UPDATE delivery_queue SET text = $1 WHERE source_channel_id = $2 AND source_ts = $3;",
+        text,
+        source_channel_str,
+        source_ts_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    fn queued_message(text: &str) -> QueuedMessage {
+        QueuedMessage {
+            queue_id: 0,
+            dist: SlackChannelId::new("Cdigest".to_string()),
+            source_channel: SlackChannelId::new("C01".to_string()),
+            source_ts: SlackTs::new("1686700000.000100".to_string()),
+            sender_name: "someone".to_string(),
+            icon_url: "https://example.com/icon.png".to_string(),
+            text: text.to_string(),
+            queued_at: 12345,
+        }
+    }
+
+    async fn test_queue(storage: &dyn Storage) -> anyhow::Result<()> {
+        let dist = SlackChannelId::new("Cdigest".to_string());
+
+        storage.enqueue(&queued_message("first")).await?;
+        storage.enqueue(&queued_message("second")).await?;
+
+        let pending = storage.pending_dists().await?;
+        assert_eq!(pending, vec![dist.clone()]);
+
+        let queued = storage.claim_queued(&dist, 100, 200).await?;
+        let texts = queued.iter().map(|m| m.text.clone()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["first".to_string(), "second".to_string()]);
+
+        storage.remove_delivered(&[queued[0].queue_id]).await?;
+        let queued = storage.claim_queued(&dist, 200, 300).await?;
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].text, "second");
+
+        Ok(())
+    }
+
+    async fn test_claim(storage: &dyn Storage) -> anyhow::Result<()> {
+        let dist = SlackChannelId::new("Cdigest".to_string());
+        storage.enqueue(&queued_message("first")).await?;
+        storage.enqueue(&queued_message("second")).await?;
+
+        let claimed = storage.claim_queued(&dist, 100, 200).await?;
+        let texts = claimed.iter().map(|m| m.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["first", "second"]);
+        // claimed messages are not handed out again until the claim runs out
+        storage.enqueue(&queued_message("third")).await?;
+        let claimed_again = storage.claim_queued(&dist, 150, 250).await?;
+        let texts = claimed_again
+            .iter()
+            .map(|m| m.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["third"]);
+        let expired = storage.claim_queued(&dist, 200, 300).await?;
+        assert_eq!(expired.len(), 2);
+
+        Ok(())
+    }
+
+    async fn test_remove_source(storage: &dyn Storage) -> anyhow::Result<()> {
+        let message = queued_message("deleted later");
+        storage.enqueue(&message).await?;
+
+        storage
+            .remove_source(&message.source_channel, &message.source_ts)
+            .await?;
+        let pending = storage.pending_dists().await?;
+        assert!(pending.is_empty());
+
+        Ok(())
+    }
+
+    async fn test_update_source(storage: &dyn Storage) -> anyhow::Result<()> {
+        let message = queued_message("before");
+        storage.enqueue(&message).await?;
+        storage
+            .enqueue(&QueuedMessage {
+                source_ts: SlackTs::new("1686700000.000200".to_string()),
+                ..queued_message("other")
+            })
+            .await?;
+
+        storage
+            .update_source(&message.source_channel, &message.source_ts, "after")
+            .await?;
+        let queued = storage.claim_queued(&message.dist, 100, 200).await?;
+        let texts = queued.iter().map(|m| m.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["after", "other"]);
+
+        Ok(())
+    }
+
+    storage_tests!(
+        test_queue,
+        test_claim,
+        test_remove_source,
+        test_update_source
+    );
+}
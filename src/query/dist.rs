@@ -1,90 +1,98 @@
+use std::collections::HashMap;
+
 use anyhow::Context;
 use slack_morphism::{SlackChannelId, SlackUserId};
-use sqlx::{Pool, Sqlite, SqlitePool};
-
-use super::utils::{self};
+use sqlx::{Pool, Sqlite};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct TagOwner {
-    tag: String,
-    owner: SlackUserId,
-}
+use super::{
+    delivery::{self, DeliveryMode, DeliverySchedule, TimeZone},
+    utils::{self},
+};
 
-pub async fn add_tag(dist: SlackChannelId, user: SlackUserId, tag: &str) -> anyhow::Result<()> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    add_tag_with_pool(dist, user, tag, pool).await
-}
-async fn add_tag_with_pool(
+pub async fn add_tag_with_pool(
     dist: SlackChannelId,
     user: SlackUserId,
     tag: &str,
-    pool: Pool<Sqlite>,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<()> {
     let user_str = user.to_string();
     let dist_str = dist.to_string();
 
-    let tag_id = utils::fetch_tag_id_with_pool(user, tag, &pool)
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag, pool)
         .await
         .context("failed to fetch the tag")?;
 
+    // valid_count is counted again from dist in the same transaction, so it never drifts
+    let mut tx = pool.begin().await?;
     let _query = sqlx::query!(
-        "INSERT INTO dist (user_id,tag_id, dist_channel_id) VALUES ($1, $2, $3);
+        "INSERT INTO dist (user_id,tag_id, dist_channel_id) VALUES ($1, $2, $3)
+        ON CONFLICT (user_id, tag_id, dist_channel_id)
+        DO NOTHING;
         UPDATE user_folder
-        SET valid_count = valid_count + 1
-        WHERE tag_id = $4
+        SET valid_count = (SELECT COUNT(*) FROM dist WHERE tag_id = $4)
+        WHERE tag_id = $5
         ",
         user_str,
         tag_id,
         dist_str,
+        tag_id,
         tag_id
     )
-    .execute(&pool)
+    .execute(&mut tx)
     .await?;
+    tx.commit().await?;
 
     Ok(())
 }
-pub async fn remove_tag(dist: SlackChannelId, user: SlackUserId, tag: &str) -> anyhow::Result<()> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    remove_tag_with_pool(dist, user, tag, pool).await
-}
-async fn remove_tag_with_pool(
-    _dist: SlackChannelId,
+// Stop collecting the tag in the dist channel only
+pub async fn remove_tag_with_pool(
+    dist: SlackChannelId,
     user: SlackUserId,
     tag: &str,
-    pool: Pool<Sqlite>,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<()> {
     let user_str = user.to_string();
+    let dist_str = dist.to_string();
 
-    let tag_id = utils::fetch_tag_id_with_pool(user, tag, &pool)
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag, pool)
         .await
         .context("failed to fetch the tag")?;
 
+    let mut tx = pool.begin().await?;
     let _query = sqlx::query!(
-        "DELETE FROM dist WHERE user_id = $1 AND tag_id = $2;
+        "DELETE FROM dist WHERE user_id = $1 AND tag_id = $2 AND dist_channel_id = $3;
         UPDATE user_folder
-        SET valid_count = valid_count - 1
-        WHERE tag_id = $3",
+        SET valid_count = (SELECT COUNT(*) FROM dist WHERE tag_id = $4)
+        WHERE tag_id = $5",
         user_str,
         tag_id,
+        dist_str,
+        tag_id,
         tag_id
     )
-    .execute(&pool)
+    .execute(&mut tx)
     .await?;
+    tx.commit().await?;
 
     Ok(())
 }
 
-pub async fn target_list(dist: &SlackChannelId) -> anyhow::Result<Vec<String>> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    target_list_with_pool(dist, pool).await
+// Count valid_count again for every tag whose count drifted from dist, returning how many were fixed
+pub async fn repair_valid_count_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<u64> {
+    let result = sqlx::query!(
+        "UPDATE user_folder
+        SET valid_count = (SELECT COUNT(*) FROM dist WHERE dist.tag_id = user_folder.tag_id)
+        WHERE valid_count <> (SELECT COUNT(*) FROM dist WHERE dist.tag_id = user_folder.tag_id);"
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
 }
 
-async fn target_list_with_pool(
+pub async fn target_list_with_pool(
     dist: &SlackChannelId,
-    pool: Pool<Sqlite>,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<Vec<String>> {
     let dist_str = dist.to_string();
 
@@ -97,7 +105,7 @@ async fn target_list_with_pool(
     ",
         dist_str
     )
-    .fetch_all(&pool)
+    .fetch_all(pool)
     .await?
     .into_iter()
     .map(|r| r.tag_name)
@@ -106,77 +114,317 @@ async fn target_list_with_pool(
     Ok(target_list)
 }
 
+// Set how collected messages are delivered; the time zone is kept when `time_zone` is None
+pub async fn set_delivery_mode_with_pool(
+    dist: &SlackChannelId,
+    user: &SlackUserId,
+    mode: DeliveryMode,
+    time_zone: Option<&TimeZone>,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let dist_str = dist.to_string();
+    let user_str = user.to_string();
+    let (mode_str, mode_minutes) = mode.to_columns();
+    let now = delivery::now();
+    let time_zone = time_zone.map(ToString::to_string);
+    let new_zone = time_zone
+        .clone()
+        .unwrap_or_else(|| TimeZone::default().to_string());
+
+    let _query = sqlx::query!(
+        "
+        INSERT INTO dist_schedule (dist_channel_id, mode, mode_minutes, time_zone, last_delivered_at, set_by)
+        VALUES ($1, $2, $3, $4, $5, $7)
+        ON CONFLICT (dist_channel_id)
+        DO UPDATE SET mode = excluded.mode, mode_minutes = excluded.mode_minutes,
+            time_zone = COALESCE($6, time_zone), last_delivered_at = excluded.last_delivered_at,
+            set_by = excluded.set_by
+        ;",
+        dist_str,
+        mode_str,
+        mode_minutes,
+        new_zone,
+        now,
+        time_zone,
+        user_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delivery_set_by_with_pool(
+    dist: &SlackChannelId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Option<SlackUserId>> {
+    let dist_str = dist.to_string();
+
+    let set_by = sqlx::query!(
+        "SELECT set_by FROM dist_schedule WHERE dist_channel_id = $1;",
+        dist_str
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|r| r.set_by)
+    .map(SlackUserId::new);
+
+    Ok(set_by)
+}
+
+// Set the quiet hours of the channel, or clear them with None
+pub async fn set_quiet_hours_with_pool(
+    dist: &SlackChannelId,
+    quiet_hours: Option<(i64, i64)>,
+    time_zone: Option<&TimeZone>,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let dist_str = dist.to_string();
+    let (quiet_start, quiet_end) = quiet_hours.unzip();
+    let now = delivery::now();
+    let time_zone = time_zone.map(ToString::to_string);
+    let new_zone = time_zone
+        .clone()
+        .unwrap_or_else(|| TimeZone::default().to_string());
+
+    let _query = sqlx::query!(
+        "
+        INSERT INTO dist_schedule (dist_channel_id, time_zone, last_delivered_at, quiet_start, quiet_end)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (dist_channel_id)
+        DO UPDATE SET quiet_start = excluded.quiet_start, quiet_end = excluded.quiet_end,
+            time_zone = COALESCE($6, time_zone)
+        ;",
+        dist_str,
+        new_zone,
+        now,
+        quiet_start,
+        quiet_end,
+        time_zone
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Return the delivery schedule of every dist channel that has one
+pub async fn schedule_list_with_pool(
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<HashMap<SlackChannelId, DeliverySchedule>> {
+    let schedules = sqlx::query!(
+        "
+    SELECT dist_channel_id, mode, mode_minutes, time_zone, last_delivered_at,
+        quiet_start, quiet_end
+    FROM dist_schedule
+    "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        let schedule = DeliverySchedule {
+            mode: DeliveryMode::from_columns(&r.mode, r.mode_minutes)?,
+            time_zone: r.time_zone.parse::<TimeZone>()?,
+            last_delivered_at: r.last_delivered_at,
+            quiet_hours: r.quiet_start.zip(r.quiet_end),
+        };
+        anyhow::Ok((SlackChannelId::new(r.dist_channel_id), schedule))
+    })
+    .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+    Ok(schedules)
+}
+
+pub async fn mark_delivered_with_pool(
+    dist: &SlackChannelId,
+    delivered_at: i64,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let dist_str = dist.to_string();
+
+    let _query = sqlx::query!(
+        "UPDATE dist_schedule SET last_delivered_at = $1 WHERE dist_channel_id = $2;",
+        delivered_at,
+        dist_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use sqlx::{Pool, Sqlite};
 
+    use crate::storage::{
+        testing::{self, storage_tests},
+        Storage,
+    };
+
     use super::*;
 
-    async fn add_test(pool: Pool<Sqlite>) -> anyhow::Result<(String, SlackChannelId, SlackUserId)> {
+    async fn add_test(
+        storage: &dyn Storage,
+    ) -> anyhow::Result<(String, SlackChannelId, SlackUserId)> {
         let tag_name = "test_dist";
         let channel = SlackChannelId::new("C012345dist".to_string());
         let user = SlackUserId::new("U0987654".to_string());
-        add_tag_with_pool(channel.clone(), user.clone(), tag_name, pool).await?;
+        storage
+            .add_tag(channel.clone(), user.clone(), tag_name)
+            .await?;
         Ok((tag_name.to_string(), channel, user))
     }
 
-    #[sqlx::test(migrations = "./migrations")]
+    async fn test_add_tag(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag, dist, user) = add_test(storage).await?;
 
-    async fn test_add_tag(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (_tag, dist, user) = add_test(pool.clone()).await?;
-        let user_id_str = user.to_string();
+        let configs = storage.tag_config_list().await?;
+        let config = configs
+            .iter()
+            .find(|config| config.tag_name == tag && config.owner_id == user)
+            .unwrap();
+        assert_eq!(config.dists, vec![dist]);
 
-        let dist_fetch = sqlx::query!(
-            "
-        SELECT dist_channel_id
-        FROM dist
-        WHERE user_id = $1
-        ",
-            user_id_str,
-        )
-        .fetch_one(&pool)
-        .await?
-        .dist_channel_id;
+        Ok(())
+    }
+
+    async fn test_remove(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag, dist, user) = add_test(storage).await?;
 
-        assert_eq!(dist_fetch, dist.to_string());
+        storage.remove_tag(dist.clone(), user, &tag).await?;
+
+        assert!(storage.target_list(&dist).await?.is_empty());
 
         Ok(())
     }
 
-    #[sqlx::test(migrations = "./migrations")]
+    async fn test_remove_scoped(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag, dist, user) = add_test(storage).await?;
+        let other = SlackChannelId::new("C0other".to_string());
+        storage.add_tag(other.clone(), user.clone(), &tag).await?;
+        // setting the same tag again changes nothing
+        storage.add_tag(other.clone(), user.clone(), &tag).await?;
 
-    async fn test_remove(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (tag, dist, user) = add_test(pool.clone()).await?;
-        let user_id_str = user.to_string();
+        storage.remove_tag(dist.clone(), user, &tag).await?;
 
-        remove_tag_with_pool(dist.clone(), user, &tag, pool.clone()).await?;
+        assert!(storage.target_list(&dist).await?.is_empty());
+        assert_eq!(storage.target_list(&other).await?, vec![tag]);
+        // valid_count still matches the dist rows
+        assert_eq!(storage.repair_valid_count().await?, 0);
 
-        let fail_fetch = sqlx::query!(
-            "
-        SELECT dist_channel_id
-        FROM dist
-        WHERE user_id = $1 
-        ",
-            user_id_str,
-        )
-        .fetch_one(&pool)
-        .await
-        .is_err();
+        Ok(())
+    }
 
-        assert!(fail_fetch);
+    // breaking valid_count needs raw SQL, so this one runs on sqlite only
+    #[sqlx::test(migrations = "./migrations")]
+    async fn test_repair_valid_count(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+        let storage = testing::sqlite_storage(pool.clone()).await?;
+        sqlx::query!("UPDATE user_folder SET valid_count = -1 WHERE tag_name = 'test_target'")
+            .execute(&pool)
+            .await?;
+
+        assert_eq!(storage.repair_valid_count().await?, 1);
+        assert_eq!(storage.repair_valid_count().await?, 0);
+
+        let valid_count =
+            sqlx::query!("SELECT valid_count FROM user_folder WHERE tag_name = 'test_target'")
+                .fetch_one(&pool)
+                .await?
+                .valid_count;
+        assert_eq!(valid_count, 1);
 
         Ok(())
     }
-    #[sqlx::test(migrations = "./migrations")]
 
-    async fn test_tag_list(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (tag, dist, _user) = add_test(pool.clone()).await?;
+    async fn test_tag_list(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag, dist, _user) = add_test(storage).await?;
 
-        let tag_list = target_list_with_pool(&dist, pool).await?;
+        let tag_list = storage.target_list(&dist).await?;
         let is_contains = tag_list.contains(&tag);
 
         assert!(is_contains);
 
         Ok(())
     }
+
+    async fn test_delivery_mode(storage: &dyn Storage) -> anyhow::Result<()> {
+        let dist = SlackChannelId::new("Cdigest".to_string());
+        let user = SlackUserId::new("U00001".to_string());
+        assert_eq!(storage.delivery_set_by(&dist).await?, None);
+
+        storage
+            .set_delivery_mode(
+                &dist,
+                &user,
+                DeliveryMode::Daily(540),
+                Some(&TimeZone::Fixed(540)),
+            )
+            .await?;
+        storage.mark_delivered(&dist, 12345).await?;
+
+        let schedules = storage.schedule_list().await?;
+        let desired_schedule = DeliverySchedule {
+            mode: DeliveryMode::Daily(540),
+            time_zone: TimeZone::Fixed(540),
+            last_delivered_at: 12345,
+            quiet_hours: None,
+        };
+        assert_eq!(schedules.get(&dist), Some(&desired_schedule));
+        assert_eq!(storage.delivery_set_by(&dist).await?, Some(user.clone()));
+
+        // the time zone is kept when it is not given
+        storage
+            .set_delivery_mode(&dist, &user, DeliveryMode::Immediate, None)
+            .await?;
+        let schedules = storage.schedule_list().await?;
+        assert!(schedules.get(&dist).is_some_and(|s| !s.is_digest()));
+        assert_eq!(
+            schedules.get(&dist).map(|s| &s.time_zone),
+            Some(&TimeZone::Fixed(540))
+        );
+
+        Ok(())
+    }
+
+    async fn test_quiet_hours(storage: &dyn Storage) -> anyhow::Result<()> {
+        let dist = SlackChannelId::new("Cquiet".to_string());
+
+        storage
+            .set_quiet_hours(
+                &dist,
+                Some((1320, 420)),
+                Some(&TimeZone::Named("Europe/London".to_string())),
+            )
+            .await?;
+        let schedules = storage.schedule_list().await?;
+        let schedule = schedules.get(&dist).cloned().unwrap_or_default();
+        assert_eq!(schedule.quiet_hours, Some((1320, 420)));
+        assert_eq!(
+            schedule.time_zone,
+            TimeZone::Named("Europe/London".to_string())
+        );
+        assert!(!schedule.is_digest());
+
+        storage.set_quiet_hours(&dist, None, None).await?;
+        let schedules = storage.schedule_list().await?;
+        let schedule = schedules.get(&dist).cloned().unwrap_or_default();
+        assert_eq!(schedule.quiet_hours, None);
+        assert_eq!(
+            schedule.time_zone,
+            TimeZone::Named("Europe/London".to_string())
+        );
+
+        Ok(())
+    }
+
+    storage_tests!(
+        test_add_tag,
+        test_remove,
+        test_remove_scoped,
+        test_tag_list,
+        test_delivery_mode,
+        test_quiet_hours,
+    );
 }
@@ -1,91 +1,154 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use slack_morphism::{SlackChannelId, SlackMessageSender};
-use sqlx::{Pool, Sqlite, SqlitePool};
+use sqlx::{Pool, Sqlite};
+
+use super::{
+    sender_rule::{self, SenderRule},
+    tag_filter::{self, CompiledFilter, TagFilter},
+    thread_mode::{ThreadMode, ThreadPosition},
+};
+
+// What the routing needs to know about a message, besides its channel and sender
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSummary {
+    pub position: ThreadPosition,
+    pub has_files: bool,
+    pub text: String,
+}
 
-// Determine if the channel is a collection target
-pub async fn is_target_for_some(
-    channel_from: SlackChannelId,
-    sender: SlackMessageSender,
-) -> anyhow::Result<bool> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    is_target_for_some_with_pool(channel_from, sender, &pool).await
+// A tag that registers a channel, with everything needed to route its messages
+#[derive(Debug, Clone)]
+pub struct TagRoute {
+    pub tag_id: i64,
+    pub bot: bool,
+    pub file: bool,
+    pub thread_mode: ThreadMode,
+    pub filters: Vec<CompiledFilter>,
+    pub rules: Vec<SenderRule>,
+    // dist channels that set the tag, empty while the tag is not valid
+    pub dists: Vec<SlackChannelId>,
+}
+
+impl TagRoute {
+    fn accepts_sender(&self, sender: &SlackMessageSender) -> bool {
+        (sender.bot_id.is_none() || self.bot) && sender_rule::accepts(&self.rules, sender)
+    }
+
+    fn accepts(&self, sender: &SlackMessageSender, summary: &MessageSummary) -> bool {
+        self.accepts_sender(sender)
+            && (!summary.has_files || self.file)
+            && self.thread_mode.accepts(summary.position)
+            && tag_filter::accepts(&self.filters, &summary.text)
+    }
 }
-async fn is_target_for_some_with_pool(
-    channel_from: SlackChannelId,
-    sender: SlackMessageSender,
-    pool: &Pool<Sqlite>,
-) -> anyhow::Result<bool> {
-    let channel_str = channel_from.to_string();
 
-    let is_bot = sender.bot_id.is_some();
+// A row of channel_list joined with its tag and the dist channels of the tag
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RouteRecord {
+    pub channel_id: String,
+    pub tag_id: i64,
+    pub bot: bool,
+    pub file: bool,
+    pub thread_mode: String,
+    pub valid_count: i64,
+    pub dist_channel_id: Option<String>,
+}
 
+// Determine if the channel is a collection target, from the routes of the channel
+pub fn is_target_for_some(
+    routes: &[TagRoute],
+    sender: &SlackMessageSender,
+) -> anyhow::Result<bool> {
     let self_bot = crate::utils::get_self_bot_id()?;
-    let is_self = sender.bot_id.map_or(false, |bot_id| bot_id == self_bot);
+    let is_self = sender
+        .bot_id
+        .as_ref()
+        .is_some_and(|bot_id| *bot_id == self_bot);
 
     if is_self {
         return Ok(false);
     }
 
-    let is_target = sqlx::query!(
-        "
-        SELECT cl.channel_id, uf.bot
-        FROM channel_list cl INNER JOIN user_folder uf
-        ON cl.tag_id = uf.tag_id
-        WHERE cl.channel_id = $1
-    ",
-        channel_str
-    )
-    .fetch_all(pool)
-    .await
-    .map_or(false, |records| {
-        records.iter().any(|rec| !is_bot || rec.bot)
-    });
-
-    Ok(is_target)
+    Ok(routes.iter().any(|route| route.accepts_sender(sender)))
 }
 
 // Return all dist channels that have set the tags that is registred the channel
-pub async fn target_to_dists(
-    target: SlackChannelId,
-    sender: SlackMessageSender,
-) -> anyhow::Result<HashSet<SlackChannelId>> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    target_to_dists_with_pool(target, sender, &pool).await
+pub fn target_to_dists(
+    routes: &[TagRoute],
+    sender: &SlackMessageSender,
+    summary: &MessageSummary,
+) -> HashSet<SlackChannelId> {
+    routes
+        .iter()
+        .filter(|route| route.accepts(sender, summary))
+        .flat_map(|route| route.dists.iter().cloned())
+        .collect::<HashSet<_>>()
 }
-pub async fn target_to_dists_with_pool(
-    target: SlackChannelId,
-    sender: SlackMessageSender,
+
+// Routes of every registered channel, keyed by channel
+pub async fn route_map_with_pool(
     pool: &Pool<Sqlite>,
-) -> anyhow::Result<HashSet<SlackChannelId>> {
-    let channel_str = target.to_string();
-
-    let is_bot = sender.bot_id.is_some();
-
-    let dists = sqlx::query!(
-        "
-    SELECT dist.dist_channel_id, uf_valid.bot
-    FROM dist 
-    INNER JOIN (
-        SELECT uf.tag_id, uf.bot
-            FROM user_folder uf INNER JOIN channel_list cl
-            ON uf.tag_id = cl.tag_id
-            WHERE 0 < uf.valid_count AND cl.channel_id = $1
-        ) AS uf_valid
-    ON uf_valid.tag_id = dist.tag_id
-    ",
-        channel_str
+) -> anyhow::Result<HashMap<SlackChannelId, Vec<TagRoute>>> {
+    let records = sqlx::query_as!(
+        RouteRecord,
+        r#"
+    SELECT cl.channel_id, uf.tag_id, uf.bot, uf.file, uf.thread_mode, uf.valid_count,
+        dist.dist_channel_id AS "dist_channel_id?"
+    FROM channel_list cl
+    INNER JOIN user_folder uf ON cl.tag_id = uf.tag_id
+    LEFT JOIN dist ON dist.tag_id = uf.tag_id
+    "#
     )
     .fetch_all(pool)
-    .await?
-    .into_iter()
-    .filter(|r| !is_bot || r.bot)
-    .map(|r| SlackChannelId::new(r.dist_channel_id))
-    .collect::<HashSet<_>>();
+    .await?;
+
+    let filters = tag_filter::all_filters_with_pool(pool).await?;
+    let rules = sender_rule::all_rules_with_pool(pool).await?;
 
-    Ok(dists)
+    Ok(assemble_routes(records, &filters, &rules))
+}
+
+pub fn assemble_routes(
+    records: Vec<RouteRecord>,
+    filters: &HashMap<i64, Vec<TagFilter>>,
+    rules: &HashMap<i64, Vec<SenderRule>>,
+) -> HashMap<SlackChannelId, Vec<TagRoute>> {
+    // compiled once per tag, however many channels the tag registers
+    let filters = filters
+        .iter()
+        .map(|(tag_id, filters)| {
+            let compiled = filters.iter().map(CompiledFilter::from_saved).collect();
+            (*tag_id, compiled)
+        })
+        .collect::<HashMap<i64, Vec<_>>>();
+    let mut routes = HashMap::<SlackChannelId, Vec<TagRoute>>::new();
+    for r in records {
+        let channel_routes = routes.entry(SlackChannelId::new(r.channel_id)).or_default();
+        let position = channel_routes
+            .iter()
+            .position(|route| route.tag_id == r.tag_id);
+        let index = if let Some(index) = position {
+            index
+        } else {
+            channel_routes.push(TagRoute {
+                tag_id: r.tag_id,
+                bot: r.bot,
+                file: r.file,
+                // an unknown mode forwards everything, as mirror does
+                thread_mode: r.thread_mode.parse().unwrap_or(ThreadMode::Mirror),
+                filters: filters.get(&r.tag_id).cloned().unwrap_or_default(),
+                rules: rules.get(&r.tag_id).cloned().unwrap_or_default(),
+                dists: Vec::new(),
+            });
+            channel_routes.len() - 1
+        };
+        if let (Some(dist), true) = (r.dist_channel_id, 0 < r.valid_count) {
+            channel_routes[index].dists.push(SlackChannelId::new(dist));
+        }
+    }
+
+    routes
 }
 
 #[cfg(test)]
@@ -93,12 +156,51 @@ mod tests {
 
     use slack_morphism::{SlackBotId, SlackUserId};
 
-    use crate::utils::get_self_bot_id;
+    use crate::{
+        storage::{testing::storage_tests, Storage},
+        utils::get_self_bot_id,
+    };
 
     use super::*;
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_is_target(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+    fn summary(position: ThreadPosition, has_files: bool) -> MessageSummary {
+        MessageSummary {
+            position,
+            has_files,
+            text: "test message".to_string(),
+        }
+    }
+
+    async fn channel_routes(
+        storage: &dyn Storage,
+        channel: &SlackChannelId,
+    ) -> anyhow::Result<Vec<TagRoute>> {
+        Ok(storage
+            .route_map()
+            .await?
+            .remove(channel)
+            .unwrap_or_default())
+    }
+
+    async fn is_target(
+        storage: &dyn Storage,
+        channel: &SlackChannelId,
+        sender: &SlackMessageSender,
+    ) -> anyhow::Result<bool> {
+        is_target_for_some(&channel_routes(storage, channel).await?, sender)
+    }
+
+    async fn dists(
+        storage: &dyn Storage,
+        channel: &SlackChannelId,
+        sender: &SlackMessageSender,
+        summary: &MessageSummary,
+    ) -> anyhow::Result<HashSet<SlackChannelId>> {
+        let routes = channel_routes(storage, channel).await?;
+        Ok(target_to_dists(&routes, sender, summary))
+    }
+
+    async fn test_is_target(storage: &dyn Storage) -> anyhow::Result<()> {
         let channel_from_1 = SlackChannelId::new("C01".to_string());
         let channel_from_2 = SlackChannelId::new("C02".to_string());
 
@@ -107,32 +209,25 @@ mod tests {
         let user = SlackUserId::new("Uanybody".to_string());
         let sender_user = SlackMessageSender::new().with_user(user);
 
-        assert!(
-            is_target_for_some_with_pool(channel_from_1.clone(), sender_user.clone(), &pool)
-                .await?
-        );
-        assert!(!is_target_for_some_with_pool(channel_from_not_collect, sender_user, &pool).await?);
+        assert!(is_target(storage, &channel_from_1, &sender_user).await?);
+        assert!(!is_target(storage, &channel_from_not_collect, &sender_user).await?);
 
         let bot = SlackBotId::new("B01234567".to_string());
         let sender_bot = SlackMessageSender::new().with_bot_id(bot);
 
-        assert!(
-            !is_target_for_some_with_pool(channel_from_1.clone(), sender_bot.clone(), &pool)
-                .await?
-        );
-        assert!(is_target_for_some_with_pool(channel_from_2, sender_bot, &pool).await?);
+        assert!(!is_target(storage, &channel_from_1, &sender_bot).await?);
+        assert!(is_target(storage, &channel_from_2, &sender_bot).await?);
 
         let _bot_self_id = get_self_bot_id()?;
         let bot_self = SlackBotId::new("B01234567".to_string());
         let sender_self = SlackMessageSender::new().with_bot_id(bot_self);
 
-        assert!(!is_target_for_some_with_pool(channel_from_1, sender_self, &pool).await?);
+        assert!(!is_target(storage, &channel_from_1, &sender_self).await?);
 
         Ok(())
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_target_to_dists(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+    async fn test_target_to_dists(storage: &dyn Storage) -> anyhow::Result<()> {
         let channel_from_1 = SlackChannelId::new("C01".to_string());
         let channel_from_2 = SlackChannelId::new("C02".to_string());
 
@@ -142,16 +237,17 @@ mod tests {
         let bot = SlackBotId::new("B01234567".to_string());
         let sender_bot = SlackMessageSender::new().with_bot_id(bot);
 
-        let dists_1 = target_to_dists_with_pool(channel_from_1, sender_user.clone(), &pool).await?;
-        let dists_2 = target_to_dists_with_pool(channel_from_2.clone(), sender_bot, &pool).await?;
-        let dists_1_2 = target_to_dists_with_pool(channel_from_2, sender_user, &pool).await?;
+        let root = summary(ThreadPosition::Root, false);
+        let dists_1 = dists(storage, &channel_from_1, &sender_user, &root).await?;
+        let dists_2 = dists(storage, &channel_from_2, &sender_bot, &root).await?;
+        let dists_1_2 = dists(storage, &channel_from_2, &sender_user, &root).await?;
 
         let dist_ch = SlackChannelId::new("Cdist".to_string());
         let dist_bot_ch = SlackChannelId::new("Cdist_bot".to_string());
 
-        let desired_dists_1 = vec![dist_ch.clone()];
-        let desired_dists_2 = vec![dist_bot_ch.clone()];
-        let desired_dists_1_2 = vec![dist_ch, dist_bot_ch];
+        let desired_dists_1 = [dist_ch.clone()];
+        let desired_dists_2 = [dist_bot_ch.clone()];
+        let desired_dists_1_2 = [dist_ch, dist_bot_ch];
 
         assert!(desired_dists_1.iter().all(|ch| dists_1.contains(ch)));
         assert!(desired_dists_2.iter().all(|ch| dists_2.contains(ch)));
@@ -159,4 +255,138 @@ mod tests {
 
         Ok(())
     }
+
+    async fn test_target_to_dists_thread(storage: &dyn Storage) -> anyhow::Result<()> {
+        let channel_from = SlackChannelId::new("C01".to_string());
+        let user = SlackUserId::new("Uanybody".to_string());
+        let sender_user = SlackMessageSender::new().with_user(user);
+        let dist_ch = SlackChannelId::new("Cdist".to_string());
+        let reply = summary(ThreadPosition::Reply, false);
+
+        let dists_reply = dists(storage, &channel_from, &sender_user, &reply).await?;
+        assert!(dists_reply.contains(&dist_ch));
+
+        storage
+            .thread_mode(
+                "test_target",
+                SlackUserId::new("U00001".to_string()),
+                ThreadMode::Root,
+            )
+            .await?;
+
+        let dists_reply = dists(storage, &channel_from, &sender_user, &reply).await?;
+        let dists_root = dists(
+            storage,
+            &channel_from,
+            &sender_user,
+            &summary(ThreadPosition::Root, false),
+        )
+        .await?;
+        assert!(!dists_reply.contains(&dist_ch));
+        assert!(dists_root.contains(&dist_ch));
+
+        Ok(())
+    }
+
+    async fn test_target_to_dists_file(storage: &dyn Storage) -> anyhow::Result<()> {
+        let channel_from = SlackChannelId::new("C01".to_string());
+        let user = SlackUserId::new("Uanybody".to_string());
+        let sender_user = SlackMessageSender::new().with_user(user);
+        let dist_ch = SlackChannelId::new("Cdist".to_string());
+        let file = summary(ThreadPosition::Root, true);
+        let text = summary(ThreadPosition::Root, false);
+
+        let dists_file = dists(storage, &channel_from, &sender_user, &file).await?;
+        assert!(dists_file.contains(&dist_ch));
+
+        storage
+            .retrieve_file("test_target", SlackUserId::new("U00001".to_string()), false)
+            .await?;
+
+        let dists_file = dists(storage, &channel_from, &sender_user, &file).await?;
+        let dists_text = dists(storage, &channel_from, &sender_user, &text).await?;
+        assert!(!dists_file.contains(&dist_ch));
+        assert!(dists_text.contains(&dist_ch));
+
+        Ok(())
+    }
+
+    async fn test_target_to_dists_filter(storage: &dyn Storage) -> anyhow::Result<()> {
+        let channel_from = SlackChannelId::new("C02".to_string());
+        let bot = SlackBotId::new("B01234567".to_string());
+        let sender_bot = SlackMessageSender::new().with_bot_id(bot);
+        let dist_bot_ch = SlackChannelId::new("Cdist_bot".to_string());
+
+        let noisy = MessageSummary {
+            text: "Noisy alert".to_string(),
+            ..summary(ThreadPosition::Root, false)
+        };
+        let quiet = summary(ThreadPosition::Root, false);
+
+        let dists_noisy = dists(storage, &channel_from, &sender_bot, &noisy).await?;
+        let dists_quiet = dists(storage, &channel_from, &sender_bot, &quiet).await?;
+
+        assert!(!dists_noisy.contains(&dist_bot_ch));
+        assert!(dists_quiet.contains(&dist_bot_ch));
+
+        Ok(())
+    }
+
+    async fn test_route_map(storage: &dyn Storage) -> anyhow::Result<()> {
+        let route_map = storage.route_map().await?;
+
+        // test_a, test_target and test_target_bot register C02
+        let routes = route_map
+            .get(&SlackChannelId::new("C02".to_string()))
+            .cloned()
+            .unwrap_or_default();
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes.iter().filter(|route| route.bot).count(), 1);
+        assert!(!route_map.contains_key(&SlackChannelId::new("Cnot".to_string())));
+
+        Ok(())
+    }
+
+    async fn test_sender_rule(storage: &dyn Storage) -> anyhow::Result<()> {
+        let channel_from = SlackChannelId::new("C02".to_string());
+        let monitor = SlackBotId::new("Bmonitor".to_string());
+        let ci = SlackBotId::new("Bci".to_string());
+        let sender_monitor = SlackMessageSender::new().with_bot_id(monitor);
+        let sender_ci = SlackMessageSender::new().with_bot_id(ci);
+        let dist_bot_ch = SlackChannelId::new("Cdist_bot".to_string());
+        let root = summary(ThreadPosition::Root, false);
+
+        let deny_ci = SenderRule {
+            kind: sender_rule::RuleKind::Deny,
+            sender_id: "Bci".to_string(),
+        };
+        storage
+            .add_rule(
+                "test_target_bot",
+                SlackUserId::new("U00001".to_string()),
+                &deny_ci,
+            )
+            .await?;
+
+        assert!(is_target(storage, &channel_from, &sender_monitor).await?);
+        assert!(!is_target(storage, &channel_from, &sender_ci).await?);
+
+        let dists_monitor = dists(storage, &channel_from, &sender_monitor, &root).await?;
+        let dists_ci = dists(storage, &channel_from, &sender_ci, &root).await?;
+
+        assert!(dists_monitor.contains(&dist_bot_ch));
+        assert!(!dists_ci.contains(&dist_bot_ch));
+
+        Ok(())
+    }
+
+    storage_tests!(
+        test_is_target,
+        test_target_to_dists,
+        test_target_to_dists_thread,
+        test_target_to_dists_file,
+        test_target_to_dists_filter,
+        test_route_map,
+        test_sender_rule,
+    );
 }
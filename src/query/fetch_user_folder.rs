@@ -1,12 +1,7 @@
 use slack_morphism::{SlackChannelId, SlackUserId};
-use sqlx::{Pool, Sqlite, SqlitePool};
+use sqlx::{Pool, Sqlite};
 
-pub async fn tag_list_user(owner_id: SlackUserId) -> anyhow::Result<Vec<String>> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    tag_list_user_with_pool(owner_id, &pool).await
-}
-async fn tag_list_user_with_pool(
+pub async fn tag_list_user_with_pool(
     owner_id: SlackUserId,
     pool: &Pool<Sqlite>,
 ) -> anyhow::Result<Vec<String>> {
@@ -28,12 +23,7 @@ async fn tag_list_user_with_pool(
 
     Ok(tag_list)
 }
-pub async fn tag_list_pub() -> anyhow::Result<Vec<String>> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    tag_list_public_with_pool(&pool).await
-}
-async fn tag_list_public_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<String>> {
+pub async fn tag_list_public_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<String>> {
     let tag_list = sqlx::query!(
         "
     SELECT tag_name
@@ -50,12 +40,7 @@ async fn tag_list_public_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<St
     Ok(tag_list)
 }
 
-pub async fn channel_list(tag: &str, owner_id: SlackUserId) -> anyhow::Result<Vec<SlackChannelId>> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    channel_list_with_pool(tag, owner_id, &pool).await
-}
-async fn channel_list_with_pool(
+pub async fn channel_list_with_pool(
     tag: &str,
     owner_id: SlackUserId,
     pool: &Pool<Sqlite>,
@@ -83,17 +68,18 @@ async fn channel_list_with_pool(
 #[cfg(test)]
 mod tests {
 
+    use crate::storage::{testing::storage_tests, Storage};
+
     use super::*;
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_tag_list(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+    async fn test_tag_list(storage: &dyn Storage) -> anyhow::Result<()> {
         let owner_id = SlackUserId::new("U00001".to_string());
 
-        let tag_list_user = tag_list_user_with_pool(owner_id, &pool).await?;
-        let tag_list_pub = tag_list_public_with_pool(&pool).await?;
+        let tag_list_user = storage.tag_list_user(owner_id).await?;
+        let tag_list_pub = storage.tag_list_public().await?;
 
-        let desired_tag_list_user = vec!["test_a".to_string(), "test_b".to_string()];
-        let desired_tag_list_pub = vec!["test_pub".to_string()];
+        let desired_tag_list_user = ["test_a".to_string(), "test_b".to_string()];
+        let desired_tag_list_pub = ["test_pub".to_string()];
 
         let contains_user = desired_tag_list_user
             .iter()
@@ -107,23 +93,22 @@ mod tests {
 
         Ok(())
     }
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_channel_list(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+    async fn test_channel_list(storage: &dyn Storage) -> anyhow::Result<()> {
         let owner_id = SlackUserId::new("U00001".to_string());
         let owner_id_2 = SlackUserId::new("U00002".to_string());
         let public = SlackUserId::new("public".to_string());
 
         let tag_name = "test_a";
 
-        let ch_list = channel_list_with_pool(tag_name, owner_id, &pool).await?;
-        let ch_list_no_auth = channel_list_with_pool(tag_name, owner_id_2, &pool).await?;
-        let ch_list_pub = channel_list_with_pool("test_pub", public, &pool).await?;
+        let ch_list = storage.channel_list(tag_name, owner_id).await?;
+        let ch_list_no_auth = storage.channel_list(tag_name, owner_id_2).await?;
+        let ch_list_pub = storage.channel_list("test_pub", public).await?;
 
-        let desired_ch_list = vec!["C01".to_string(), "C02".to_string()];
+        let desired_ch_list = ["C01", "C02"];
 
         let is_contain = desired_ch_list
             .iter()
-            .all(|s| ch_list.contains(&SlackChannelId::new(s.to_string())));
+            .all(|s| ch_list.contains(&SlackChannelId::new((*s).to_string())));
         let is_contain_pub = ch_list_pub.contains(&SlackChannelId::new("C03".to_string()));
 
         assert!(ch_list_no_auth.is_empty());
@@ -132,4 +117,6 @@ mod tests {
 
         Ok(())
     }
+
+    storage_tests!(test_tag_list, test_channel_list);
 }
@@ -0,0 +1,147 @@
+use slack_morphism::{SlackChannelId, SlackTs};
+use sqlx::{Pool, Sqlite};
+
+// Remember where a source message has been forwarded, to follow its later edits
+pub async fn register_forwarded_with_pool(
+    source_channel: &SlackChannelId,
+    source_ts: &SlackTs,
+    dist: &SlackChannelId,
+    forwarded_ts: &SlackTs,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let source_channel_str = source_channel.to_string();
+    let source_ts_str = source_ts.to_string();
+    let dist_str = dist.to_string();
+    let forwarded_ts_str = forwarded_ts.to_string();
+
+    let _query = sqlx::query!(
+        "
+        INSERT INTO forwarded_message (source_channel_id, source_ts, dist_channel_id, forwarded_ts)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (source_channel_id, source_ts, dist_channel_id)
+        DO UPDATE SET forwarded_ts = excluded.forwarded_ts
+        ;",
+        source_channel_str,
+        source_ts_str,
+        dist_str,
+        forwarded_ts_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Return every (dist channel, forwarded ts) pair of a source message
+pub async fn forwarded_list_with_pool(
+    source_channel: &SlackChannelId,
+    source_ts: &SlackTs,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<(SlackChannelId, SlackTs)>> {
+    let source_channel_str = source_channel.to_string();
+    let source_ts_str = source_ts.to_string();
+
+    let forwarded = sqlx::query!(
+        "
+    SELECT dist_channel_id, forwarded_ts
+    FROM forwarded_message
+    WHERE source_channel_id = $1 AND source_ts = $2
+    ",
+        source_channel_str,
+        source_ts_str
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        (
+            SlackChannelId::new(r.dist_channel_id),
+            SlackTs::new(r.forwarded_ts),
+        )
+    })
+    .collect::<Vec<_>>();
+
+    Ok(forwarded)
+}
+
+// Forget a forwarded copy once it has been deleted from its dist channel
+pub async fn unregister_forwarded_with_pool(
+    source_channel: &SlackChannelId,
+    source_ts: &SlackTs,
+    dist: &SlackChannelId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let source_channel_str = source_channel.to_string();
+    let source_ts_str = source_ts.to_string();
+    let dist_str = dist.to_string();
+
+    let _query = sqlx::query!(
+        "DELETE FROM forwarded_message
+        WHERE source_channel_id = $1 AND source_ts = $2 AND dist_channel_id = $3;",
+        source_channel_str,
+        source_ts_str,
+        dist_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    async fn register_test(
+        storage: &dyn Storage,
+    ) -> anyhow::Result<(SlackChannelId, SlackTs, SlackChannelId, SlackTs)> {
+        let source_channel = SlackChannelId::new("C01".to_string());
+        let source_ts = SlackTs::new("1686700000.000100".to_string());
+        let dist = SlackChannelId::new("Cdist".to_string());
+        let forwarded_ts = SlackTs::new("1686700001.000200".to_string());
+        storage
+            .register_forwarded(&source_channel, &source_ts, &dist, &forwarded_ts)
+            .await?;
+        Ok((source_channel, source_ts, dist, forwarded_ts))
+    }
+
+    async fn test_forwarded_list(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (source_channel, source_ts, dist, forwarded_ts) = register_test(storage).await?;
+
+        let forwarded = storage.forwarded_list(&source_channel, &source_ts).await?;
+        let not_forwarded = storage
+            .forwarded_list(
+                &source_channel,
+                &SlackTs::new("1686700002.000300".to_string()),
+            )
+            .await?;
+
+        assert_eq!(forwarded, vec![(dist, forwarded_ts)]);
+        assert!(not_forwarded.is_empty());
+
+        Ok(())
+    }
+
+    async fn test_unregister_forwarded(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (source_channel, source_ts, dist, _forwarded_ts) = register_test(storage).await?;
+        let other = SlackChannelId::new("Cother".to_string());
+        let other_ts = SlackTs::new("1686700001.000300".to_string());
+        storage
+            .register_forwarded(&source_channel, &source_ts, &other, &other_ts)
+            .await?;
+
+        // only the copy in the given dist channel is forgotten
+        storage
+            .unregister_forwarded(&source_channel, &source_ts, &dist)
+            .await?;
+
+        let forwarded = storage.forwarded_list(&source_channel, &source_ts).await?;
+        assert_eq!(forwarded, vec![(other, other_ts)]);
+
+        Ok(())
+    }
+
+    storage_tests!(test_forwarded_list, test_unregister_forwarded);
+}
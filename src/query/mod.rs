@@ -3,12 +3,22 @@ use std::env;
 use anyhow::Context;
 use dotenvy::dotenv;
 
-pub mod create_table;
+pub mod audit_log;
+pub mod delivery;
+pub mod delivery_queue;
 pub mod dist;
 pub mod dist_target_map;
 pub mod fetch_user_folder;
+pub mod forwarded_message;
+pub mod sender_rule;
+pub mod tag_config;
+pub mod tag_filter;
+pub mod tag_member;
+pub mod thread_mode;
 pub mod user_folder;
+pub mod user_lang;
 pub mod utils;
+pub mod workspace_admin;
 
 pub fn db_url() -> anyhow::Result<String> {
     dotenv().ok();
@@ -0,0 +1,242 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::Context;
+use slack_morphism::{SlackMessageSender, SlackUserId};
+use sqlx::{Pool, Sqlite};
+
+use super::utils;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleKind {
+    Allow,
+    Deny,
+}
+
+// A user id or a bot id that a tag collects or ignores
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SenderRule {
+    pub kind: RuleKind,
+    pub sender_id: String,
+}
+
+impl SenderRule {
+    fn matches(&self, sender: &SlackMessageSender) -> bool {
+        let is_user = sender
+            .user
+            .as_ref()
+            .is_some_and(|user| user.0 == self.sender_id);
+        let is_bot = sender
+            .bot_id
+            .as_ref()
+            .is_some_and(|bot_id| bot_id.0 == self.sender_id);
+        is_user || is_bot
+    }
+}
+
+// A sender passes when it is allowed (if any allow rule exists) and not denied
+pub fn accepts(rules: &[SenderRule], sender: &SlackMessageSender) -> bool {
+    let mut allows = rules
+        .iter()
+        .filter(|r| r.kind == RuleKind::Allow)
+        .peekable();
+    let allowed = allows.peek().is_none() || allows.any(|r| r.matches(sender));
+    let denied = rules
+        .iter()
+        .filter(|r| r.kind == RuleKind::Deny)
+        .any(|r| r.matches(sender));
+
+    allowed && !denied
+}
+
+impl FromStr for RuleKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(RuleKind::Allow),
+            "deny" => Ok(RuleKind::Deny),
+            _ => Err(anyhow::anyhow!("rule kind should be allow or deny")),
+        }
+    }
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            RuleKind::Allow => "allow",
+            RuleKind::Deny => "deny",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+impl fmt::Display for SenderRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind, self.sender_id)
+    }
+}
+
+pub async fn add_rule_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    rule: &SenderRule,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let kind = rule.kind.to_string();
+
+    let _query = sqlx::query!(
+        "INSERT INTO sender_rule (tag_id, kind, sender_id) VALUES ($1, $2, $3)
+        ON CONFLICT (tag_id, kind, sender_id)
+        DO NOTHING;",
+        tag_id,
+        kind,
+        rule.sender_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_rule_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    rule: &SenderRule,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let kind = rule.kind.to_string();
+
+    let _query = sqlx::query!(
+        "DELETE FROM sender_rule WHERE tag_id = $1 AND kind = $2 AND sender_id = $3;",
+        tag_id,
+        kind,
+        rule.sender_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn rule_list_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<SenderRule>> {
+    let owner_id = user.to_string();
+
+    let rules = sqlx::query!(
+        "
+    SELECT sr.kind, sr.sender_id
+    FROM sender_rule sr INNER JOIN user_folder uf
+    ON sr.tag_id = uf.tag_id
+    WHERE uf.owner_id = $1 AND uf.tag_name = $2
+    ",
+        owner_id,
+        tag_name
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        anyhow::Ok(SenderRule {
+            kind: r.kind.parse::<RuleKind>()?,
+            sender_id: r.sender_id,
+        })
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(rules)
+}
+
+// Sender rules of every tag, keyed by tag id
+pub async fn all_rules_with_pool(
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<HashMap<i64, Vec<SenderRule>>> {
+    let records = sqlx::query!(
+        "
+    SELECT tag_id, kind, sender_id
+    FROM sender_rule
+    "
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut rules = HashMap::<i64, Vec<SenderRule>>::new();
+    for r in records {
+        let rule = SenderRule {
+            kind: r.kind.parse::<RuleKind>()?,
+            sender_id: r.sender_id,
+        };
+        rules.entry(r.tag_id).or_default().push(rule);
+    }
+
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use slack_morphism::{SlackBotId, SlackChannelId};
+
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn accepts_test() {
+        let monitor =
+            SlackMessageSender::new().with_bot_id(SlackBotId::new("Bmonitor".to_string()));
+        let ci = SlackMessageSender::new().with_bot_id(SlackBotId::new("Bci".to_string()));
+        let user = SlackMessageSender::new().with_user(SlackUserId::new("U01".to_string()));
+
+        let deny_ci = vec![SenderRule {
+            kind: RuleKind::Deny,
+            sender_id: "Bci".to_string(),
+        }];
+        let allow_monitor = vec![SenderRule {
+            kind: RuleKind::Allow,
+            sender_id: "Bmonitor".to_string(),
+        }];
+
+        assert!(accepts(&[], &ci));
+        assert!(accepts(&deny_ci, &monitor));
+        assert!(accepts(&deny_ci, &user));
+        assert!(!accepts(&deny_ci, &ci));
+        assert!(accepts(&allow_monitor, &monitor));
+        assert!(!accepts(&allow_monitor, &user));
+    }
+
+    async fn test_add_remove_rule(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let tag_name = "test_a";
+        let rule = SenderRule {
+            kind: RuleKind::Deny,
+            sender_id: "Bci".to_string(),
+        };
+
+        storage.add_rule(tag_name, owner_id.clone(), &rule).await?;
+        let rules = storage.rule_list(tag_name, owner_id.clone()).await?;
+        assert_eq!(rules, vec![rule.clone()]);
+
+        let routes = storage.route_map().await?;
+        assert!(routes[&SlackChannelId::new("C01".to_string())]
+            .iter()
+            .any(|route| route.rules.contains(&rule)));
+
+        storage
+            .remove_rule(tag_name, owner_id.clone(), &rule)
+            .await?;
+        let rules = storage.rule_list(tag_name, owner_id).await?;
+        assert!(rules.is_empty());
+
+        Ok(())
+    }
+
+    storage_tests!(test_add_remove_rule);
+}
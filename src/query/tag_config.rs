@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use slack_morphism::{SlackChannelId, SlackUserId};
+use sqlx::{Pool, Sqlite};
+
+use super::thread_mode::ThreadMode;
+
+// Everything a tag is made of in user_folder, channel_list and dist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagConfig {
+    pub tag_name: String,
+    pub owner_id: SlackUserId,
+    pub bot: bool,
+    pub file: bool,
+    pub thread_mode: ThreadMode,
+    pub channels: Vec<SlackChannelId>,
+    pub dists: Vec<SlackChannelId>,
+}
+
+// A row of user_folder
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TagRecord {
+    pub tag_id: i64,
+    pub tag_name: String,
+    pub owner_id: String,
+    pub bot: bool,
+    pub file: bool,
+    pub thread_mode: String,
+}
+
+// Tags in the order they were created, with the channels of channel_list and dist rows given by tag id
+pub fn assemble_configs(
+    tags: Vec<TagRecord>,
+    channels: Vec<(i64, String)>,
+    dists: Vec<(i64, String)>,
+) -> Vec<TagConfig> {
+    let mut channel_map = HashMap::<i64, Vec<SlackChannelId>>::new();
+    for (tag_id, channel_id) in channels {
+        channel_map
+            .entry(tag_id)
+            .or_default()
+            .push(SlackChannelId::new(channel_id));
+    }
+    let mut dist_map = HashMap::<i64, Vec<SlackChannelId>>::new();
+    for (tag_id, dist_channel_id) in dists {
+        dist_map
+            .entry(tag_id)
+            .or_default()
+            .push(SlackChannelId::new(dist_channel_id));
+    }
+
+    tags.into_iter()
+        .map(|r| TagConfig {
+            tag_name: r.tag_name,
+            owner_id: SlackUserId::new(r.owner_id),
+            bot: r.bot,
+            file: r.file,
+            // an unknown mode forwards everything, as mirror does
+            thread_mode: r.thread_mode.parse().unwrap_or(ThreadMode::Mirror),
+            channels: channel_map.remove(&r.tag_id).unwrap_or_default(),
+            dists: dist_map.remove(&r.tag_id).unwrap_or_default(),
+        })
+        .collect()
+}
+
+pub async fn tag_config_list_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<TagConfig>> {
+    let tags = sqlx::query_as!(
+        TagRecord,
+        "
+    SELECT tag_id, tag_name, owner_id, bot, file, thread_mode
+    FROM user_folder
+    ORDER BY tag_id
+    "
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let channels = sqlx::query!(
+        "
+    SELECT tag_id, channel_id
+    FROM channel_list
+    ORDER BY tag_id, channel_id
+    "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| (r.tag_id, r.channel_id))
+    .collect::<Vec<_>>();
+
+    let dists = sqlx::query!(
+        "
+    SELECT tag_id, dist_channel_id
+    FROM dist
+    ORDER BY tag_id, dist_channel_id
+    "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| (r.tag_id, r.dist_channel_id))
+    .collect::<Vec<_>>();
+
+    Ok(assemble_configs(tags, channels, dists))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    async fn test_tag_config_list(storage: &dyn Storage) -> anyhow::Result<()> {
+        let configs = storage.tag_config_list().await?;
+
+        let test_a = configs
+            .iter()
+            .find(|config| config.tag_name == "test_a")
+            .unwrap();
+        assert_eq!(test_a.owner_id, SlackUserId::new("U00001".to_string()));
+        assert_eq!(
+            test_a.channels,
+            vec![
+                SlackChannelId::new("C01".to_string()),
+                SlackChannelId::new("C02".to_string())
+            ]
+        );
+        assert!(test_a.dists.is_empty());
+
+        let test_target_bot = configs
+            .iter()
+            .find(|config| config.tag_name == "test_target_bot")
+            .unwrap();
+        assert!(test_target_bot.bot);
+        assert_eq!(
+            test_target_bot.dists,
+            vec![SlackChannelId::new("Cdist_bot".to_string())]
+        );
+
+        let test_b = configs
+            .iter()
+            .find(|config| config.tag_name == "test_b")
+            .unwrap();
+        assert!(test_b.channels.is_empty());
+
+        Ok(())
+    }
+
+    storage_tests!(test_tag_config_list);
+}
@@ -0,0 +1,325 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::Context;
+use regex::Regex;
+use slack_morphism::SlackUserId;
+use sqlx::{Pool, Sqlite};
+
+use super::utils;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterKind {
+    Include,
+    Exclude,
+}
+
+// A keyword or a regex that decides whether a tag collects a message
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TagFilter {
+    pub kind: FilterKind,
+    pub pattern: String,
+    pub is_regex: bool,
+}
+
+impl TagFilter {
+    // Patterns surrounded by slashes, like `/^deploy/`, are treated as regex
+    pub fn parse(kind: FilterKind, arg: &str) -> anyhow::Result<Self> {
+        let regex_pattern = arg
+            .strip_prefix('/')
+            .and_then(|s| s.strip_suffix('/'))
+            .filter(|s| !s.is_empty());
+        let filter = match regex_pattern {
+            Some(pattern) => TagFilter {
+                kind,
+                pattern: pattern.to_string(),
+                is_regex: true,
+            },
+            None => TagFilter {
+                kind,
+                pattern: arg.to_string(),
+                is_regex: false,
+            },
+        };
+        filter.compile()?;
+        Ok(filter)
+    }
+
+    pub fn compile(&self) -> anyhow::Result<CompiledFilter> {
+        let matcher = if self.is_regex {
+            let regex = Regex::new(&self.pattern)
+                .with_context(|| format!("invalid regex: {}", self.pattern))?;
+            Matcher::Regex(regex)
+        } else {
+            Matcher::Keyword(self.pattern.to_lowercase())
+        };
+        Ok(CompiledFilter {
+            kind: self.kind,
+            matcher,
+        })
+    }
+}
+
+// A filter ready to match messages, built once with the cached routes
+#[derive(Debug, Clone)]
+pub struct CompiledFilter {
+    pub kind: FilterKind,
+    matcher: Matcher,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    // lowercased, as keywords ignore case
+    Keyword(String),
+    Regex(Regex),
+    // a pattern saved before patterns were checked, which matches nothing
+    Invalid,
+}
+
+impl CompiledFilter {
+    // filters that fail to compile are kept, so that an include filter still holds messages back
+    pub fn from_saved(filter: &TagFilter) -> Self {
+        filter.compile().unwrap_or(CompiledFilter {
+            kind: filter.kind,
+            matcher: Matcher::Invalid,
+        })
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match &self.matcher {
+            Matcher::Keyword(keyword) => text.to_lowercase().contains(keyword),
+            Matcher::Regex(regex) => regex.is_match(text),
+            Matcher::Invalid => false,
+        }
+    }
+}
+
+// A message passes when it matches some include filter (if any exists) and no exclude filter
+pub fn accepts(filters: &[CompiledFilter], text: &str) -> bool {
+    let mut include_filters = filters
+        .iter()
+        .filter(|f| f.kind == FilterKind::Include)
+        .peekable();
+    let is_included = include_filters.peek().is_none() || include_filters.any(|f| f.matches(text));
+    let is_excluded = filters
+        .iter()
+        .filter(|f| f.kind == FilterKind::Exclude)
+        .any(|f| f.matches(text));
+
+    is_included && !is_excluded
+}
+
+impl FromStr for FilterKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "include" => Ok(FilterKind::Include),
+            "exclude" => Ok(FilterKind::Exclude),
+            _ => Err(anyhow::anyhow!("filter kind should be include or exclude")),
+        }
+    }
+}
+
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FilterKind::Include => "include",
+            FilterKind::Exclude => "exclude",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+impl fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_regex {
+            write!(f, "{} /{}/", self.kind, self.pattern)
+        } else {
+            write!(f, "{} {}", self.kind, self.pattern)
+        }
+    }
+}
+
+pub async fn add_filter_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    filter: &TagFilter,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    filter.compile()?;
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let kind = filter.kind.to_string();
+
+    let _query = sqlx::query!(
+        "INSERT INTO tag_filter (tag_id, kind, pattern, is_regex) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (tag_id, kind, pattern, is_regex)
+        DO NOTHING;",
+        tag_id,
+        kind,
+        filter.pattern,
+        filter.is_regex
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_filter_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    filter: &TagFilter,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let kind = filter.kind.to_string();
+
+    let _query = sqlx::query!(
+        "DELETE FROM tag_filter
+        WHERE tag_id = $1 AND kind = $2 AND pattern = $3 AND is_regex = $4;",
+        tag_id,
+        kind,
+        filter.pattern,
+        filter.is_regex
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn filter_list_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<TagFilter>> {
+    let owner_id = user.to_string();
+
+    let filters = sqlx::query!(
+        "
+    SELECT tf.kind, tf.pattern, tf.is_regex
+    FROM tag_filter tf INNER JOIN user_folder uf
+    ON tf.tag_id = uf.tag_id
+    WHERE uf.owner_id = $1 AND uf.tag_name = $2
+    ",
+        owner_id,
+        tag_name
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        anyhow::Ok(TagFilter {
+            kind: r.kind.parse::<FilterKind>()?,
+            pattern: r.pattern,
+            is_regex: r.is_regex,
+        })
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(filters)
+}
+
+// Filters of every tag, keyed by tag id
+pub async fn all_filters_with_pool(
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<HashMap<i64, Vec<TagFilter>>> {
+    let records = sqlx::query!(
+        "
+    SELECT tag_id, kind, pattern, is_regex
+    FROM tag_filter
+    "
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut filters = HashMap::<i64, Vec<TagFilter>>::new();
+    for r in records {
+        let filter = TagFilter {
+            kind: r.kind.parse::<FilterKind>()?,
+            pattern: r.pattern,
+            is_regex: r.is_regex,
+        };
+        filters.entry(r.tag_id).or_default().push(filter);
+    }
+
+    Ok(filters)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn accepts_test() {
+        let include = TagFilter::parse(FilterKind::Include, "deploy").unwrap();
+        let exclude = TagFilter::parse(FilterKind::Exclude, "/^\\[test\\]/").unwrap();
+        let filters = vec![include.compile().unwrap(), exclude.compile().unwrap()];
+
+        assert!(accepts(&[], "anything"));
+        assert!(accepts(&filters, "Deploy finished"));
+        assert!(!accepts(&filters, "[test] deploy finished"));
+        assert!(!accepts(&filters, "build finished"));
+
+        // a saved pattern that no longer compiles holds every message back
+        let invalid = TagFilter {
+            kind: FilterKind::Include,
+            pattern: "[".to_string(),
+            is_regex: true,
+        };
+        assert!(!accepts(
+            &[CompiledFilter::from_saved(&invalid)],
+            "anything"
+        ));
+    }
+
+    #[tokio::test]
+    async fn parse_test() {
+        let regex = TagFilter::parse(FilterKind::Include, "/^error/").unwrap();
+        let keyword = TagFilter::parse(FilterKind::Include, "/").unwrap();
+
+        assert!(regex.is_regex);
+        assert_eq!(regex.pattern, "^error");
+        assert!(!keyword.is_regex);
+        assert!(TagFilter::parse(FilterKind::Include, "/[/").is_err());
+    }
+
+    async fn test_add_remove_filter(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let tag_name = "test_a";
+        let filter = TagFilter::parse(FilterKind::Include, "/^deploy/")?;
+
+        storage
+            .add_filter(tag_name, owner_id.clone(), &filter)
+            .await?;
+        let filters = storage.filter_list(tag_name, owner_id.clone()).await?;
+        assert_eq!(filters, vec![filter.clone()]);
+
+        storage
+            .remove_filter(tag_name, owner_id.clone(), &filter)
+            .await?;
+        let filters = storage.filter_list(tag_name, owner_id.clone()).await?;
+        assert!(filters.is_empty());
+
+        let invalid = TagFilter {
+            kind: FilterKind::Include,
+            pattern: "[".to_string(),
+            is_regex: true,
+        };
+        assert!(storage
+            .add_filter(tag_name, owner_id.clone(), &invalid)
+            .await
+            .is_err());
+        assert!(storage.filter_list(tag_name, owner_id).await?.is_empty());
+
+        Ok(())
+    }
+
+    storage_tests!(test_add_remove_filter);
+}
@@ -0,0 +1,234 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::Context;
+use slack_morphism::SlackUserId;
+use sqlx::{Pool, Sqlite};
+
+use super::utils;
+
+// What a member may do with a tag shared with them; the owner may do everything
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TagRole {
+    // list the channels and set the tag on dist channels
+    Viewer,
+    // also change the channels and the settings of the tag
+    Editor,
+}
+
+// A user id or a user group id that a tag is shared with
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TagShare {
+    pub tag_name: String,
+    pub owner_id: SlackUserId,
+    pub member_id: String,
+    pub role: TagRole,
+}
+
+impl FromStr for TagRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(TagRole::Viewer),
+            "editor" => Ok(TagRole::Editor),
+            _ => Err(anyhow::anyhow!("role should be editor or viewer")),
+        }
+    }
+}
+
+impl fmt::Display for TagRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role = match self {
+            TagRole::Viewer => "viewer",
+            TagRole::Editor => "editor",
+        };
+        write!(f, "{role}")
+    }
+}
+
+impl fmt::Display for TagShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.member_id, self.role)
+    }
+}
+
+pub async fn share_tag_with_pool(
+    tag_name: &str,
+    owner: SlackUserId,
+    member_id: &str,
+    role: TagRole,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let tag_id = utils::fetch_tag_id_with_pool(owner, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let role = role.to_string();
+
+    let _query = sqlx::query!(
+        "INSERT INTO tag_member (tag_id, member_id, role) VALUES ($1, $2, $3)
+        ON CONFLICT (tag_id, member_id)
+        DO UPDATE SET role = excluded.role;",
+        tag_id,
+        member_id,
+        role
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unshare_tag_with_pool(
+    tag_name: &str,
+    owner: SlackUserId,
+    member_id: &str,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let tag_id = utils::fetch_tag_id_with_pool(owner, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+
+    let _query = sqlx::query!(
+        "DELETE FROM tag_member WHERE tag_id = $1 AND member_id = $2;",
+        tag_id,
+        member_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// The members one tag is shared with
+pub async fn tag_shares_with_pool(
+    tag_name: &str,
+    owner: &SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<TagShare>> {
+    let owner_str = owner.to_string();
+    let shares = sqlx::query!(
+        "
+    SELECT uf.tag_name, uf.owner_id, tm.member_id, tm.role
+    FROM tag_member tm INNER JOIN user_folder uf
+    ON tm.tag_id = uf.tag_id
+    WHERE uf.tag_name = $1 AND uf.owner_id = $2
+    ORDER BY tm.member_id
+    ",
+        tag_name,
+        owner_str
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| {
+        anyhow::Ok(TagShare {
+            tag_name: r.tag_name,
+            owner_id: SlackUserId::new(r.owner_id),
+            member_id: r.member_id,
+            role: r.role.parse::<TagRole>()?,
+        })
+    })
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(shares)
+}
+
+// The tags shared with any of the members; a user is in a few groups, so they are asked one by one
+pub async fn member_shares_with_pool(
+    member_ids: &[String],
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Vec<TagShare>> {
+    let mut shares = Vec::new();
+    for member_id in member_ids {
+        let rows = sqlx::query!(
+            "
+        SELECT uf.tag_name, uf.owner_id, tm.member_id, tm.role
+        FROM tag_member tm INNER JOIN user_folder uf
+        ON tm.tag_id = uf.tag_id
+        WHERE tm.member_id = $1
+        ",
+            member_id
+        )
+        .fetch_all(pool)
+        .await?;
+        for r in rows {
+            shares.push(TagShare {
+                tag_name: r.tag_name,
+                owner_id: SlackUserId::new(r.owner_id),
+                member_id: r.member_id,
+                role: r.role.parse::<TagRole>()?,
+            });
+        }
+    }
+    shares.sort_by(|a, b| (&a.tag_name, &a.owner_id.0).cmp(&(&b.tag_name, &b.owner_id.0)));
+
+    Ok(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn role_order_test() {
+        assert!(TagRole::Viewer < TagRole::Editor);
+        assert_eq!("editor".parse::<TagRole>().unwrap(), TagRole::Editor);
+        assert!("owner".parse::<TagRole>().is_err());
+    }
+
+    async fn test_share_unshare(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let tag_name = "test_a";
+
+        storage
+            .share_tag(tag_name, owner_id.clone(), "U00002", TagRole::Viewer)
+            .await?;
+        storage
+            .share_tag(tag_name, owner_id.clone(), "U00002", TagRole::Editor)
+            .await?;
+        storage
+            .share_tag("test_b", owner_id.clone(), "S0group", TagRole::Viewer)
+            .await?;
+        let share = TagShare {
+            tag_name: tag_name.to_string(),
+            owner_id: owner_id.clone(),
+            member_id: "U00002".to_string(),
+            role: TagRole::Editor,
+        };
+        assert_eq!(
+            storage.tag_shares(tag_name, &owner_id).await?,
+            vec![share.clone()]
+        );
+
+        // the shares with the user and with the groups the user is in
+        let members = ["U00002".to_string(), "S0group".to_string()];
+        let shares = storage.member_shares(&members).await?;
+        assert_eq!(
+            shares,
+            vec![
+                share,
+                TagShare {
+                    tag_name: "test_b".to_string(),
+                    owner_id: owner_id.clone(),
+                    member_id: "S0group".to_string(),
+                    role: TagRole::Viewer,
+                }
+            ]
+        );
+        assert!(storage
+            .member_shares(&["U00003".to_string()])
+            .await?
+            .is_empty());
+
+        storage
+            .unshare_tag(tag_name, owner_id.clone(), "U00002")
+            .await?;
+        assert!(storage.tag_shares(tag_name, &owner_id).await?.is_empty());
+
+        Ok(())
+    }
+
+    storage_tests!(test_share_unshare);
+}
@@ -0,0 +1,81 @@
+use std::{fmt, str::FromStr};
+
+// How a tag forwards thread replies posted in its channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadMode {
+    // post replies under the forwarded parent
+    Mirror,
+    // drop replies, except those also sent to the channel
+    Drop,
+    // forward top-level messages only
+    Root,
+}
+
+// Where a message sits in a thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadPosition {
+    Root,
+    Reply,
+    BroadcastReply,
+}
+
+impl ThreadMode {
+    pub fn accepts(self, position: ThreadPosition) -> bool {
+        match (self, position) {
+            (ThreadMode::Mirror, _)
+            | (_, ThreadPosition::Root)
+            | (ThreadMode::Drop, ThreadPosition::BroadcastReply) => true,
+            (ThreadMode::Drop | ThreadMode::Root, _) => false,
+        }
+    }
+}
+
+impl FromStr for ThreadMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mirror" => Ok(ThreadMode::Mirror),
+            "drop" => Ok(ThreadMode::Drop),
+            "root" => Ok(ThreadMode::Root),
+            _ => Err(anyhow::anyhow!(
+                "thread mode should be mirror, drop or root"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ThreadMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            ThreadMode::Mirror => "mirror",
+            ThreadMode::Drop => "drop",
+            ThreadMode::Root => "root",
+        };
+        write!(f, "{mode}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_test() {
+        assert!(ThreadMode::Mirror.accepts(ThreadPosition::Reply));
+        assert!(ThreadMode::Drop.accepts(ThreadPosition::Root));
+        assert!(ThreadMode::Drop.accepts(ThreadPosition::BroadcastReply));
+        assert!(!ThreadMode::Drop.accepts(ThreadPosition::Reply));
+        assert!(ThreadMode::Root.accepts(ThreadPosition::Root));
+        assert!(!ThreadMode::Root.accepts(ThreadPosition::BroadcastReply));
+    }
+
+    #[test]
+    fn from_str_test() {
+        let modes = [ThreadMode::Mirror, ThreadMode::Drop, ThreadMode::Root];
+        assert!(modes
+            .iter()
+            .all(|mode| mode.to_string().parse::<ThreadMode>().unwrap() == *mode));
+        assert!("flat".parse::<ThreadMode>().is_err());
+    }
+}
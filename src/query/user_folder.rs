@@ -1,23 +1,17 @@
+use anyhow::Context;
 use slack_morphism::{SlackChannelId, SlackUserId};
-use sqlx::{Pool, Sqlite, SqlitePool};
+use sqlx::{Pool, Sqlite};
 
-use super::utils;
+use crate::storage::TagError;
+
+use super::{thread_mode::ThreadMode, utils};
 
 // register channel to tag
-pub async fn register_channel(
-    tag_name: &str,
-    channel: SlackChannelId,
-    user: SlackUserId,
-) -> anyhow::Result<()> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    register_channel_with_pool(tag_name, channel, user, pool).await
-}
 pub async fn register_channel_with_pool(
     tag_name: &str,
     channel: SlackChannelId,
     user: SlackUserId,
-    pool: Pool<Sqlite>,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<()> {
     let owner_id = user.to_string();
 
@@ -30,10 +24,10 @@ pub async fn register_channel_with_pool(
         tag_name,
         owner_id
     )
-    .execute(&pool)
+    .execute(pool)
     .await?;
 
-    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, &pool).await?;
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, pool).await?;
 
     let channel_id = channel.to_string();
     let _query_cl = sqlx::query!(
@@ -43,74 +37,119 @@ pub async fn register_channel_with_pool(
         channel_id,
         tag_id
     )
-    .execute(&pool)
+    .execute(pool)
     .await?;
 
     Ok(())
 }
-pub async fn unregister_channel(
+// Remove the channel from the tag; the tag and its dist channels are kept
+pub async fn unregister_channel_with_pool(
     tag_name: &str,
-    _channel: SlackChannelId,
+    channel: SlackChannelId,
     user: SlackUserId,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<()> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    unregister_channel_with_url(tag_name, user, pool).await
+    let tag_id = utils::fetch_tag_id_with_pool(user, tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let channel_id = channel.to_string();
+    let _query_cl = sqlx::query!(
+        "DELETE FROM channel_list WHERE tag_id = $1 AND channel_id = $2;",
+        tag_id,
+        channel_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
 }
-
-// Unregistration of channel_list table is automatic due to cascade constraints
-pub async fn unregister_channel_with_url(
+// Unregistration of channel_list and dist tables is automatic due to cascade constraints
+pub async fn drop_tag_with_pool(
     tag_name: &str,
     user: SlackUserId,
-    pool: Pool<Sqlite>,
-) -> anyhow::Result<()> {
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<i64> {
     let owner_id = user.to_string();
-    let _query_uf = sqlx::query!(
-        "DELETE FROM user_folder WHERE tag_name = $1 AND owner_id = $2;",
+    let tag_id = sqlx::query_scalar!(
+        r#"DELETE FROM user_folder WHERE tag_name = $1 AND owner_id = $2
+        RETURNING tag_id AS "tag_id!";"#,
         tag_name,
         owner_id
     )
-    .execute(&pool)
-    .await?;
-    Ok(())
+    // the delete is only kept when the statement runs to its end, so every returned row is read
+    .fetch_all(pool)
+    .await?
+    .pop()
+    .ok_or_else(|| TagError::NotFound(tag_name.to_string()))?;
+    Ok(tag_id)
 }
-pub async fn retrieve_bot(
+pub async fn retrieve_bot_with_pool(
     tag_name: &str,
     user: SlackUserId,
     retrieve_bot: bool,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<()> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    retrieve_bot_with_pool(tag_name, user, retrieve_bot, pool).await
+    let owner_id = user.to_string();
+    let result = sqlx::query!(
+        "UPDATE user_folder SET bot = $1 WHERE tag_name = $2 AND owner_id = $3;",
+        retrieve_bot,
+        tag_name,
+        owner_id
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(TagError::NotFound(tag_name.to_string()).into());
+    }
+    Ok(())
 }
-async fn retrieve_bot_with_pool(
+
+pub async fn retrieve_file_with_pool(
     tag_name: &str,
     user: SlackUserId,
-    retrieve_bot: bool,
-    pool: Pool<Sqlite>,
+    retrieve_file: bool,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<()> {
     let owner_id = user.to_string();
-    let _query_uf = sqlx::query!(
-        "UPDATE user_folder SET bot = $1 WHERE tag_name = $2 AND owner_id = $3;",
-        retrieve_bot,
+    let result = sqlx::query!(
+        "UPDATE user_folder SET file = $1 WHERE tag_name = $2 AND owner_id = $3;",
+        retrieve_file,
         tag_name,
         owner_id
     )
-    .execute(&pool)
+    .execute(pool)
     .await?;
+    if result.rows_affected() == 0 {
+        return Err(TagError::NotFound(tag_name.to_string()).into());
+    }
     Ok(())
 }
 
-pub async fn is_valid_tag_for_user(user: &SlackUserId, tag_name: &str) -> anyhow::Result<bool> {
-    let db_url = super::db_url()?;
-    let pool = SqlitePool::connect(&db_url).await?;
-    is_valid_tag_for_user_with_pool(user, tag_name, pool).await
+pub async fn thread_mode_with_pool(
+    tag_name: &str,
+    user: SlackUserId,
+    thread_mode: ThreadMode,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let owner_id = user.to_string();
+    let thread_mode_str = thread_mode.to_string();
+    let result = sqlx::query!(
+        "UPDATE user_folder SET thread_mode = $1 WHERE tag_name = $2 AND owner_id = $3;",
+        thread_mode_str,
+        tag_name,
+        owner_id
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(TagError::NotFound(tag_name.to_string()).into());
+    }
+    Ok(())
 }
 
-async fn is_valid_tag_for_user_with_pool(
+pub async fn is_valid_tag_for_user_with_pool(
     user: &SlackUserId,
     tag_name: &str,
-    pool: Pool<Sqlite>,
+    pool: &Pool<Sqlite>,
 ) -> anyhow::Result<bool> {
     let user_str = user.to_string();
 
@@ -125,7 +164,7 @@ async fn is_valid_tag_for_user_with_pool(
         user_str,
         tag_name
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await?
     .is_exist
     .eq(&1);
@@ -133,106 +172,360 @@ async fn is_valid_tag_for_user_with_pool(
     Ok(is_valid)
 }
 
+// Rename the tag, keeping its channels, dist channels and settings
+pub async fn rename_tag_with_pool(
+    tag_name: &str,
+    new_name: &str,
+    user: SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    if is_valid_tag_for_user_with_pool(&user, new_name, pool).await? {
+        return Err(TagError::AlreadyExists(new_name.to_string()).into());
+    }
+    let owner_id = user.to_string();
+    let result = sqlx::query!(
+        "UPDATE user_folder SET tag_name = $1 WHERE tag_name = $2 AND owner_id = $3;",
+        new_name,
+        tag_name,
+        owner_id
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(TagError::NotFound(tag_name.to_string()).into());
+    }
+    Ok(())
+}
+
+// Create a new tag with the channels, settings, filters and sender rules of the tag.
+// The copy is not set on any dist channel yet.
+pub async fn copy_tag_with_pool(
+    tag_name: &str,
+    new_name: &str,
+    user: SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    if is_valid_tag_for_user_with_pool(&user, new_name, pool).await? {
+        return Err(TagError::AlreadyExists(new_name.to_string()).into());
+    }
+    let tag_id = utils::fetch_tag_id_with_pool(user.clone(), tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let owner_id = user.to_string();
+
+    let mut tx = pool.begin().await?;
+    let _query_uf = sqlx::query!(
+        "INSERT INTO user_folder (tag_name, owner_id, bot, file, thread_mode)
+        SELECT $1, owner_id, bot, file, thread_mode FROM user_folder WHERE tag_id = $2;",
+        new_name,
+        tag_id
+    )
+    .execute(&mut tx)
+    .await?;
+    let new_id = sqlx::query!(
+        "SELECT tag_id FROM user_folder WHERE tag_name = $1 AND owner_id = $2;",
+        new_name,
+        owner_id
+    )
+    .fetch_one(&mut tx)
+    .await?
+    .tag_id;
+    let _query_copy = sqlx::query!(
+        "INSERT INTO channel_list (channel_id, tag_id)
+        SELECT channel_id, $1 FROM channel_list WHERE tag_id = $2;
+        INSERT INTO tag_filter (tag_id, kind, pattern, is_regex)
+        SELECT $3, kind, pattern, is_regex FROM tag_filter WHERE tag_id = $4;
+        INSERT INTO sender_rule (tag_id, kind, sender_id)
+        SELECT $5, kind, sender_id FROM sender_rule WHERE tag_id = $6;",
+        new_id,
+        tag_id,
+        new_id,
+        tag_id,
+        new_id,
+        tag_id
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Move the channels, dist channels, filters, sender rules and shares of the tag into `into`,
+// then delete the tag. The settings of `into` and the roles it already gives are kept,
+// and its valid_count is counted again from dist.
+pub async fn merge_tag_with_pool(
+    tag_name: &str,
+    into: &str,
+    user: SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<i64> {
+    if tag_name == into {
+        return Err(TagError::MergeIntoItself.into());
+    }
+    let tag_id = utils::fetch_tag_id_with_pool(user.clone(), tag_name, pool)
+        .await
+        .context("failed to fetch the tag")?;
+    let into_id = utils::fetch_tag_id_with_pool(user, into, pool)
+        .await
+        .context("failed to fetch the tag to merge into")?;
+
+    let mut tx = pool.begin().await?;
+    let _query_merge = sqlx::query!(
+        "INSERT INTO channel_list (channel_id, tag_id)
+        SELECT channel_id, $1 FROM channel_list WHERE tag_id = $2
+        ON CONFLICT (channel_id, tag_id) DO NOTHING;
+        INSERT INTO dist (user_id, tag_id, dist_channel_id)
+        SELECT user_id, $3, dist_channel_id FROM dist WHERE tag_id = $4
+        ON CONFLICT (user_id, tag_id, dist_channel_id) DO NOTHING;
+        INSERT INTO tag_filter (tag_id, kind, pattern, is_regex)
+        SELECT $5, kind, pattern, is_regex FROM tag_filter WHERE tag_id = $6
+        ON CONFLICT (tag_id, kind, pattern, is_regex) DO NOTHING;
+        INSERT INTO sender_rule (tag_id, kind, sender_id)
+        SELECT $7, kind, sender_id FROM sender_rule WHERE tag_id = $8
+        ON CONFLICT (tag_id, kind, sender_id) DO NOTHING;
+        INSERT INTO tag_member (tag_id, member_id, role)
+        SELECT $9, member_id, role FROM tag_member WHERE tag_id = $10
+        ON CONFLICT (tag_id, member_id) DO NOTHING;
+        UPDATE user_folder
+        SET valid_count = (SELECT COUNT(*) FROM dist WHERE tag_id = $11)
+        WHERE tag_id = $12;
+        DELETE FROM user_folder WHERE tag_id = $13;",
+        into_id,
+        tag_id,
+        into_id,
+        tag_id,
+        into_id,
+        tag_id,
+        into_id,
+        tag_id,
+        into_id,
+        tag_id,
+        into_id,
+        into_id,
+        tag_id
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(tag_id)
+}
+
 #[cfg(test)]
 mod tests {
     use slack_morphism::SlackUserId;
 
+    use crate::{
+        query::{tag_config::TagConfig, tag_member::TagRole},
+        storage::{testing::storage_tests, Storage},
+    };
+
     use super::*;
 
     async fn register_test(
-        pool: Pool<Sqlite>,
+        storage: &dyn Storage,
     ) -> anyhow::Result<(String, SlackChannelId, SlackUserId)> {
         let tag_name = "test";
         let channel = SlackChannelId::new("C01234".to_string());
         let user = SlackUserId::new("U0987".to_string());
-        register_channel_with_pool(tag_name, channel.clone(), user.clone(), pool).await?;
+        storage
+            .register_channel(tag_name, channel.clone(), user.clone())
+            .await?;
         Ok((tag_name.to_string(), channel, user))
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_register_channel(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (_, channel, _) = register_test(pool.clone()).await?;
-
-        let result_channel_id = sqlx::query!(
-            "SELECT uf.tag_id, cl.channel_id
-             FROM user_folder AS uf INNER JOIN channel_list AS cl
-             ON cl.tag_id = uf.tag_id
-             ",
-        )
-        .fetch_all(&pool)
-        .await?
-        .iter()
-        .map(|r| r.channel_id.clone())
-        .collect::<Vec<_>>();
-
-        let target = channel.to_string();
-        let is_registered = result_channel_id.contains(&target);
-        assert!(is_registered);
+    async fn tag_config(
+        storage: &dyn Storage,
+        tag_name: &str,
+        user: &SlackUserId,
+    ) -> anyhow::Result<TagConfig> {
+        storage
+            .tag_config_list()
+            .await?
+            .into_iter()
+            .find(|config| config.tag_name == tag_name && config.owner_id == *user)
+            .ok_or_else(|| anyhow::anyhow!("tag `{tag_name}` is not found"))
+    }
+
+    async fn test_register_channel(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag_name, channel, user) = register_test(storage).await?;
+
+        let channels = storage.channel_list(&tag_name, user).await?;
+        assert_eq!(channels, vec![channel]);
         Ok(())
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_unregister_channel(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (tag_name, _channel, user) = register_test(pool.clone()).await?;
+    async fn test_unregister_channel(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag_name, channel, user) = register_test(storage).await?;
+        let other = SlackChannelId::new("C05678".to_string());
+        storage
+            .register_channel(&tag_name, other.clone(), user.clone())
+            .await?;
+
+        storage
+            .unregister_channel(&tag_name, channel, user.clone())
+            .await?;
+
+        // the other channel and the tag are kept
+        assert!(storage.is_valid_tag_for_user(&user, &tag_name).await?);
+        let channels = storage.channel_list(&tag_name, user).await?;
+        assert_eq!(channels, vec![other]);
+
+        Ok(())
+    }
 
-        unregister_channel_with_url(&tag_name, user, pool.clone()).await?;
+    async fn test_retrieve_bot(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag_name, _channel, user) = register_test(storage).await?;
 
-        let result_channel_id = sqlx::query!(
-            "SELECT channel_id
-            FROM channel_list
-            WHERE channel_id = 'C01234'
-            ",
-        )
-        .fetch_one(&pool)
-        .await;
+        assert!(!tag_config(storage, &tag_name, &user).await?.bot);
 
-        let exist_channel_id = result_channel_id.is_err();
-        assert!(exist_channel_id);
+        storage.retrieve_bot(&tag_name, user.clone(), true).await?;
+
+        assert!(tag_config(storage, &tag_name, &user).await?.bot);
 
         Ok(())
     }
 
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_retrieve_bot(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (tag_name, _channel, user) = register_test(pool.clone()).await?;
+    async fn test_retrieve_file(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag_name, _channel, user) = register_test(storage).await?;
+
+        storage
+            .retrieve_file(&tag_name, user.clone(), false)
+            .await?;
 
-        let result_bot = sqlx::query!(
-            "SELECT bot
-            FROM user_folder
-            WHERE tag_name = 'test' AND owner_id = 'U0987'
-            ",
-        )
-        .fetch_one(&pool)
-        .await?
-        .bot;
+        assert!(!tag_config(storage, &tag_name, &user).await?.file);
+        assert!(storage.retrieve_file("missing", user, false).await.is_err());
 
-        assert!(!result_bot);
+        Ok(())
+    }
 
-        retrieve_bot_with_pool(&tag_name, user, true, pool.clone()).await?;
+    async fn test_thread_mode(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag_name, _channel, user) = register_test(storage).await?;
 
-        let result_bot = sqlx::query!(
-            "SELECT bot
-            FROM user_folder
-            WHERE tag_name = 'test' AND owner_id = 'U0987'
-            ",
-        )
-        .fetch_one(&pool)
-        .await?
-        .bot;
+        storage
+            .thread_mode(&tag_name, user.clone(), ThreadMode::Drop)
+            .await?;
 
-        assert!(result_bot);
+        let config = tag_config(storage, &tag_name, &user).await?;
+        assert_eq!(config.thread_mode, ThreadMode::Drop);
+        assert!(storage
+            .thread_mode("missing", user, ThreadMode::Root)
+            .await
+            .is_err());
 
         Ok(())
     }
-    #[sqlx::test(migrations = "./migrations")]
-    async fn test_is_valid(pool: Pool<Sqlite>) -> anyhow::Result<()> {
-        let (tag_name, _channel, user) = register_test(pool.clone()).await?;
+
+    async fn test_is_valid(storage: &dyn Storage) -> anyhow::Result<()> {
+        let (tag_name, _channel, user) = register_test(storage).await?;
         let not_auth_user = SlackUserId::new("U000".to_string());
 
-        let is_valid = is_valid_tag_for_user_with_pool(&user, &tag_name, pool.clone()).await?;
-        let _not_valid = is_valid_tag_for_user_with_pool(&not_auth_user, &tag_name, pool).await?;
+        let is_valid = storage.is_valid_tag_for_user(&user, &tag_name).await?;
+        let _not_valid = storage
+            .is_valid_tag_for_user(&not_auth_user, &tag_name)
+            .await?;
 
         assert!(is_valid);
         Ok(())
     }
+
+    async fn test_rename_tag(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U00001".to_string());
+
+        storage
+            .rename_tag("test_target", "renamed", user.clone())
+            .await?;
+        assert!(!storage.is_valid_tag_for_user(&user, "test_target").await?);
+        let renamed = tag_config(storage, "renamed", &user).await?;
+        assert_eq!(renamed.dists.len(), 1);
+
+        assert!(storage
+            .rename_tag("renamed", "test_a", user.clone())
+            .await
+            .is_err());
+        assert!(storage.rename_tag("missing", "other", user).await.is_err());
+
+        Ok(())
+    }
+
+    async fn test_copy_tag(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U00001".to_string());
+
+        storage
+            .copy_tag("test_target_bot", "copied", user.clone())
+            .await?;
+        let copied = tag_config(storage, "copied", &user).await?;
+        assert!(copied.bot);
+        // no dist channel sets the copy yet
+        assert!(copied.dists.is_empty());
+        assert_eq!(storage.repair_valid_count().await?, 0);
+        assert_eq!(copied.channels.len(), 1);
+        let filters = storage.filter_list("copied", user.clone()).await?;
+        assert_eq!(filters.len(), 1);
+
+        assert!(storage.copy_tag("test_a", "copied", user).await.is_err());
+
+        Ok(())
+    }
+
+    async fn test_merge_tag(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U00001".to_string());
+        storage
+            .share_tag("test_target_bot", user.clone(), "U00002", TagRole::Editor)
+            .await?;
+
+        storage
+            .merge_tag("test_target_bot", "test_target", user.clone())
+            .await?;
+        assert!(
+            !storage
+                .is_valid_tag_for_user(&user, "test_target_bot")
+                .await?
+        );
+
+        let merged = tag_config(storage, "test_target", &user).await?;
+        assert_eq!(merged.dists.len(), 2);
+        assert_eq!(storage.repair_valid_count().await?, 0);
+        // C02 was registered in both tags
+        assert_eq!(merged.channels.len(), 2);
+        // the filter and the share of test_target_bot move as well
+        let filters = storage.filter_list("test_target", user.clone()).await?;
+        assert_eq!(filters.len(), 1);
+        let shares = storage.tag_shares("test_target", &user).await?;
+        assert!(shares.iter().any(|share| share.member_id == "U00002"));
+
+        assert!(storage
+            .merge_tag("test_target", "test_target", user)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    async fn test_drop_tag(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U00001".to_string());
+        let dist = SlackChannelId::new("Cdist".to_string());
+
+        storage.drop_tag("test_target", user.clone()).await?;
+        assert!(!storage.is_valid_tag_for_user(&user, "test_target").await?);
+        assert!(storage.target_list(&dist).await?.is_empty());
+
+        assert!(storage.drop_tag("test_target", user).await.is_err());
+
+        Ok(())
+    }
+
+    storage_tests!(
+        test_register_channel,
+        test_unregister_channel,
+        test_retrieve_bot,
+        test_retrieve_file,
+        test_thread_mode,
+        test_is_valid,
+        test_rename_tag,
+        test_copy_tag,
+        test_merge_tag,
+        test_drop_tag,
+    );
 }
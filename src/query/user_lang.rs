@@ -0,0 +1,138 @@
+use std::{fmt, str::FromStr};
+
+use slack_morphism::SlackUserId;
+use sqlx::{Pool, Sqlite};
+
+// The language the bot replies to a user in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Lang {
+    #[default]
+    Ja,
+    En,
+}
+
+impl Lang {
+    // Slack locales look like `ja-JP`; languages without messages are left to the default
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        match locale.split(['-', '_']).next()? {
+            "ja" => Some(Lang::Ja),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ja" => Ok(Lang::Ja),
+            "en" => Ok(Lang::En),
+            _ => Err(anyhow::anyhow!("language should be ja or en")),
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lang = match self {
+            Lang::Ja => "ja",
+            Lang::En => "en",
+        };
+        write!(f, "{lang}")
+    }
+}
+
+// None forgets the language, to follow the Slack locale again
+pub async fn set_lang_with_pool(
+    user: &SlackUserId,
+    lang: Option<Lang>,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<()> {
+    let user_str = user.to_string();
+
+    match lang {
+        Some(lang) => {
+            let lang_str = lang.to_string();
+            let _query = sqlx::query!(
+                "INSERT INTO user_lang (user_id, lang) VALUES ($1, $2)
+                ON CONFLICT (user_id)
+                DO UPDATE SET lang = excluded.lang;",
+                user_str,
+                lang_str
+            )
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            let _query = sqlx::query!("DELETE FROM user_lang WHERE user_id = $1;", user_str)
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn lang_with_pool(
+    user: &SlackUserId,
+    pool: &Pool<Sqlite>,
+) -> anyhow::Result<Option<Lang>> {
+    let user_str = user.to_string();
+
+    let lang = sqlx::query!(
+        "
+    SELECT lang
+    FROM user_lang
+    WHERE user_id = $1
+    ",
+        user_str
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|r| r.lang.parse::<Lang>())
+    .transpose()?;
+
+    Ok(lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    #[test]
+    fn from_locale_test() {
+        assert_eq!(Lang::from_locale("ja-JP"), Some(Lang::Ja));
+        assert_eq!(Lang::from_locale("en-US"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("en_GB"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("fr-FR"), None);
+        assert_eq!(Lang::from_locale(""), None);
+    }
+
+    #[test]
+    fn from_str_test() {
+        for lang in [Lang::Ja, Lang::En] {
+            assert_eq!(lang.to_string().parse::<Lang>().unwrap(), lang);
+        }
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    async fn test_set_lang(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U0lang".to_string());
+        assert_eq!(storage.user_lang(&user).await?, None);
+
+        storage.set_user_lang(&user, Some(Lang::En)).await?;
+        storage.set_user_lang(&user, Some(Lang::Ja)).await?;
+        assert_eq!(storage.user_lang(&user).await?, Some(Lang::Ja));
+
+        storage.set_user_lang(&user, None).await?;
+        assert_eq!(storage.user_lang(&user).await?, None);
+
+        Ok(())
+    }
+
+    storage_tests!(test_set_lang);
+}
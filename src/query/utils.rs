@@ -1,6 +1,8 @@
 use slack_morphism::SlackUserId;
 use sqlx::{Pool, Sqlite};
 
+use crate::storage::TagError;
+
 pub async fn fetch_tag_id_with_pool(
     owner_id: SlackUserId,
     tag_name: &str,
@@ -17,8 +19,9 @@ pub async fn fetch_tag_id_with_pool(
         owner_id_str,
         tag_name
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?
+    .ok_or_else(|| TagError::NotFound(tag_name.to_string()))?
     .tag_id;
 
     Ok(tag_id)
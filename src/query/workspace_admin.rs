@@ -0,0 +1,65 @@
+use slack_morphism::SlackUserId;
+use sqlx::{Pool, Sqlite};
+
+pub async fn add_admin_with_pool(user: &SlackUserId, pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let user_str = user.to_string();
+
+    let _query = sqlx::query!(
+        "INSERT INTO workspace_admin (user_id) VALUES ($1)
+        ON CONFLICT (user_id)
+        DO NOTHING;",
+        user_str
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_admin_with_pool(user: &SlackUserId, pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let user_str = user.to_string();
+
+    let _query = sqlx::query!("DELETE FROM workspace_admin WHERE user_id = $1;", user_str)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn admin_list_with_pool(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<SlackUserId>> {
+    let admins = sqlx::query!(
+        "
+    SELECT user_id
+    FROM workspace_admin
+    "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| SlackUserId::new(r.user_id))
+    .collect::<Vec<_>>();
+
+    Ok(admins)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{testing::storage_tests, Storage};
+
+    use super::*;
+
+    async fn test_add_remove_admin(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U0admin".to_string());
+
+        storage.add_admin(&user).await?;
+        storage.add_admin(&user).await?;
+        assert_eq!(storage.admin_list().await?, vec![user.clone()]);
+
+        storage.remove_admin(&user).await?;
+        assert!(storage.admin_list().await?.is_empty());
+
+        Ok(())
+    }
+
+    storage_tests!(test_add_remove_admin);
+}
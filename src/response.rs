@@ -0,0 +1,359 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use slack_morphism::{
+    errors::SlackClientError,
+    prelude::{
+        SlackApiChatPostEphemeralRequest, SlackBlock, SlackBlockMarkDownText, SlackBlockText,
+        SlackHyperClient, SlackSectionBlock,
+    },
+    SlackApiTokenType, SlackChannelId, SlackMessageContent, SlackUserId,
+};
+
+use crate::{
+    command_parser::ParseError,
+    commands::{access::AccessError, modal::ModalError},
+    i18n::{self, Catalog},
+    post_message::MessagePoster,
+    query::tag_member::TagShare,
+    storage::{Storage, TagError},
+    utils,
+};
+
+// Slack rejects section texts longer than this
+const MAX_SECTION_TEXT: usize = 3000;
+
+// A reply rendered as Block Kit, with a plain text for notifications
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub text: String,
+    pub blocks: Vec<SlackBlock>,
+}
+
+impl Response {
+    pub async fn post_ephemeral(
+        self,
+        cli: Arc<SlackHyperClient>,
+        channel_id: SlackChannelId,
+        user_id: SlackUserId,
+    ) -> anyhow::Result<()> {
+        // the text is kept for notifications, and the blocks are shown instead of it
+        let content = SlackMessageContent::new()
+            .with_text(self.text)
+            .with_blocks(self.blocks);
+        let token = utils::get_token(&SlackApiTokenType::Bot)?;
+        let session = cli.open_session(&token);
+        let req = SlackApiChatPostEphemeralRequest::new(channel_id, user_id, content);
+        session
+            .chat_post_ephemeral(&req)
+            .await
+            .context("failed to post message.")?;
+        Ok(())
+    }
+}
+
+// Tag names are given by users, so the characters of mrkdwn are escaped
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Mentions and the like, in one line of a reply
+pub fn join(msg: &dyn Catalog, items: &[String]) -> String {
+    if items.is_empty() {
+        return msg.none().to_string();
+    }
+    items.join(", ")
+}
+
+// Tags, filters and rules are written by users, so they are escaped before being joined
+pub fn names(msg: &dyn Catalog, items: &[String]) -> String {
+    join(
+        msg,
+        &items.iter().map(|item| escape(item)).collect::<Vec<_>>(),
+    )
+}
+
+pub fn mentions(msg: &dyn Catalog, channels: &[SlackChannelId]) -> String {
+    join(
+        msg,
+        &channels
+            .iter()
+            .map(utils::channel_id_to_channel_name)
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn section(text: String) -> SlackBlock {
+    SlackBlock::Section(
+        SlackSectionBlock::new()
+            .with_text(SlackBlockText::MarkDown(SlackBlockMarkDownText::new(text))),
+    )
+}
+
+// A bold title with the count, then one item per line, split into sections that fit
+fn list_sections(msg: &dyn Catalog, title: &str, items: &[String]) -> Vec<SlackBlock> {
+    let mut texts = vec![format!("*{title}* ({})", items.len())];
+    if items.is_empty() {
+        texts[0].push('\n');
+        texts[0].push_str(msg.none());
+    }
+    for item in items {
+        let line = format!("• {item}");
+        let last = texts.last_mut().expect("texts start with the title");
+        if last.len() + line.len() + 1 > MAX_SECTION_TEXT {
+            texts.push(line);
+        } else {
+            last.push('\n');
+            last.push_str(&line);
+        }
+    }
+    texts.into_iter().map(section).collect()
+}
+
+pub fn tag_list(
+    msg: &dyn Catalog,
+    user_tags: &[String],
+    shared: &[TagShare],
+    public_tags: &[String],
+) -> Response {
+    let user_items = user_tags.iter().map(|tag| escape(tag)).collect::<Vec<_>>();
+    let shared_items = shared
+        .iter()
+        .map(|share| {
+            format!(
+                "{} (<@{}> {})",
+                escape(&share.tag_name),
+                share.owner_id,
+                share.role
+            )
+        })
+        .collect::<Vec<_>>();
+    let public_items = public_tags
+        .iter()
+        .map(|tag| escape(tag))
+        .collect::<Vec<_>>();
+
+    let mut blocks = list_sections(msg, msg.private_tags(), &user_items);
+    blocks.extend(list_sections(msg, msg.shared_tags(), &shared_items));
+    blocks.extend(list_sections(msg, msg.public_tags(), &public_items));
+    Response {
+        text: msg.tag_list_summary(user_items.len(), shared_items.len(), public_items.len()),
+        blocks,
+    }
+}
+
+pub fn channel_list(msg: &dyn Catalog, tag: &str, channels: &[SlackChannelId]) -> Response {
+    let items = channels
+        .iter()
+        .map(utils::channel_id_to_channel_name)
+        .collect::<Vec<_>>();
+    Response {
+        text: msg.channel_list_summary(tag, channels.len()),
+        blocks: list_sections(msg, &msg.channel_list_title(&escape(tag)), &items),
+    }
+}
+
+pub fn target_list(msg: &dyn Catalog, tags: &[String]) -> Response {
+    let items = tags.iter().map(|tag| escape(tag)).collect::<Vec<_>>();
+    Response {
+        text: msg.target_list_summary(tags.len()),
+        blocks: list_sections(msg, msg.target_list_title(), &items),
+    }
+}
+
+fn cause<E: std::error::Error + 'static>(err: &anyhow::Error) -> Option<&E> {
+    err.chain()
+        .find_map(<dyn std::error::Error>::downcast_ref::<E>)
+}
+
+fn is_caused_by<E: std::error::Error + 'static>(err: &anyhow::Error) -> bool {
+    cause::<E>(err).is_some()
+}
+
+// Errors of the database and of Slack say nothing to users, so only their kind is told;
+// the errors written for users, like a missing tag or a bad argument, are told in their language.
+// A missing tag is looked for first, as the queries add their own context to it.
+pub fn error_message(msg: &dyn Catalog, err: &anyhow::Error) -> String {
+    if let Some(parse_err) = err.downcast_ref::<ParseError>() {
+        msg.command_error(&msg.parse_error(parse_err))
+    } else if let Some(tag_err) = cause::<TagError>(err) {
+        msg.command_error(&msg.tag_error(tag_err))
+    } else if let Some(access_err) = err.downcast_ref::<AccessError>() {
+        msg.command_error(&msg.access_error(access_err))
+    } else if let Some(modal_err) = err.downcast_ref::<ModalError>() {
+        msg.command_error(&msg.modal_error(modal_err))
+    } else if is_caused_by::<sqlx::Error>(err) {
+        msg.database_error().to_string()
+    } else if is_caused_by::<SlackClientError>(err) {
+        msg.slack_error().to_string()
+    } else {
+        msg.command_error(&err.to_string())
+    }
+}
+
+// The whole chain is logged, and the user is told what went wrong
+pub async fn post_error(
+    cli: Arc<SlackHyperClient>,
+    storage: &dyn Storage,
+    channel_id: SlackChannelId,
+    user_id: SlackUserId,
+    err: &anyhow::Error,
+) -> anyhow::Result<()> {
+    println!("err:{err:?}");
+    let msg = i18n::catalog(&cli, storage, &user_id).await;
+    MessagePoster::new(channel_id, error_message(msg, err), cli)
+        .post_ephemeral(user_id)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command_parser::Command,
+        commands::access::OwnerAction,
+        query::{tag_member::TagRole, user_lang::Lang},
+    };
+
+    use super::*;
+
+    fn texts(blocks: &[SlackBlock]) -> Vec<String> {
+        blocks
+            .iter()
+            .map(|block| match block {
+                SlackBlock::Section(SlackSectionBlock {
+                    text: Some(SlackBlockText::MarkDown(md)),
+                    ..
+                }) => md.text.clone(),
+                _ => panic!("only mrkdwn sections are rendered"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tag_list_test() {
+        let shared = [TagShare {
+            tag_name: "news".to_string(),
+            owner_id: SlackUserId::new("U02".to_string()),
+            member_id: "U01".to_string(),
+            role: TagRole::Editor,
+        }];
+        let response = tag_list(
+            i18n::messages(Lang::Ja),
+            &["major".to_string(), "a<b".to_string()],
+            &shared,
+            &[],
+        );
+        assert_eq!(
+            texts(&response.blocks),
+            vec![
+                "*プライベートタグ* (2)\n• major\n• a&lt;b",
+                "*共有されたタグ* (1)\n• news (<@U02> editor)",
+                "*パブリックタグ* (0)\nなし",
+            ]
+        );
+        assert_eq!(
+            response.text,
+            "タグはプライベート 2 件、共有 1 件、パブリック 0 件です"
+        );
+    }
+
+    #[test]
+    fn channel_list_test() {
+        let channels = [
+            SlackChannelId::new("C01".to_string()),
+            SlackChannelId::new("C02".to_string()),
+        ];
+        let response = channel_list(i18n::messages(Lang::Ja), "major", &channels);
+        assert_eq!(
+            texts(&response.blocks),
+            vec!["*タグ major に登録されたチャンネル* (2)\n• <#C01>\n• <#C02>"]
+        );
+    }
+
+    #[test]
+    fn long_list_test() {
+        let tags = (0..1000).map(|i| format!("tag{i}")).collect::<Vec<_>>();
+        let texts = texts(&target_list(i18n::messages(Lang::En), &tags).blocks);
+        assert!(texts.len() > 1);
+        assert!(texts.iter().all(|text| text.len() <= MAX_SECTION_TEXT));
+        assert_eq!(
+            texts
+                .iter()
+                .map(|text| text.matches('•').count())
+                .sum::<usize>(),
+            1000
+        );
+    }
+
+    #[test]
+    fn join_test() {
+        let msg = i18n::messages(Lang::En);
+        assert_eq!(
+            names(msg, &["major".to_string(), "a<b".to_string()]),
+            "major, a&lt;b"
+        );
+        assert_eq!(names(msg, &[]), "None");
+        let channels = [
+            SlackChannelId::new("C01".to_string()),
+            SlackChannelId::new("C02".to_string()),
+        ];
+        assert_eq!(mentions(msg, &channels), "<#C01>, <#C02>");
+    }
+
+    #[test]
+    fn error_message_test() {
+        let (ja, en) = (i18n::messages(Lang::Ja), i18n::messages(Lang::En));
+        let err = anyhow::Error::from(TagError::NotFound("major".to_string()));
+        assert_eq!(
+            error_message(ja, &err),
+            "コマンドを実行できませんでした。\nタグ major は存在しません"
+        );
+        assert_eq!(
+            error_message(en, &err),
+            "Could not run the command.\nthe tag major does not exist"
+        );
+
+        // refused commands are told in the user's language as well
+        let err = anyhow::Error::from(AccessError::Viewer("major".to_string()));
+        assert_eq!(
+            error_message(ja, &err),
+            "コマンドを実行できませんでした。\nタグ major は閲覧者として共有されているため、編集できません"
+        );
+        assert_eq!(
+            error_message(en, &err),
+            "Could not run the command.\nthe tag major is shared with you as a viewer, and cannot be edited"
+        );
+        let err = anyhow::Error::from(AccessError::OwnerOnly(OwnerAction::Merge));
+        assert_eq!(
+            error_message(ja, &err),
+            "コマンドを実行できませんでした。\nタグの統合ができるのはタグの所有者のみです"
+        );
+        assert_eq!(
+            error_message(en, &err),
+            "Could not run the command.\nonly the owner of the tag can merge it"
+        );
+        let err = anyhow::Error::from(ModalError::Empty);
+        assert_eq!(
+            error_message(ja, &err),
+            "コマンドを実行できませんでした。\n変更する内容がありません"
+        );
+
+        // the details of internal errors are not shown, even under a context
+        let err = Err::<(), _>(sqlx::Error::RowNotFound)
+            .context("failed to list tags")
+            .unwrap_err();
+        assert!(!error_message(ja, &err).contains("failed to list tags"));
+        assert!(error_message(ja, &err).starts_with("データベース"));
+
+        // the mistakes in a command line are told in the user's language
+        let err = anyhow::Error::from(Command::parse("add major").unwrap_err());
+        assert!(error_message(ja, &err).contains("#channel が指定されていません"));
+        assert!(error_message(en, &err).contains("#channel is missing"));
+        let err = anyhow::Error::from(Command::parse("thread major all").unwrap_err());
+        assert!(error_message(ja, &err).contains("mirror、drop、root のいずれか"));
+        assert!(error_message(en, &err).contains("it should be mirror, drop or root"));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use slack_morphism::SlackChannelId;
+use tokio::sync::RwLock;
+
+use crate::{
+    query::{delivery::DeliverySchedule, dist_target_map::TagRoute},
+    storage::Storage,
+};
+
+type RouteMap = HashMap<SlackChannelId, Vec<TagRoute>>;
+type ScheduleMap = HashMap<SlackChannelId, DeliverySchedule>;
+
+// Other replicas sharing a PostgreSQL database change the routes without invalidating this cache,
+// so the routes are reloaded at least this often
+const ROUTES_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct LoadedRoutes {
+    routes: Arc<RouteMap>,
+    // the delivery schedules of dist channels, which tell whether a message is posted now or later
+    schedules: Arc<ScheduleMap>,
+    loaded_at: Instant,
+}
+
+// Routes of every registered channel kept in memory, with the delivery schedules,
+// so that messages from channels nobody collects are rejected without touching the database
+#[derive(Debug, Clone)]
+pub struct RoutingCache {
+    // None until loaded, and again after an invalidation
+    routes: Arc<RwLock<Option<LoadedRoutes>>>,
+    ttl: Duration,
+}
+
+impl Default for RoutingCache {
+    fn default() -> Self {
+        Self::new(ROUTES_TTL)
+    }
+}
+
+impl RoutingCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            routes: Arc::default(),
+            ttl,
+        }
+    }
+
+    fn fresh(&self, loaded: Option<&LoadedRoutes>) -> Option<LoadedRoutes> {
+        loaded
+            .filter(|loaded| loaded.loaded_at.elapsed() < self.ttl)
+            .cloned()
+    }
+
+    async fn load(&self, storage: &dyn Storage) -> anyhow::Result<LoadedRoutes> {
+        // the write lock is held while loading, so an invalidation cannot be overwritten by stale routes
+        let mut routes = self.routes.write().await;
+        if let Some(loaded) = self.fresh(routes.as_ref()) {
+            return Ok(loaded);
+        }
+        let loaded = LoadedRoutes {
+            routes: Arc::new(storage.route_map().await?),
+            schedules: Arc::new(storage.schedule_list().await?),
+            loaded_at: Instant::now(),
+        };
+        *routes = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    async fn loaded(&self, storage: &dyn Storage) -> anyhow::Result<LoadedRoutes> {
+        let cached = self.fresh(self.routes.read().await.as_ref());
+        match cached {
+            Some(loaded) => Ok(loaded),
+            None => self.load(storage).await,
+        }
+    }
+
+    // Load the routes ahead of the first message
+    pub async fn warm_up(&self, storage: &dyn Storage) -> anyhow::Result<()> {
+        self.load(storage).await?;
+        Ok(())
+    }
+
+    pub async fn channel_routes(
+        &self,
+        channel: &SlackChannelId,
+        storage: &dyn Storage,
+    ) -> anyhow::Result<Vec<TagRoute>> {
+        let routes = self.loaded(storage).await?.routes;
+        Ok(routes.get(channel).cloned().unwrap_or_default())
+    }
+
+    pub async fn schedules(&self, storage: &dyn Storage) -> anyhow::Result<Arc<ScheduleMap>> {
+        Ok(self.loaded(storage).await?.schedules)
+    }
+
+    // Drop the routes and the schedules, to be reloaded on the next message
+    pub async fn invalidate(&self) {
+        *self.routes.write().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slack_morphism::SlackUserId;
+
+    use crate::{query::delivery::DeliveryMode, storage::testing::storage_tests};
+
+    use super::*;
+
+    async fn test_invalidate(storage: &dyn Storage) -> anyhow::Result<()> {
+        let cache = RoutingCache::default();
+        let channel = SlackChannelId::new("Cnew".to_string());
+        let owner_id = SlackUserId::new("U00001".to_string());
+
+        assert!(cache.channel_routes(&channel, storage).await?.is_empty());
+
+        storage
+            .register_channel("test_target", channel.clone(), owner_id)
+            .await?;
+        assert!(cache.channel_routes(&channel, storage).await?.is_empty());
+
+        cache.invalidate().await;
+        let routes = cache.channel_routes(&channel, storage).await?;
+        assert!(routes.iter().any(|route| route
+            .dists
+            .contains(&SlackChannelId::new("Cdist".to_string()))));
+
+        Ok(())
+    }
+
+    async fn test_ttl(storage: &dyn Storage) -> anyhow::Result<()> {
+        // routes older than the ttl are reloaded without an invalidation, as another replica would see them
+        let cache = RoutingCache::new(Duration::ZERO);
+        let channel = SlackChannelId::new("Cnew".to_string());
+        let owner_id = SlackUserId::new("U00001".to_string());
+
+        assert!(cache.channel_routes(&channel, storage).await?.is_empty());
+        storage
+            .register_channel("test_target", channel.clone(), owner_id)
+            .await?;
+        assert!(!cache.channel_routes(&channel, storage).await?.is_empty());
+
+        Ok(())
+    }
+
+    async fn test_schedules(storage: &dyn Storage) -> anyhow::Result<()> {
+        let cache = RoutingCache::default();
+        let dist = SlackChannelId::new("Cdist".to_string());
+        let user = SlackUserId::new("U00001".to_string());
+
+        assert!(!cache.schedules(storage).await?.contains_key(&dist));
+        storage
+            .set_delivery_mode(&dist, &user, DeliveryMode::Interval(30), None)
+            .await?;
+        assert!(!cache.schedules(storage).await?.contains_key(&dist));
+
+        cache.invalidate().await;
+        let schedules = cache.schedules(storage).await?;
+        assert!(schedules
+            .get(&dist)
+            .is_some_and(DeliverySchedule::is_digest));
+
+        Ok(())
+    }
+
+    storage_tests!(test_invalidate, test_ttl, test_schedules);
+}
@@ -0,0 +1,21 @@
+use anyhow::Context;
+use slack_morphism::prelude::SlackClientEventsUserState;
+use std::sync::Arc;
+
+use crate::{routing_cache::RoutingCache, storage::Storage};
+
+// Resources created at startup and shared with every handler through the listener state
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub storage: Arc<dyn Storage>,
+    pub routing: RoutingCache,
+}
+
+pub async fn app_state(state: &SlackClientEventsUserState) -> anyhow::Result<AppState> {
+    let storage = state.read().await;
+    let app_state = storage
+        .get_user_state::<AppState>()
+        .context("app state is missing.")?;
+
+    Ok(app_state.clone())
+}
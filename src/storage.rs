@@ -0,0 +1,566 @@
+pub mod postgres;
+pub mod sqlite;
+#[cfg(test)]
+pub mod testing;
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use slack_morphism::{SlackChannelId, SlackTs, SlackUserId};
+use sqlx::{PgPool, SqlitePool};
+
+use crate::query::{
+    audit_log::{AuditEntry, AuditQuery},
+    delivery::{DeliveryMode, DeliverySchedule, TimeZone},
+    delivery_queue::QueuedMessage,
+    dist_target_map::TagRoute,
+    sender_rule::SenderRule,
+    tag_config::TagConfig,
+    tag_filter::TagFilter,
+    tag_member::{TagRole, TagShare},
+    thread_mode::ThreadMode,
+    user_lang::Lang,
+};
+
+use self::{postgres::PostgresStorage, sqlite::SqliteStorage};
+
+// Everything the bot persists, backed by sqlite or by a PostgreSQL shared among replicas
+#[async_trait]
+pub trait Storage: Send + Sync + std::fmt::Debug {
+    // tags and their channel lists
+    async fn register_channel(
+        &self,
+        tag_name: &str,
+        channel: SlackChannelId,
+        user: SlackUserId,
+    ) -> anyhow::Result<()>;
+    async fn unregister_channel(
+        &self,
+        tag_name: &str,
+        channel: SlackChannelId,
+        user: SlackUserId,
+    ) -> anyhow::Result<()>;
+    // drop_tag and merge_tag return the id of the deleted tag, which its audit entry keeps
+    async fn drop_tag(&self, tag_name: &str, user: SlackUserId) -> anyhow::Result<i64>;
+    async fn retrieve_bot(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        retrieve_bot: bool,
+    ) -> anyhow::Result<()>;
+    async fn retrieve_file(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        retrieve_file: bool,
+    ) -> anyhow::Result<()>;
+    async fn thread_mode(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        thread_mode: ThreadMode,
+    ) -> anyhow::Result<()>;
+    async fn rename_tag(
+        &self,
+        tag_name: &str,
+        new_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()>;
+    async fn copy_tag(
+        &self,
+        tag_name: &str,
+        new_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()>;
+    async fn merge_tag(&self, tag_name: &str, into: &str, user: SlackUserId)
+        -> anyhow::Result<i64>;
+    async fn is_valid_tag_for_user(
+        &self,
+        user: &SlackUserId,
+        tag_name: &str,
+    ) -> anyhow::Result<bool>;
+    async fn tag_list_user(&self, owner_id: SlackUserId) -> anyhow::Result<Vec<String>>;
+    async fn tag_list_public(&self) -> anyhow::Result<Vec<String>>;
+    async fn channel_list(
+        &self,
+        tag: &str,
+        owner_id: SlackUserId,
+    ) -> anyhow::Result<Vec<SlackChannelId>>;
+    // every tag with its channels and dist channels, for backups
+    async fn tag_config_list(&self) -> anyhow::Result<Vec<TagConfig>>;
+
+    // members tags are shared with
+    async fn share_tag(
+        &self,
+        tag_name: &str,
+        owner: SlackUserId,
+        member_id: &str,
+        role: TagRole,
+    ) -> anyhow::Result<()>;
+    async fn unshare_tag(
+        &self,
+        tag_name: &str,
+        owner: SlackUserId,
+        member_id: &str,
+    ) -> anyhow::Result<()>;
+    async fn tag_shares(
+        &self,
+        tag_name: &str,
+        owner: &SlackUserId,
+    ) -> anyhow::Result<Vec<TagShare>>;
+    // the shares with any of the members, which are a user and the user groups the user is in
+    async fn member_shares(&self, member_ids: &[String]) -> anyhow::Result<Vec<TagShare>>;
+
+    // workspace admins, besides the ones given by SLACK_ADMIN_IDS
+    async fn add_admin(&self, user: &SlackUserId) -> anyhow::Result<()>;
+    async fn remove_admin(&self, user: &SlackUserId) -> anyhow::Result<()>;
+    async fn admin_list(&self) -> anyhow::Result<Vec<SlackUserId>>;
+
+    // the language users chose with the lang command; None follows their Slack locale
+    async fn user_lang(&self, user: &SlackUserId) -> anyhow::Result<Option<Lang>>;
+    async fn set_user_lang(&self, user: &SlackUserId, lang: Option<Lang>) -> anyhow::Result<()>;
+
+    // the log of changes to tags and distributions
+    async fn record_audit(&self, entry: &AuditEntry) -> anyhow::Result<()>;
+    async fn audit_list(&self, query: &AuditQuery) -> anyhow::Result<Vec<AuditEntry>>;
+
+    // filters and sender rules of tags
+    async fn add_filter(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        filter: &TagFilter,
+    ) -> anyhow::Result<()>;
+    async fn remove_filter(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        filter: &TagFilter,
+    ) -> anyhow::Result<()>;
+    async fn filter_list(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<Vec<TagFilter>>;
+    async fn add_rule(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        rule: &SenderRule,
+    ) -> anyhow::Result<()>;
+    async fn remove_rule(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        rule: &SenderRule,
+    ) -> anyhow::Result<()>;
+    async fn rule_list(&self, tag_name: &str, user: SlackUserId)
+        -> anyhow::Result<Vec<SenderRule>>;
+
+    // distributions
+    async fn add_tag(
+        &self,
+        dist: SlackChannelId,
+        user: SlackUserId,
+        tag: &str,
+    ) -> anyhow::Result<()>;
+    async fn remove_tag(
+        &self,
+        dist: SlackChannelId,
+        user: SlackUserId,
+        tag: &str,
+    ) -> anyhow::Result<()>;
+    async fn target_list(&self, dist: &SlackChannelId) -> anyhow::Result<Vec<String>>;
+    // count valid_count again where it drifted from dist, returning how many tags were fixed
+    async fn repair_valid_count(&self) -> anyhow::Result<u64>;
+    async fn route_map(&self) -> anyhow::Result<HashMap<SlackChannelId, Vec<TagRoute>>>;
+
+    // delivery schedules and the queue of held messages
+    async fn set_delivery_mode(
+        &self,
+        dist: &SlackChannelId,
+        user: &SlackUserId,
+        mode: DeliveryMode,
+        time_zone: Option<&TimeZone>,
+    ) -> anyhow::Result<()>;
+    // the user who set the delivery mode last, whose language the digest is written in
+    async fn delivery_set_by(&self, dist: &SlackChannelId) -> anyhow::Result<Option<SlackUserId>>;
+    async fn set_quiet_hours(
+        &self,
+        dist: &SlackChannelId,
+        quiet_hours: Option<(i64, i64)>,
+        time_zone: Option<&TimeZone>,
+    ) -> anyhow::Result<()>;
+    async fn schedule_list(&self) -> anyhow::Result<HashMap<SlackChannelId, DeliverySchedule>>;
+    async fn mark_delivered(&self, dist: &SlackChannelId, delivered_at: i64) -> anyhow::Result<()>;
+    async fn enqueue(&self, message: &QueuedMessage) -> anyhow::Result<()>;
+    async fn pending_dists(&self) -> anyhow::Result<Vec<SlackChannelId>>;
+    // replicas share the queue, so each delivers only the messages it claimed
+    async fn claim_queued(
+        &self,
+        dist: &SlackChannelId,
+        now: i64,
+        claimed_until: i64,
+    ) -> anyhow::Result<Vec<QueuedMessage>>;
+    async fn remove_delivered(&self, queue_ids: &[i64]) -> anyhow::Result<()>;
+    async fn remove_source(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+    ) -> anyhow::Result<()>;
+    async fn update_source(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        text: &str,
+    ) -> anyhow::Result<()>;
+
+    // forwarded copies of messages
+    async fn register_forwarded(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        dist: &SlackChannelId,
+        forwarded_ts: &SlackTs,
+    ) -> anyhow::Result<()>;
+    async fn forwarded_list(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+    ) -> anyhow::Result<Vec<(SlackChannelId, SlackTs)>>;
+    async fn unregister_forwarded(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        dist: &SlackChannelId,
+    ) -> anyhow::Result<()>;
+}
+
+// Why a tag given by a user could not be used. These are shown to users, so they are
+// translated through the catalog, and the English here is what is logged.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TagError {
+    #[error("the tag {0} does not exist")]
+    NotFound(String),
+    #[error("the tag {0} already exists")]
+    AlreadyExists(String),
+    #[error("a tag cannot be merged into itself")]
+    MergeIntoItself,
+}
+
+// What the migrations up to 7_quiet_hours.sql add, as (version, table, column).
+// Databases created before migrations were versioned have no migration history, so the migrations
+// whose table or column already exists are recorded as applied, and the rest are run.
+const LEGACY_MIGRATIONS: [(i64, &str, Option<&str>); 8] = [
+    (0, "user_folder", None),
+    (1, "forwarded_message", None),
+    (2, "user_folder", Some("thread_mode")),
+    (3, "user_folder", Some("file")),
+    (4, "tag_filter", None),
+    (5, "sender_rule", None),
+    (6, "dist_schedule", None),
+    (7, "dist_schedule", Some("quiet_start")),
+];
+
+// Choose the backend by the scheme of DATABASE_URL, and bring its schema up to date.
+// valid_count left drifted by older versions is repaired as well.
+pub async fn connect(db_url: &str) -> anyhow::Result<Arc<dyn Storage>> {
+    let scheme = db_url.split_once(':').map(|(scheme, _)| scheme);
+    let storage: Arc<dyn Storage> = match scheme {
+        Some("sqlite") => {
+            let storage = SqliteStorage::new(SqlitePool::connect(db_url).await?);
+            storage.migrate().await?;
+            Arc::new(storage)
+        }
+        Some("postgres" | "postgresql") => {
+            let storage = PostgresStorage::new(PgPool::connect(db_url).await?);
+            storage.migrate().await?;
+            Arc::new(storage)
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "DATABASE_URL should start with sqlite: or postgres:"
+            ))
+        }
+    };
+    storage.repair_valid_count().await?;
+    Ok(storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use slack_morphism::SlackMessageSender;
+
+    use crate::{
+        i18n,
+        query::{
+            audit_log::AuditAction,
+            dist_target_map::{self, MessageSummary},
+            tag_filter::FilterKind,
+            thread_mode::ThreadPosition,
+        },
+        response,
+    };
+
+    use super::{testing::storage_tests, *};
+
+    // The same checks run against every backend, on the seed fixture
+    async fn test_storage(storage: &dyn Storage) -> anyhow::Result<()> {
+        tags_suite(storage).await?;
+        dists_suite(storage).await?;
+        routes_suite(storage).await?;
+        delivery_suite(storage).await?;
+        forwarded_suite(storage).await?;
+        missing_tag_suite(storage).await?;
+        Ok(())
+    }
+
+    async fn tags_suite(storage: &dyn Storage) -> anyhow::Result<()> {
+        let owner_id = SlackUserId::new("U00001".to_string());
+        let user_tags = storage.tag_list_user(owner_id.clone()).await?;
+        assert!(user_tags.contains(&"test_a".to_string()));
+        assert!(storage
+            .tag_list_public()
+            .await?
+            .contains(&"test_pub".to_string()));
+
+        let user = SlackUserId::new("U0987".to_string());
+        let channel = SlackChannelId::new("C01234".to_string());
+        storage
+            .register_channel("test", channel.clone(), user.clone())
+            .await?;
+        assert!(storage.is_valid_tag_for_user(&user, "test").await?);
+        assert_eq!(
+            storage.channel_list("test", user.clone()).await?,
+            vec![channel.clone()]
+        );
+        storage.copy_tag("test", "test_copy", user.clone()).await?;
+        storage.merge_tag("test_copy", "test", user.clone()).await?;
+        storage
+            .rename_tag("test", "test_renamed", user.clone())
+            .await?;
+        storage
+            .rename_tag("test_renamed", "test", user.clone())
+            .await?;
+        assert!(!storage.is_valid_tag_for_user(&user, "test_copy").await?);
+        let configs = storage.tag_config_list().await?;
+        assert!(configs
+            .iter()
+            .any(|config| config.tag_name == "test" && config.channels == vec![channel.clone()]));
+
+        let filter = TagFilter::parse(FilterKind::Exclude, "deploy")?;
+        storage.add_filter("test", user.clone(), &filter).await?;
+        assert_eq!(
+            storage.filter_list("test", user.clone()).await?,
+            vec![filter.clone()]
+        );
+        storage.remove_filter("test", user.clone(), &filter).await?;
+        assert!(storage.filter_list("test", user.clone()).await?.is_empty());
+
+        storage
+            .share_tag("test", user.clone(), "U0share", TagRole::Viewer)
+            .await?;
+        let shares = storage.tag_shares("test", &user).await?;
+        assert!(shares
+            .iter()
+            .any(|share| share.member_id == "U0share" && share.role == TagRole::Viewer));
+        storage.unshare_tag("test", user.clone(), "U0share").await?;
+        assert!(storage.tag_shares("test", &user).await?.is_empty());
+
+        storage
+            .unregister_channel("test", channel.clone(), user.clone())
+            .await?;
+        assert!(storage.channel_list("test", user.clone()).await?.is_empty());
+        assert!(storage.is_valid_tag_for_user(&user, "test").await?);
+        let entry = AuditEntry::new(user.clone(), AuditAction::Add)
+            .with_tag("test", user.clone())
+            .with_channels(vec![SlackChannelId::new("C01234".to_string())]);
+        storage.record_audit(&entry).await?;
+        let query = AuditQuery {
+            tag: Some(("test".to_string(), user.clone())),
+            limit: 10,
+            ..Default::default()
+        };
+        let entries = storage.audit_list(&query).await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].channels, entry.channels);
+        storage.drop_tag("test", user.clone()).await?;
+        assert!(!storage.is_valid_tag_for_user(&user, "test").await?);
+
+        storage.add_admin(&user).await?;
+        assert_eq!(storage.admin_list().await?, vec![user.clone()]);
+        storage.remove_admin(&user).await?;
+        assert!(storage.admin_list().await?.is_empty());
+
+        assert_eq!(storage.user_lang(&user).await?, None);
+        storage.set_user_lang(&user, Some(Lang::En)).await?;
+        storage.set_user_lang(&user, Some(Lang::Ja)).await?;
+        assert_eq!(storage.user_lang(&user).await?, Some(Lang::Ja));
+        storage.set_user_lang(&user, None).await?;
+        assert_eq!(storage.user_lang(&user).await?, None);
+
+        Ok(())
+    }
+
+    async fn dists_suite(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U0987654".to_string());
+        let dist = SlackChannelId::new("C012345dist".to_string());
+
+        storage
+            .add_tag(dist.clone(), user.clone(), "test_dist")
+            .await?;
+        assert_eq!(
+            storage.target_list(&dist).await?,
+            vec!["test_dist".to_string()]
+        );
+        let other = SlackChannelId::new("C012345other".to_string());
+        storage
+            .add_tag(other.clone(), user.clone(), "test_dist")
+            .await?;
+        storage
+            .remove_tag(dist.clone(), user.clone(), "test_dist")
+            .await?;
+        assert!(storage.target_list(&dist).await?.is_empty());
+        assert_eq!(
+            storage.target_list(&other).await?,
+            vec!["test_dist".to_string()]
+        );
+        storage.remove_tag(other.clone(), user, "test_dist").await?;
+        assert_eq!(storage.repair_valid_count().await?, 0);
+
+        Ok(())
+    }
+
+    async fn routes_suite(storage: &dyn Storage) -> anyhow::Result<()> {
+        let routes = storage.route_map().await?;
+        let sender = SlackMessageSender::new().with_user(SlackUserId::new("Uanybody".to_string()));
+        let summary = MessageSummary {
+            position: ThreadPosition::Root,
+            has_files: false,
+            text: "test message".to_string(),
+        };
+
+        let c01 = routes
+            .get(&SlackChannelId::new("C01".to_string()))
+            .cloned()
+            .unwrap_or_default();
+        let dists = dist_target_map::target_to_dists(&c01, &sender, &summary);
+        assert!(dists.contains(&SlackChannelId::new("Cdist".to_string())));
+        assert!(!routes.contains_key(&SlackChannelId::new("Cnot".to_string())));
+
+        Ok(())
+    }
+
+    async fn delivery_suite(storage: &dyn Storage) -> anyhow::Result<()> {
+        let dist = SlackChannelId::new("Cdigest".to_string());
+        let user = SlackUserId::new("U00001".to_string());
+        storage
+            .set_delivery_mode(
+                &dist,
+                &user,
+                DeliveryMode::Interval(30),
+                Some(&TimeZone::Named("Asia/Tokyo".to_string())),
+            )
+            .await?;
+        storage
+            .set_quiet_hours(&dist, Some((1320, 420)), None)
+            .await?;
+        let schedule = storage
+            .schedule_list()
+            .await?
+            .get(&dist)
+            .cloned()
+            .unwrap_or_default();
+        assert_eq!(schedule.mode, DeliveryMode::Interval(30));
+        assert_eq!(
+            schedule.time_zone,
+            TimeZone::Named("Asia/Tokyo".to_string())
+        );
+        assert_eq!(schedule.quiet_hours, Some((1320, 420)));
+
+        let message = QueuedMessage {
+            queue_id: 0,
+            dist: dist.clone(),
+            source_channel: SlackChannelId::new("C01".to_string()),
+            source_ts: SlackTs::new("1686700000.000100".to_string()),
+            sender_name: "someone".to_string(),
+            icon_url: "https://example.com/icon.png".to_string(),
+            text: "held".to_string(),
+            queued_at: 12345,
+        };
+        storage.enqueue(&message).await?;
+        assert_eq!(storage.pending_dists().await?, vec![dist.clone()]);
+        let queued = storage.claim_queued(&dist, 100, 200).await?;
+        assert_eq!(queued.len(), 1);
+        storage.remove_delivered(&[queued[0].queue_id]).await?;
+        assert!(storage.pending_dists().await?.is_empty());
+
+        Ok(())
+    }
+
+    async fn forwarded_suite(storage: &dyn Storage) -> anyhow::Result<()> {
+        let source_channel = SlackChannelId::new("C01".to_string());
+        let source_ts = SlackTs::new("1686700000.000100".to_string());
+        let dist = SlackChannelId::new("Cdist".to_string());
+        let forwarded_ts = SlackTs::new("1686700001.000200".to_string());
+
+        storage
+            .register_forwarded(&source_channel, &source_ts, &dist, &forwarded_ts)
+            .await?;
+        assert_eq!(
+            storage.forwarded_list(&source_channel, &source_ts).await?,
+            vec![(dist.clone(), forwarded_ts)]
+        );
+        storage
+            .unregister_forwarded(&source_channel, &source_ts, &dist)
+            .await?;
+        assert!(storage
+            .forwarded_list(&source_channel, &source_ts)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    // A mistyped tag is told to the user as such, and not as a failure of the database
+    async fn missing_tag_suite(storage: &dyn Storage) -> anyhow::Result<()> {
+        let user = SlackUserId::new("U0missing".to_string());
+        let channel = SlackChannelId::new("C01234".to_string());
+        let filter = TagFilter::parse(FilterKind::Include, "deploy")?;
+        let results = [
+            storage
+                .unregister_channel("nope", channel.clone(), user.clone())
+                .await,
+            storage.drop_tag("nope", user.clone()).await.map(drop),
+            storage.retrieve_bot("nope", user.clone(), true).await,
+            storage.copy_tag("nope", "nope_copy", user.clone()).await,
+            storage
+                .merge_tag("nope", "test_a", user.clone())
+                .await
+                .map(drop),
+            storage.add_filter("nope", user.clone(), &filter).await,
+            storage
+                .share_tag("nope", user.clone(), "U0share", TagRole::Editor)
+                .await,
+            storage.add_tag(channel.clone(), user.clone(), "nope").await,
+            storage.remove_tag(channel, user, "nope").await,
+        ];
+
+        let (ja, en) = (i18n::messages(Lang::Ja), i18n::messages(Lang::En));
+        for result in results {
+            let err = result.expect_err("the tag nope does not exist");
+            assert_eq!(
+                response::error_message(en, &err),
+                "Could not run the command.\nthe tag nope does not exist"
+            );
+            assert_eq!(
+                response::error_message(ja, &err),
+                "コマンドを実行できませんでした。\nタグ nope は存在しません"
+            );
+        }
+
+        Ok(())
+    }
+
+    storage_tests!(test_storage);
+}
@@ -0,0 +1,1258 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use slack_morphism::{SlackChannelId, SlackTs, SlackUserId};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool, Postgres, Transaction,
+};
+
+use crate::query::{
+    audit_log::{self, AuditAction, AuditEntry, AuditQuery},
+    delivery::{DeliveryMode, DeliverySchedule, TimeZone},
+    delivery_queue::QueuedMessage,
+    dist_target_map::{self, RouteRecord, TagRoute},
+    sender_rule::{RuleKind, SenderRule},
+    tag_config::{self, TagConfig, TagRecord},
+    tag_filter::{FilterKind, TagFilter},
+    tag_member::{TagRole, TagShare},
+    thread_mode::ThreadMode,
+    user_lang::Lang,
+};
+
+use super::{Storage, TagError, LEGACY_MIGRATIONS};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations_postgres");
+
+// The PostgreSQL backend, for several replicas sharing one database.
+// Queries are checked at runtime, since `sqlx::query!` is checked against the sqlite schema.
+#[derive(Debug, Clone)]
+pub struct PostgresStorage {
+    pool: PgPool,
+}
+
+impl PostgresStorage {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        // databases set up by hand from migrations_postgres have no migration history either
+        let is_legacy = sqlx::query_scalar::<_, bool>(
+            "SELECT to_regclass('user_folder') IS NOT NULL AND to_regclass('_sqlx_migrations') IS NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if is_legacy {
+            let mut conn = self.pool.acquire().await?;
+            conn.ensure_migrations_table().await?;
+            for (version, table, column) in LEGACY_MIGRATIONS {
+                let Some(migration) = MIGRATOR
+                    .iter()
+                    .find(|migration| migration.version == version)
+                else {
+                    continue;
+                };
+                if !self.has_schema(table, column).await? {
+                    continue;
+                }
+                sqlx::query(
+                    "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+                    VALUES ($1, $2, true, $3, 0)",
+                )
+                .bind(migration.version)
+                .bind(&*migration.description)
+                .bind(&*migration.checksum)
+                .execute(&mut conn)
+                .await?;
+            }
+        }
+
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    // Whether the table, or the column of it, exists
+    async fn has_schema(&self, table: &str, column: Option<&str>) -> anyhow::Result<bool> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM information_schema.columns
+            WHERE table_schema = current_schema() AND table_name = $1
+            AND ($2::TEXT IS NULL OR column_name = $2)",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+
+    async fn fetch_tag_id(&self, owner_id: &SlackUserId, tag_name: &str) -> anyhow::Result<i64> {
+        let tag_id = sqlx::query_scalar::<_, i64>(
+            "SELECT tag_id FROM user_folder WHERE owner_id = $1 AND tag_name = $2",
+        )
+        .bind(owner_id.to_string())
+        .bind(tag_name)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| TagError::NotFound(tag_name.to_string()))?;
+
+        Ok(tag_id)
+    }
+
+    // Replicas changing the dist channels of the same tag wait for each other,
+    // so that the count below sees the rows the other one committed
+    async fn lock_tag(tx: &mut Transaction<'_, Postgres>, tag_id: i64) -> anyhow::Result<()> {
+        sqlx::query("SELECT tag_id FROM user_folder WHERE tag_id = $1 FOR UPDATE")
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+        Ok(())
+    }
+
+    async fn count_dists(tx: &mut Transaction<'_, Postgres>, tag_id: i64) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE user_folder
+            SET valid_count = (SELECT COUNT(*) FROM dist WHERE tag_id = $1)
+            WHERE tag_id = $1",
+        )
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn update_tag_column<T>(
+        &self,
+        sql: &str,
+        value: T,
+        tag_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()>
+    where
+        T: for<'q> sqlx::Encode<'q, sqlx::Postgres> + sqlx::Type<sqlx::Postgres> + Send,
+    {
+        let result = sqlx::query(sql)
+            .bind(value)
+            .bind(tag_name)
+            .bind(user.to_string())
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(TagError::NotFound(tag_name.to_string()).into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn register_channel(
+        &self,
+        tag_name: &str,
+        channel: SlackChannelId,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO user_folder (tag_name, owner_id) VALUES ($1, $2)
+            ON CONFLICT (tag_name, owner_id)
+            DO NOTHING",
+        )
+        .bind(tag_name)
+        .bind(user.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        let tag_id = self.fetch_tag_id(&user, tag_name).await?;
+
+        sqlx::query(
+            "INSERT INTO channel_list (channel_id, tag_id) VALUES ($1, $2)
+            ON CONFLICT (channel_id, tag_id)
+            DO NOTHING",
+        )
+        .bind(channel.to_string())
+        .bind(tag_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn unregister_channel(
+        &self,
+        tag_name: &str,
+        channel: SlackChannelId,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query("DELETE FROM channel_list WHERE tag_id = $1 AND channel_id = $2")
+            .bind(tag_id)
+            .bind(channel.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn drop_tag(&self, tag_name: &str, user: SlackUserId) -> anyhow::Result<i64> {
+        let tag_id = sqlx::query_scalar::<_, i64>(
+            "DELETE FROM user_folder WHERE tag_name = $1 AND owner_id = $2 RETURNING tag_id",
+        )
+        .bind(tag_name)
+        .bind(user.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| TagError::NotFound(tag_name.to_string()))?;
+        Ok(tag_id)
+    }
+
+    async fn retrieve_bot(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        retrieve_bot: bool,
+    ) -> anyhow::Result<()> {
+        self.update_tag_column(
+            "UPDATE user_folder SET bot = $1 WHERE tag_name = $2 AND owner_id = $3",
+            retrieve_bot,
+            tag_name,
+            user,
+        )
+        .await
+    }
+
+    async fn retrieve_file(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        retrieve_file: bool,
+    ) -> anyhow::Result<()> {
+        self.update_tag_column(
+            "UPDATE user_folder SET file = $1 WHERE tag_name = $2 AND owner_id = $3",
+            retrieve_file,
+            tag_name,
+            user,
+        )
+        .await
+    }
+
+    async fn thread_mode(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        thread_mode: ThreadMode,
+    ) -> anyhow::Result<()> {
+        self.update_tag_column(
+            "UPDATE user_folder SET thread_mode = $1 WHERE tag_name = $2 AND owner_id = $3",
+            thread_mode.to_string(),
+            tag_name,
+            user,
+        )
+        .await
+    }
+
+    async fn rename_tag(
+        &self,
+        tag_name: &str,
+        new_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        if self.is_valid_tag_for_user(&user, new_name).await? {
+            return Err(TagError::AlreadyExists(new_name.to_string()).into());
+        }
+        let result = sqlx::query(
+            "UPDATE user_folder SET tag_name = $1 WHERE tag_name = $2 AND owner_id = $3",
+        )
+        .bind(new_name)
+        .bind(tag_name)
+        .bind(user.to_string())
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(TagError::NotFound(tag_name.to_string()).into());
+        }
+        Ok(())
+    }
+
+    async fn copy_tag(
+        &self,
+        tag_name: &str,
+        new_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        if self.is_valid_tag_for_user(&user, new_name).await? {
+            return Err(TagError::AlreadyExists(new_name.to_string()).into());
+        }
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+
+        let mut tx = self.pool.begin().await?;
+        let new_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO user_folder (tag_name, owner_id, bot, file, thread_mode)
+            SELECT $1, owner_id, bot, file, thread_mode FROM user_folder WHERE tag_id = $2
+            RETURNING tag_id",
+        )
+        .bind(new_name)
+        .bind(tag_id)
+        .fetch_one(&mut tx)
+        .await?;
+        for sql in [
+            "INSERT INTO channel_list (channel_id, tag_id)
+            SELECT channel_id, $1 FROM channel_list WHERE tag_id = $2",
+            "INSERT INTO tag_filter (tag_id, kind, pattern, is_regex)
+            SELECT $1, kind, pattern, is_regex FROM tag_filter WHERE tag_id = $2",
+            "INSERT INTO sender_rule (tag_id, kind, sender_id)
+            SELECT $1, kind, sender_id FROM sender_rule WHERE tag_id = $2",
+        ] {
+            sqlx::query(sql)
+                .bind(new_id)
+                .bind(tag_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn merge_tag(
+        &self,
+        tag_name: &str,
+        into: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<i64> {
+        if tag_name == into {
+            return Err(TagError::MergeIntoItself.into());
+        }
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        let into_id = self
+            .fetch_tag_id(&user, into)
+            .await
+            .context("failed to fetch the tag to merge into")?;
+
+        let mut tx = self.pool.begin().await?;
+        Self::lock_tag(&mut tx, into_id).await?;
+        for sql in [
+            "INSERT INTO channel_list (channel_id, tag_id)
+            SELECT channel_id, $1 FROM channel_list WHERE tag_id = $2
+            ON CONFLICT (channel_id, tag_id) DO NOTHING",
+            "INSERT INTO dist (user_id, tag_id, dist_channel_id)
+            SELECT user_id, $1, dist_channel_id FROM dist WHERE tag_id = $2
+            ON CONFLICT (user_id, tag_id, dist_channel_id) DO NOTHING",
+            "INSERT INTO tag_filter (tag_id, kind, pattern, is_regex)
+            SELECT $1, kind, pattern, is_regex FROM tag_filter WHERE tag_id = $2
+            ON CONFLICT (tag_id, kind, pattern, is_regex) DO NOTHING",
+            "INSERT INTO sender_rule (tag_id, kind, sender_id)
+            SELECT $1, kind, sender_id FROM sender_rule WHERE tag_id = $2
+            ON CONFLICT (tag_id, kind, sender_id) DO NOTHING",
+            "INSERT INTO tag_member (tag_id, member_id, role)
+            SELECT $1, member_id, role FROM tag_member WHERE tag_id = $2
+            ON CONFLICT (tag_id, member_id) DO NOTHING",
+        ] {
+            sqlx::query(sql)
+                .bind(into_id)
+                .bind(tag_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        Self::count_dists(&mut tx, into_id).await?;
+        sqlx::query("DELETE FROM user_folder WHERE tag_id = $1")
+            .bind(tag_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(tag_id)
+    }
+
+    async fn is_valid_tag_for_user(
+        &self,
+        user: &SlackUserId,
+        tag_name: &str,
+    ) -> anyhow::Result<bool> {
+        let is_valid = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (
+                SELECT 1 FROM user_folder WHERE owner_id = $1 AND tag_name = $2
+            )",
+        )
+        .bind(user.to_string())
+        .bind(tag_name)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(is_valid)
+    }
+
+    async fn tag_list_user(&self, owner_id: SlackUserId) -> anyhow::Result<Vec<String>> {
+        let tag_list =
+            sqlx::query_scalar::<_, String>("SELECT tag_name FROM user_folder WHERE owner_id = $1")
+                .bind(owner_id.to_string())
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(tag_list)
+    }
+
+    async fn tag_list_public(&self) -> anyhow::Result<Vec<String>> {
+        let tag_list = sqlx::query_scalar::<_, String>(
+            "SELECT tag_name FROM user_folder WHERE owner_id = 'public'",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tag_list)
+    }
+
+    async fn channel_list(
+        &self,
+        tag: &str,
+        owner_id: SlackUserId,
+    ) -> anyhow::Result<Vec<SlackChannelId>> {
+        let ch_list = sqlx::query_scalar::<_, String>(
+            "SELECT cl.channel_id
+            FROM channel_list cl INNER JOIN user_folder uf
+            ON cl.tag_id = uf.tag_id
+            WHERE uf.owner_id = $1 AND uf.tag_name = $2",
+        )
+        .bind(owner_id.to_string())
+        .bind(tag)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(SlackChannelId::new)
+        .collect::<Vec<_>>();
+        Ok(ch_list)
+    }
+
+    async fn tag_config_list(&self) -> anyhow::Result<Vec<TagConfig>> {
+        let tags = sqlx::query_as::<_, TagRecord>(
+            "SELECT tag_id, tag_name, owner_id, bot, file, thread_mode
+            FROM user_folder
+            ORDER BY tag_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let channels = sqlx::query_as::<_, (i64, String)>(
+            "SELECT tag_id, channel_id FROM channel_list ORDER BY tag_id, channel_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let dists = sqlx::query_as::<_, (i64, String)>(
+            "SELECT tag_id, dist_channel_id FROM dist ORDER BY tag_id, dist_channel_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tag_config::assemble_configs(tags, channels, dists))
+    }
+
+    async fn share_tag(
+        &self,
+        tag_name: &str,
+        owner: SlackUserId,
+        member_id: &str,
+        role: TagRole,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&owner, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query(
+            "INSERT INTO tag_member (tag_id, member_id, role) VALUES ($1, $2, $3)
+            ON CONFLICT (tag_id, member_id)
+            DO UPDATE SET role = excluded.role",
+        )
+        .bind(tag_id)
+        .bind(member_id)
+        .bind(role.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn unshare_tag(
+        &self,
+        tag_name: &str,
+        owner: SlackUserId,
+        member_id: &str,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&owner, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query("DELETE FROM tag_member WHERE tag_id = $1 AND member_id = $2")
+            .bind(tag_id)
+            .bind(member_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn tag_shares(
+        &self,
+        tag_name: &str,
+        owner: &SlackUserId,
+    ) -> anyhow::Result<Vec<TagShare>> {
+        sqlx::query_as::<_, ShareRow>(
+            "SELECT uf.tag_name, uf.owner_id, tm.member_id, tm.role
+            FROM tag_member tm INNER JOIN user_folder uf
+            ON tm.tag_id = uf.tag_id
+            WHERE uf.tag_name = $1 AND uf.owner_id = $2
+            ORDER BY tm.member_id",
+        )
+        .bind(tag_name)
+        .bind(owner.to_string())
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(tag_share)
+        .collect()
+    }
+
+    async fn member_shares(&self, member_ids: &[String]) -> anyhow::Result<Vec<TagShare>> {
+        sqlx::query_as::<_, ShareRow>(
+            "SELECT uf.tag_name, uf.owner_id, tm.member_id, tm.role
+            FROM tag_member tm INNER JOIN user_folder uf
+            ON tm.tag_id = uf.tag_id
+            WHERE tm.member_id = ANY($1)
+            ORDER BY uf.tag_name, uf.owner_id",
+        )
+        .bind(member_ids)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(tag_share)
+        .collect()
+    }
+
+    async fn add_admin(&self, user: &SlackUserId) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO workspace_admin (user_id) VALUES ($1)
+            ON CONFLICT (user_id)
+            DO NOTHING",
+        )
+        .bind(user.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_admin(&self, user: &SlackUserId) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM workspace_admin WHERE user_id = $1")
+            .bind(user.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn admin_list(&self) -> anyhow::Result<Vec<SlackUserId>> {
+        let admins = sqlx::query_scalar::<_, String>("SELECT user_id FROM workspace_admin")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(SlackUserId::new)
+            .collect::<Vec<_>>();
+        Ok(admins)
+    }
+
+    async fn user_lang(&self, user: &SlackUserId) -> anyhow::Result<Option<Lang>> {
+        sqlx::query_scalar::<_, String>("SELECT lang FROM user_lang WHERE user_id = $1")
+            .bind(user.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|lang| lang.parse::<Lang>())
+            .transpose()
+    }
+
+    async fn set_user_lang(&self, user: &SlackUserId, lang: Option<Lang>) -> anyhow::Result<()> {
+        match lang {
+            Some(lang) => {
+                sqlx::query(
+                    "INSERT INTO user_lang (user_id, lang) VALUES ($1, $2)
+                    ON CONFLICT (user_id)
+                    DO UPDATE SET lang = excluded.lang",
+                )
+                .bind(user.to_string())
+                .bind(lang.to_string())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM user_lang WHERE user_id = $1")
+                    .bind(user.to_string())
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn record_audit(&self, entry: &AuditEntry) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO audit_log
+                (actor_id, action, tag_name, owner_id, channels, detail, created_at, tag_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7,
+                COALESCE($8, (SELECT tag_id FROM user_folder WHERE tag_name = $9 AND owner_id = $4)))",
+        )
+        .bind(entry.actor.to_string())
+        .bind(entry.action.to_string())
+        .bind(&entry.tag_name)
+        .bind(entry.owner_id.as_ref().map(ToString::to_string))
+        .bind(audit_log::join_channels(&entry.channels))
+        .bind(&entry.detail)
+        .bind(entry.created_at)
+        .bind(entry.tag_id)
+        .bind(entry.current_tag_name())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn audit_list(&self, query: &AuditQuery) -> anyhow::Result<Vec<AuditEntry>> {
+        let (tag_name, owner_id) = match &query.tag {
+            Some((tag_name, owner_id)) => (Some(tag_name.clone()), Some(owner_id.to_string())),
+            None => (None, None),
+        };
+        sqlx::query_as::<
+            _,
+            (
+                i64,
+                String,
+                String,
+                Option<i64>,
+                Option<String>,
+                Option<String>,
+                String,
+                String,
+                i64,
+            ),
+        >(
+            "SELECT audit_id, actor_id, action, tag_id, tag_name, owner_id, channels, detail, created_at
+            FROM audit_log
+            WHERE ($1::TEXT IS NULL
+                OR tag_id = (SELECT tag_id FROM user_folder WHERE tag_name = $1 AND owner_id = $2)
+                OR (tag_name = $1 AND owner_id = $2
+                    AND NOT EXISTS (SELECT 1 FROM user_folder WHERE tag_name = $1 AND owner_id = $2)))
+            AND ($3::TEXT IS NULL OR (' ' || channels || ' ') LIKE ('% ' || $3 || ' %'))
+            AND ($4::TEXT IS NULL OR actor_id = $4 OR owner_id = $4 OR owner_id = 'public')
+            ORDER BY audit_id DESC
+            LIMIT $5",
+        )
+        .bind(tag_name)
+        .bind(owner_id)
+        .bind(query.channel.as_ref().map(ToString::to_string))
+        .bind(query.viewer.as_ref().map(ToString::to_string))
+        .bind(query.limit)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(
+            |(audit_id, actor, action, tag_id, tag_name, owner_id, channels, detail, created_at)| {
+                anyhow::Ok(AuditEntry {
+                    audit_id,
+                    actor: SlackUserId::new(actor),
+                    action: action.parse::<AuditAction>()?,
+                    tag_id,
+                    tag_name,
+                    owner_id: owner_id.map(SlackUserId::new),
+                    channels: audit_log::split_channels(&channels),
+                    detail,
+                    created_at,
+                })
+            },
+        )
+        .collect::<anyhow::Result<Vec<_>>>()
+    }
+
+    async fn add_filter(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        filter: &TagFilter,
+    ) -> anyhow::Result<()> {
+        filter.compile()?;
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query(
+            "INSERT INTO tag_filter (tag_id, kind, pattern, is_regex) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (tag_id, kind, pattern, is_regex)
+            DO NOTHING",
+        )
+        .bind(tag_id)
+        .bind(filter.kind.to_string())
+        .bind(&filter.pattern)
+        .bind(filter.is_regex)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_filter(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        filter: &TagFilter,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query(
+            "DELETE FROM tag_filter
+            WHERE tag_id = $1 AND kind = $2 AND pattern = $3 AND is_regex = $4",
+        )
+        .bind(tag_id)
+        .bind(filter.kind.to_string())
+        .bind(&filter.pattern)
+        .bind(filter.is_regex)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn filter_list(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<Vec<TagFilter>> {
+        sqlx::query_as::<_, (String, String, bool)>(
+            "SELECT tf.kind, tf.pattern, tf.is_regex
+            FROM tag_filter tf INNER JOIN user_folder uf
+            ON tf.tag_id = uf.tag_id
+            WHERE uf.owner_id = $1 AND uf.tag_name = $2",
+        )
+        .bind(user.to_string())
+        .bind(tag_name)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(kind, pattern, is_regex)| {
+            anyhow::Ok(TagFilter {
+                kind: kind.parse::<FilterKind>()?,
+                pattern,
+                is_regex,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+    }
+
+    async fn add_rule(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        rule: &SenderRule,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query(
+            "INSERT INTO sender_rule (tag_id, kind, sender_id) VALUES ($1, $2, $3)
+            ON CONFLICT (tag_id, kind, sender_id)
+            DO NOTHING",
+        )
+        .bind(tag_id)
+        .bind(rule.kind.to_string())
+        .bind(&rule.sender_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_rule(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        rule: &SenderRule,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&user, tag_name)
+            .await
+            .context("failed to fetch the tag")?;
+        sqlx::query("DELETE FROM sender_rule WHERE tag_id = $1 AND kind = $2 AND sender_id = $3")
+            .bind(tag_id)
+            .bind(rule.kind.to_string())
+            .bind(&rule.sender_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn rule_list(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<Vec<SenderRule>> {
+        sqlx::query_as::<_, (String, String)>(
+            "SELECT sr.kind, sr.sender_id
+            FROM sender_rule sr INNER JOIN user_folder uf
+            ON sr.tag_id = uf.tag_id
+            WHERE uf.owner_id = $1 AND uf.tag_name = $2",
+        )
+        .bind(user.to_string())
+        .bind(tag_name)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(kind, sender_id)| {
+            anyhow::Ok(SenderRule {
+                kind: kind.parse::<RuleKind>()?,
+                sender_id,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+    }
+
+    async fn add_tag(
+        &self,
+        dist: SlackChannelId,
+        user: SlackUserId,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&user, tag)
+            .await
+            .context("failed to fetch the tag")?;
+
+        let mut tx = self.pool.begin().await?;
+        Self::lock_tag(&mut tx, tag_id).await?;
+        sqlx::query(
+            "INSERT INTO dist (user_id, tag_id, dist_channel_id) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, tag_id, dist_channel_id)
+            DO NOTHING",
+        )
+        .bind(user.to_string())
+        .bind(tag_id)
+        .bind(dist.to_string())
+        .execute(&mut tx)
+        .await?;
+        Self::count_dists(&mut tx, tag_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn remove_tag(
+        &self,
+        dist: SlackChannelId,
+        user: SlackUserId,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        let tag_id = self
+            .fetch_tag_id(&user, tag)
+            .await
+            .context("failed to fetch the tag")?;
+
+        let mut tx = self.pool.begin().await?;
+        Self::lock_tag(&mut tx, tag_id).await?;
+        sqlx::query("DELETE FROM dist WHERE user_id = $1 AND tag_id = $2 AND dist_channel_id = $3")
+            .bind(user.to_string())
+            .bind(tag_id)
+            .bind(dist.to_string())
+            .execute(&mut tx)
+            .await?;
+        Self::count_dists(&mut tx, tag_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn repair_valid_count(&self) -> anyhow::Result<u64> {
+        let result = sqlx::query(
+            "UPDATE user_folder
+            SET valid_count = (SELECT COUNT(*) FROM dist WHERE dist.tag_id = user_folder.tag_id)
+            WHERE valid_count <> (SELECT COUNT(*) FROM dist WHERE dist.tag_id = user_folder.tag_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn target_list(&self, dist: &SlackChannelId) -> anyhow::Result<Vec<String>> {
+        let target_list = sqlx::query_scalar::<_, String>(
+            "SELECT uf.tag_name
+            FROM dist INNER JOIN user_folder AS uf
+            ON dist.tag_id = uf.tag_id
+            WHERE dist.dist_channel_id = $1",
+        )
+        .bind(dist.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(target_list)
+    }
+
+    async fn route_map(&self) -> anyhow::Result<HashMap<SlackChannelId, Vec<TagRoute>>> {
+        let records = sqlx::query_as::<_, RouteRecord>(
+            "SELECT cl.channel_id, uf.tag_id, uf.bot, uf.file, uf.thread_mode, uf.valid_count,
+                dist.dist_channel_id
+            FROM channel_list cl
+            INNER JOIN user_folder uf ON cl.tag_id = uf.tag_id
+            LEFT JOIN dist ON dist.tag_id = uf.tag_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut filters = HashMap::<i64, Vec<TagFilter>>::new();
+        let filter_records = sqlx::query_as::<_, (i64, String, String, bool)>(
+            "SELECT tag_id, kind, pattern, is_regex FROM tag_filter",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for (tag_id, kind, pattern, is_regex) in filter_records {
+            let filter = TagFilter {
+                kind: kind.parse::<FilterKind>()?,
+                pattern,
+                is_regex,
+            };
+            filters.entry(tag_id).or_default().push(filter);
+        }
+
+        let mut rules = HashMap::<i64, Vec<SenderRule>>::new();
+        let rule_records = sqlx::query_as::<_, (i64, String, String)>(
+            "SELECT tag_id, kind, sender_id FROM sender_rule",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for (tag_id, kind, sender_id) in rule_records {
+            let rule = SenderRule {
+                kind: kind.parse::<RuleKind>()?,
+                sender_id,
+            };
+            rules.entry(tag_id).or_default().push(rule);
+        }
+
+        Ok(dist_target_map::assemble_routes(records, &filters, &rules))
+    }
+
+    async fn set_delivery_mode(
+        &self,
+        dist: &SlackChannelId,
+        user: &SlackUserId,
+        mode: DeliveryMode,
+        time_zone: Option<&TimeZone>,
+    ) -> anyhow::Result<()> {
+        let (mode_str, mode_minutes) = mode.to_columns();
+        sqlx::query(
+            "INSERT INTO dist_schedule
+                (dist_channel_id, mode, mode_minutes, time_zone, last_delivered_at, set_by)
+            VALUES ($1, $2, $3, COALESCE($4, $7), $5, $6)
+            ON CONFLICT (dist_channel_id)
+            DO UPDATE SET mode = excluded.mode, mode_minutes = excluded.mode_minutes,
+                time_zone = COALESCE($4, dist_schedule.time_zone),
+                last_delivered_at = excluded.last_delivered_at, set_by = excluded.set_by",
+        )
+        .bind(dist.to_string())
+        .bind(mode_str)
+        .bind(mode_minutes)
+        .bind(time_zone.map(ToString::to_string))
+        .bind(crate::query::delivery::now())
+        .bind(user.to_string())
+        .bind(TimeZone::default().to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delivery_set_by(&self, dist: &SlackChannelId) -> anyhow::Result<Option<SlackUserId>> {
+        let set_by = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT set_by FROM dist_schedule WHERE dist_channel_id = $1",
+        )
+        .bind(dist.to_string())
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten()
+        .map(SlackUserId::new);
+        Ok(set_by)
+    }
+
+    async fn set_quiet_hours(
+        &self,
+        dist: &SlackChannelId,
+        quiet_hours: Option<(i64, i64)>,
+        time_zone: Option<&TimeZone>,
+    ) -> anyhow::Result<()> {
+        let (quiet_start, quiet_end) = quiet_hours.unzip();
+        sqlx::query(
+            "INSERT INTO dist_schedule (dist_channel_id, time_zone, last_delivered_at, quiet_start, quiet_end)
+            VALUES ($1, COALESCE($2, $6), $3, $4, $5)
+            ON CONFLICT (dist_channel_id)
+            DO UPDATE SET quiet_start = excluded.quiet_start, quiet_end = excluded.quiet_end,
+                time_zone = COALESCE($2, dist_schedule.time_zone)",
+        )
+        .bind(dist.to_string())
+        .bind(time_zone.map(ToString::to_string))
+        .bind(crate::query::delivery::now())
+        .bind(quiet_start)
+        .bind(quiet_end)
+        .bind(TimeZone::default().to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn schedule_list(&self) -> anyhow::Result<HashMap<SlackChannelId, DeliverySchedule>> {
+        sqlx::query_as::<_, (String, String, i64, String, i64, Option<i64>, Option<i64>)>(
+            "SELECT dist_channel_id, mode, mode_minutes, time_zone, last_delivered_at,
+                quiet_start, quiet_end
+            FROM dist_schedule",
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(
+            |(dist, mode, mode_minutes, time_zone, last_delivered_at, quiet_start, quiet_end)| {
+                let schedule = DeliverySchedule {
+                    mode: DeliveryMode::from_columns(&mode, mode_minutes)?,
+                    time_zone: time_zone.parse::<TimeZone>()?,
+                    last_delivered_at,
+                    quiet_hours: quiet_start.zip(quiet_end),
+                };
+                anyhow::Ok((SlackChannelId::new(dist), schedule))
+            },
+        )
+        .collect::<anyhow::Result<HashMap<_, _>>>()
+    }
+
+    async fn mark_delivered(&self, dist: &SlackChannelId, delivered_at: i64) -> anyhow::Result<()> {
+        sqlx::query("UPDATE dist_schedule SET last_delivered_at = $1 WHERE dist_channel_id = $2")
+            .bind(delivered_at)
+            .bind(dist.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn enqueue(&self, message: &QueuedMessage) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO delivery_queue
+                (dist_channel_id, source_channel_id, source_ts, sender_name, icon_url, text, queued_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(message.dist.to_string())
+        .bind(message.source_channel.to_string())
+        .bind(message.source_ts.to_string())
+        .bind(&message.sender_name)
+        .bind(&message.icon_url)
+        .bind(&message.text)
+        .bind(message.queued_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pending_dists(&self) -> anyhow::Result<Vec<SlackChannelId>> {
+        let dists =
+            sqlx::query_scalar::<_, String>("SELECT DISTINCT dist_channel_id FROM delivery_queue")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .map(SlackChannelId::new)
+                .collect::<Vec<_>>();
+        Ok(dists)
+    }
+
+    // A replica waiting for the rows another one is claiming sees them claimed once it may go on,
+    // so each message is claimed only once
+    async fn claim_queued(
+        &self,
+        dist: &SlackChannelId,
+        now: i64,
+        claimed_until: i64,
+    ) -> anyhow::Result<Vec<QueuedMessage>> {
+        let mut claimed = sqlx::query_as::<_, QueuedRow>(
+            "UPDATE delivery_queue
+            SET claimed_until = $1
+            WHERE dist_channel_id = $2 AND claimed_until <= $3
+            RETURNING queue_id, dist_channel_id, source_channel_id, source_ts, sender_name, icon_url, text, queued_at",
+        )
+        .bind(claimed_until)
+        .bind(dist.to_string())
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(queued_message)
+        .collect::<Vec<_>>();
+        claimed.sort_by_key(|message| message.queue_id);
+        Ok(claimed)
+    }
+
+    async fn remove_delivered(&self, queue_ids: &[i64]) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM delivery_queue WHERE queue_id = ANY($1)")
+            .bind(queue_ids)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_source(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+    ) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM delivery_queue WHERE source_channel_id = $1 AND source_ts = $2")
+            .bind(source_channel.to_string())
+            .bind(source_ts.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_source(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE delivery_queue SET text = $1 WHERE source_channel_id = $2 AND source_ts = $3",
+        )
+        .bind(text)
+        .bind(source_channel.to_string())
+        .bind(source_ts.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn register_forwarded(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        dist: &SlackChannelId,
+        forwarded_ts: &SlackTs,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO forwarded_message (source_channel_id, source_ts, dist_channel_id, forwarded_ts)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (source_channel_id, source_ts, dist_channel_id)
+            DO UPDATE SET forwarded_ts = excluded.forwarded_ts",
+        )
+        .bind(source_channel.to_string())
+        .bind(source_ts.to_string())
+        .bind(dist.to_string())
+        .bind(forwarded_ts.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn forwarded_list(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+    ) -> anyhow::Result<Vec<(SlackChannelId, SlackTs)>> {
+        let forwarded = sqlx::query_as::<_, (String, String)>(
+            "SELECT dist_channel_id, forwarded_ts
+            FROM forwarded_message
+            WHERE source_channel_id = $1 AND source_ts = $2",
+        )
+        .bind(source_channel.to_string())
+        .bind(source_ts.to_string())
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(dist, forwarded_ts)| (SlackChannelId::new(dist), SlackTs::new(forwarded_ts)))
+        .collect::<Vec<_>>();
+        Ok(forwarded)
+    }
+
+    async fn unregister_forwarded(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        dist: &SlackChannelId,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "DELETE FROM forwarded_message
+            WHERE source_channel_id = $1 AND source_ts = $2 AND dist_channel_id = $3",
+        )
+        .bind(source_channel.to_string())
+        .bind(source_ts.to_string())
+        .bind(dist.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "postgres-test"))]
+mod tests {
+    use sqlx::Executor;
+
+    use crate::storage::testing::PostgresTestDb;
+
+    use super::*;
+
+    // POSTGRES_TEST_URL=postgres://... cargo test --features postgres-test
+    #[tokio::test]
+    async fn test_migrate_setup_only() -> anyhow::Result<()> {
+        let db = PostgresTestDb::create("postgres::test_migrate_setup_only").await?;
+        // only 0_setup.sql, so the tables and columns of the later migrations have to be created
+        let setup = MIGRATOR
+            .iter()
+            .find(|migration| migration.version == 0)
+            .context("0_setup.sql is missing")?;
+        db.pool.execute(&*setup.sql).await?;
+
+        let storage = PostgresStorage::new(db.pool.clone());
+        storage.migrate().await?;
+        // running again on an up to date database does nothing
+        storage.migrate().await?;
+        let applied = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM _sqlx_migrations")
+            .fetch_one(&db.pool)
+            .await?;
+        assert_eq!(applied, i64::try_from(MIGRATOR.iter().count())?);
+        assert!(
+            storage
+                .has_schema("user_folder", Some("thread_mode"))
+                .await?
+        );
+        assert!(
+            storage
+                .has_schema("dist_schedule", Some("quiet_end"))
+                .await?
+        );
+        assert!(
+            !storage
+                .has_schema("user_folder", Some("no_such_column"))
+                .await?
+        );
+
+        db.drop().await
+    }
+}
+
+type QueuedRow = (i64, String, String, String, String, String, String, i64);
+
+fn queued_message(
+    (queue_id, dist, source_channel, source_ts, sender_name, icon_url, text, queued_at): QueuedRow,
+) -> QueuedMessage {
+    QueuedMessage {
+        queue_id,
+        dist: SlackChannelId::new(dist),
+        source_channel: SlackChannelId::new(source_channel),
+        source_ts: SlackTs::new(source_ts),
+        sender_name,
+        icon_url,
+        text,
+        queued_at,
+    }
+}
+
+type ShareRow = (String, String, String, String);
+
+fn tag_share((tag_name, owner_id, member_id, role): ShareRow) -> anyhow::Result<TagShare> {
+    Ok(TagShare {
+        tag_name,
+        owner_id: SlackUserId::new(owner_id),
+        member_id,
+        role: role.parse::<TagRole>()?,
+    })
+}
@@ -0,0 +1,571 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use slack_morphism::{SlackChannelId, SlackTs, SlackUserId};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    Pool, Sqlite,
+};
+
+use crate::query::{
+    audit_log::{self, AuditEntry, AuditQuery},
+    delivery::{DeliveryMode, DeliverySchedule, TimeZone},
+    delivery_queue::{self, QueuedMessage},
+    dist,
+    dist_target_map::{self, TagRoute},
+    fetch_user_folder, forwarded_message,
+    sender_rule::{self, SenderRule},
+    tag_config::{self, TagConfig},
+    tag_filter::{self, TagFilter},
+    tag_member::{self, TagRole, TagShare},
+    thread_mode::ThreadMode,
+    user_folder,
+    user_lang::{self, Lang},
+    workspace_admin,
+};
+
+use super::{Storage, LEGACY_MIGRATIONS};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+// The sqlite backend, on top of the checked queries in `query`
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStorage {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn migrate(&self) -> anyhow::Result<()> {
+        let is_legacy = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'user_folder')
+            AND NOT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if is_legacy {
+            let mut conn = self.pool.acquire().await?;
+            conn.ensure_migrations_table().await?;
+            for (version, table, column) in LEGACY_MIGRATIONS {
+                let Some(migration) = MIGRATOR
+                    .iter()
+                    .find(|migration| migration.version == version)
+                else {
+                    continue;
+                };
+                if !self.has_schema(table, column).await? {
+                    continue;
+                }
+                sqlx::query(
+                    "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+                    VALUES ($1, $2, true, $3, 0)",
+                )
+                .bind(migration.version)
+                .bind(&*migration.description)
+                .bind(&*migration.checksum)
+                .execute(&mut conn)
+                .await?;
+            }
+        }
+
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    // Whether the table, or the column of it, exists
+    async fn has_schema(&self, table: &str, column: Option<&str>) -> anyhow::Result<bool> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM pragma_table_info($1) WHERE $2 IS NULL OR name = $2",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn register_channel(
+        &self,
+        tag_name: &str,
+        channel: SlackChannelId,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        user_folder::register_channel_with_pool(tag_name, channel, user, &self.pool).await
+    }
+
+    async fn unregister_channel(
+        &self,
+        tag_name: &str,
+        channel: SlackChannelId,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        user_folder::unregister_channel_with_pool(tag_name, channel, user, &self.pool).await
+    }
+
+    async fn drop_tag(&self, tag_name: &str, user: SlackUserId) -> anyhow::Result<i64> {
+        user_folder::drop_tag_with_pool(tag_name, user, &self.pool).await
+    }
+
+    async fn retrieve_bot(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        retrieve_bot: bool,
+    ) -> anyhow::Result<()> {
+        user_folder::retrieve_bot_with_pool(tag_name, user, retrieve_bot, &self.pool).await
+    }
+
+    async fn retrieve_file(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        retrieve_file: bool,
+    ) -> anyhow::Result<()> {
+        user_folder::retrieve_file_with_pool(tag_name, user, retrieve_file, &self.pool).await
+    }
+
+    async fn thread_mode(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        thread_mode: ThreadMode,
+    ) -> anyhow::Result<()> {
+        user_folder::thread_mode_with_pool(tag_name, user, thread_mode, &self.pool).await
+    }
+
+    async fn rename_tag(
+        &self,
+        tag_name: &str,
+        new_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        user_folder::rename_tag_with_pool(tag_name, new_name, user, &self.pool).await
+    }
+
+    async fn copy_tag(
+        &self,
+        tag_name: &str,
+        new_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<()> {
+        user_folder::copy_tag_with_pool(tag_name, new_name, user, &self.pool).await
+    }
+
+    async fn merge_tag(
+        &self,
+        tag_name: &str,
+        into: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<i64> {
+        user_folder::merge_tag_with_pool(tag_name, into, user, &self.pool).await
+    }
+
+    async fn is_valid_tag_for_user(
+        &self,
+        user: &SlackUserId,
+        tag_name: &str,
+    ) -> anyhow::Result<bool> {
+        user_folder::is_valid_tag_for_user_with_pool(user, tag_name, &self.pool).await
+    }
+
+    async fn tag_list_user(&self, owner_id: SlackUserId) -> anyhow::Result<Vec<String>> {
+        fetch_user_folder::tag_list_user_with_pool(owner_id, &self.pool).await
+    }
+
+    async fn tag_list_public(&self) -> anyhow::Result<Vec<String>> {
+        fetch_user_folder::tag_list_public_with_pool(&self.pool).await
+    }
+
+    async fn channel_list(
+        &self,
+        tag: &str,
+        owner_id: SlackUserId,
+    ) -> anyhow::Result<Vec<SlackChannelId>> {
+        fetch_user_folder::channel_list_with_pool(tag, owner_id, &self.pool).await
+    }
+
+    async fn tag_config_list(&self) -> anyhow::Result<Vec<TagConfig>> {
+        tag_config::tag_config_list_with_pool(&self.pool).await
+    }
+
+    async fn share_tag(
+        &self,
+        tag_name: &str,
+        owner: SlackUserId,
+        member_id: &str,
+        role: TagRole,
+    ) -> anyhow::Result<()> {
+        tag_member::share_tag_with_pool(tag_name, owner, member_id, role, &self.pool).await
+    }
+
+    async fn unshare_tag(
+        &self,
+        tag_name: &str,
+        owner: SlackUserId,
+        member_id: &str,
+    ) -> anyhow::Result<()> {
+        tag_member::unshare_tag_with_pool(tag_name, owner, member_id, &self.pool).await
+    }
+
+    async fn tag_shares(
+        &self,
+        tag_name: &str,
+        owner: &SlackUserId,
+    ) -> anyhow::Result<Vec<TagShare>> {
+        tag_member::tag_shares_with_pool(tag_name, owner, &self.pool).await
+    }
+
+    async fn member_shares(&self, member_ids: &[String]) -> anyhow::Result<Vec<TagShare>> {
+        tag_member::member_shares_with_pool(member_ids, &self.pool).await
+    }
+
+    async fn add_admin(&self, user: &SlackUserId) -> anyhow::Result<()> {
+        workspace_admin::add_admin_with_pool(user, &self.pool).await
+    }
+
+    async fn remove_admin(&self, user: &SlackUserId) -> anyhow::Result<()> {
+        workspace_admin::remove_admin_with_pool(user, &self.pool).await
+    }
+
+    async fn admin_list(&self) -> anyhow::Result<Vec<SlackUserId>> {
+        workspace_admin::admin_list_with_pool(&self.pool).await
+    }
+
+    async fn user_lang(&self, user: &SlackUserId) -> anyhow::Result<Option<Lang>> {
+        user_lang::lang_with_pool(user, &self.pool).await
+    }
+
+    async fn set_user_lang(&self, user: &SlackUserId, lang: Option<Lang>) -> anyhow::Result<()> {
+        user_lang::set_lang_with_pool(user, lang, &self.pool).await
+    }
+
+    async fn record_audit(&self, entry: &AuditEntry) -> anyhow::Result<()> {
+        audit_log::record_with_pool(entry, &self.pool).await
+    }
+
+    async fn audit_list(&self, query: &AuditQuery) -> anyhow::Result<Vec<AuditEntry>> {
+        audit_log::audit_list_with_pool(query, &self.pool).await
+    }
+
+    async fn add_filter(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        filter: &TagFilter,
+    ) -> anyhow::Result<()> {
+        tag_filter::add_filter_with_pool(tag_name, user, filter, &self.pool).await
+    }
+
+    async fn remove_filter(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        filter: &TagFilter,
+    ) -> anyhow::Result<()> {
+        tag_filter::remove_filter_with_pool(tag_name, user, filter, &self.pool).await
+    }
+
+    async fn filter_list(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<Vec<TagFilter>> {
+        tag_filter::filter_list_with_pool(tag_name, user, &self.pool).await
+    }
+
+    async fn add_rule(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        rule: &SenderRule,
+    ) -> anyhow::Result<()> {
+        sender_rule::add_rule_with_pool(tag_name, user, rule, &self.pool).await
+    }
+
+    async fn remove_rule(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+        rule: &SenderRule,
+    ) -> anyhow::Result<()> {
+        sender_rule::remove_rule_with_pool(tag_name, user, rule, &self.pool).await
+    }
+
+    async fn rule_list(
+        &self,
+        tag_name: &str,
+        user: SlackUserId,
+    ) -> anyhow::Result<Vec<SenderRule>> {
+        sender_rule::rule_list_with_pool(tag_name, user, &self.pool).await
+    }
+
+    async fn add_tag(
+        &self,
+        dist: SlackChannelId,
+        user: SlackUserId,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        dist::add_tag_with_pool(dist, user, tag, &self.pool).await
+    }
+
+    async fn remove_tag(
+        &self,
+        dist: SlackChannelId,
+        user: SlackUserId,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        dist::remove_tag_with_pool(dist, user, tag, &self.pool).await
+    }
+
+    async fn repair_valid_count(&self) -> anyhow::Result<u64> {
+        dist::repair_valid_count_with_pool(&self.pool).await
+    }
+
+    async fn target_list(&self, dist: &SlackChannelId) -> anyhow::Result<Vec<String>> {
+        dist::target_list_with_pool(dist, &self.pool).await
+    }
+
+    async fn route_map(&self) -> anyhow::Result<HashMap<SlackChannelId, Vec<TagRoute>>> {
+        dist_target_map::route_map_with_pool(&self.pool).await
+    }
+
+    async fn set_delivery_mode(
+        &self,
+        dist: &SlackChannelId,
+        user: &SlackUserId,
+        mode: DeliveryMode,
+        time_zone: Option<&TimeZone>,
+    ) -> anyhow::Result<()> {
+        dist::set_delivery_mode_with_pool(dist, user, mode, time_zone, &self.pool).await
+    }
+
+    async fn delivery_set_by(&self, dist: &SlackChannelId) -> anyhow::Result<Option<SlackUserId>> {
+        dist::delivery_set_by_with_pool(dist, &self.pool).await
+    }
+
+    async fn set_quiet_hours(
+        &self,
+        dist: &SlackChannelId,
+        quiet_hours: Option<(i64, i64)>,
+        time_zone: Option<&TimeZone>,
+    ) -> anyhow::Result<()> {
+        dist::set_quiet_hours_with_pool(dist, quiet_hours, time_zone, &self.pool).await
+    }
+
+    async fn schedule_list(&self) -> anyhow::Result<HashMap<SlackChannelId, DeliverySchedule>> {
+        dist::schedule_list_with_pool(&self.pool).await
+    }
+
+    async fn mark_delivered(&self, dist: &SlackChannelId, delivered_at: i64) -> anyhow::Result<()> {
+        dist::mark_delivered_with_pool(dist, delivered_at, &self.pool).await
+    }
+
+    async fn enqueue(&self, message: &QueuedMessage) -> anyhow::Result<()> {
+        delivery_queue::enqueue_with_pool(message, &self.pool).await
+    }
+
+    async fn pending_dists(&self) -> anyhow::Result<Vec<SlackChannelId>> {
+        delivery_queue::pending_dists_with_pool(&self.pool).await
+    }
+
+    async fn claim_queued(
+        &self,
+        dist: &SlackChannelId,
+        now: i64,
+        claimed_until: i64,
+    ) -> anyhow::Result<Vec<QueuedMessage>> {
+        delivery_queue::claim_queued_with_pool(dist, now, claimed_until, &self.pool).await
+    }
+
+    async fn remove_delivered(&self, queue_ids: &[i64]) -> anyhow::Result<()> {
+        delivery_queue::remove_delivered_with_pool(queue_ids, &self.pool).await
+    }
+
+    async fn remove_source(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+    ) -> anyhow::Result<()> {
+        delivery_queue::remove_source_with_pool(source_channel, source_ts, &self.pool).await
+    }
+
+    async fn update_source(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        delivery_queue::update_source_with_pool(source_channel, source_ts, text, &self.pool).await
+    }
+
+    async fn register_forwarded(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        dist: &SlackChannelId,
+        forwarded_ts: &SlackTs,
+    ) -> anyhow::Result<()> {
+        forwarded_message::register_forwarded_with_pool(
+            source_channel,
+            source_ts,
+            dist,
+            forwarded_ts,
+            &self.pool,
+        )
+        .await
+    }
+
+    async fn forwarded_list(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+    ) -> anyhow::Result<Vec<(SlackChannelId, SlackTs)>> {
+        forwarded_message::forwarded_list_with_pool(source_channel, source_ts, &self.pool).await
+    }
+
+    async fn unregister_forwarded(
+        &self,
+        source_channel: &SlackChannelId,
+        source_ts: &SlackTs,
+        dist: &SlackChannelId,
+    ) -> anyhow::Result<()> {
+        forwarded_message::unregister_forwarded_with_pool(
+            source_channel,
+            source_ts,
+            dist,
+            &self.pool,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn table_list(pool: &Pool<Sqlite>) -> anyhow::Result<Vec<String>> {
+        let tables = sqlx::query_scalar::<_, String>(
+            "SELECT name FROM sqlite_schema WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(tables)
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_migrate(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+        let storage = SqliteStorage::new(pool.clone());
+        storage.migrate().await?;
+        // running again on an up to date database does nothing
+        storage.migrate().await?;
+
+        let tables = table_list(&pool).await?;
+        let desired_tables = [
+            "dist",
+            "user_folder",
+            "channel_list",
+            "forwarded_message",
+            "tag_filter",
+            "sender_rule",
+            "dist_schedule",
+            "delivery_queue",
+            "_sqlx_migrations",
+        ];
+        assert!(desired_tables
+            .iter()
+            .all(|table| tables.contains(&table.to_string())));
+
+        Ok(())
+    }
+
+    // Apply the migrations up to `last` by hand, as a database without a migration history
+    async fn apply_unversioned(pool: &Pool<Sqlite>, last: i64) -> anyhow::Result<()> {
+        for migration in MIGRATOR
+            .iter()
+            .filter(|migration| migration.version <= last)
+        {
+            sqlx::query(&migration.sql).execute(pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn applied_count(pool: &Pool<Sqlite>) -> anyhow::Result<i64> {
+        let applied = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM _sqlx_migrations")
+            .fetch_one(pool)
+            .await?;
+        Ok(applied)
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_migrate_legacy(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+        // the whole schema without a migration history, as examples/sqlite_init.rs created it
+        apply_unversioned(&pool, 7).await?;
+        sqlx::query("INSERT INTO dist_schedule (dist_channel_id, utc_offset) VALUES ('C01', -330)")
+            .execute(&pool)
+            .await?;
+
+        let storage = SqliteStorage::new(pool.clone());
+        storage.migrate().await?;
+        assert_eq!(
+            applied_count(&pool).await?,
+            i64::try_from(MIGRATOR.iter().count())?
+        );
+        // the offset of the old column is kept as a fixed time zone
+        let schedules = storage.schedule_list().await?;
+        assert_eq!(
+            schedules
+                .get(&SlackChannelId::new("C01".to_string()))
+                .map(|schedule| &schedule.time_zone),
+            Some(&TimeZone::Fixed(-330))
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_migrate_setup_only(pool: Pool<Sqlite>) -> anyhow::Result<()> {
+        // only 0_setup.sql, so the tables and columns of the later migrations have to be created
+        apply_unversioned(&pool, 0).await?;
+        // a tag of a real user that only shares its name with a test row
+        sqlx::query("INSERT INTO user_folder (tag_name, owner_id) VALUES ('test_a', 'U12345')")
+            .execute(&pool)
+            .await?;
+
+        let storage = SqliteStorage::new(pool.clone());
+        storage.migrate().await?;
+        assert_eq!(
+            applied_count(&pool).await?,
+            i64::try_from(MIGRATOR.iter().count())?
+        );
+        assert!(
+            storage
+                .has_schema("user_folder", Some("thread_mode"))
+                .await?
+        );
+        assert!(
+            storage
+                .has_schema("dist_schedule", Some("quiet_end"))
+                .await?
+        );
+        assert!(storage.has_schema("tag_member", None).await?);
+        assert!(
+            !storage
+                .has_schema("user_folder", Some("no_such_column"))
+                .await?
+        );
+
+        // only the test rows of 0_setup.sql are removed
+        let tags = sqlx::query_scalar::<_, String>("SELECT owner_id FROM user_folder")
+            .fetch_all(&pool)
+            .await?;
+        assert_eq!(tags, ["U12345"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// Shared fixtures, so that the behaviour tests run against every backend.
+// `storage_tests!(test_a, test_b)` turns `async fn test_a(storage: &dyn Storage)` into a test
+// on sqlite, and on the PostgreSQL given by POSTGRES_TEST_URL with the postgres-test feature.
+
+use sqlx::{Executor, SqlitePool};
+
+use super::sqlite::SqliteStorage;
+
+// The rows the tests expect, applied after the migrations
+pub const SEED: &str = include_str!("../query/fixtures/seed.sql");
+
+pub async fn sqlite_storage(pool: SqlitePool) -> anyhow::Result<SqliteStorage> {
+    pool.execute(SEED).await?;
+    Ok(SqliteStorage::new(pool))
+}
+
+#[cfg(feature = "postgres-test")]
+pub use self::postgres::PostgresTestDb;
+
+#[cfg(feature = "postgres-test")]
+mod postgres {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    use anyhow::Context;
+    use sqlx::{postgres::PgPoolOptions, Executor, PgPool};
+
+    use super::{super::postgres::PostgresStorage, SEED};
+
+    // A schema of its own for each test, which every connection of `pool` uses
+    pub struct PostgresTestDb {
+        admin: PgPool,
+        schema: String,
+        pub pool: PgPool,
+    }
+
+    impl PostgresTestDb {
+        pub async fn create(test_name: &str) -> anyhow::Result<Self> {
+            let url = std::env::var("POSTGRES_TEST_URL")
+                .context("POSTGRES_TEST_URL is needed by the postgres-test feature")?;
+            let mut hasher = DefaultHasher::new();
+            test_name.hash(&mut hasher);
+            let schema = format!("test_{:016x}", hasher.finish());
+
+            let admin = PgPool::connect(&url).await?;
+            // a schema left by a failed run is created again
+            admin
+                .execute(
+                    format!("DROP SCHEMA IF EXISTS {schema} CASCADE; CREATE SCHEMA {schema};")
+                        .as_str(),
+                )
+                .await?;
+            let search_path = format!("SET search_path TO {schema}");
+            let pool = PgPoolOptions::new()
+                .max_connections(4)
+                .after_connect(move |conn, _| {
+                    let search_path = search_path.clone();
+                    Box::pin(async move {
+                        conn.execute(search_path.as_str()).await?;
+                        Ok(())
+                    })
+                })
+                .connect(&url)
+                .await?;
+
+            Ok(Self {
+                admin,
+                schema,
+                pool,
+            })
+        }
+
+        // with the migrations and the seed applied
+        pub async fn seeded(test_name: &str) -> anyhow::Result<(Self, PostgresStorage)> {
+            let db = Self::create(test_name).await?;
+            let storage = PostgresStorage::new(db.pool.clone());
+            storage.migrate().await?;
+            db.pool.execute(SEED).await?;
+            Ok((db, storage))
+        }
+
+        pub async fn drop(self) -> anyhow::Result<()> {
+            self.pool.close().await;
+            self.admin
+                .execute(format!("DROP SCHEMA {} CASCADE", self.schema).as_str())
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+macro_rules! storage_tests {
+    ($($name:ident),* $(,)?) => {
+        mod sqlite_backend {
+            $(
+                #[sqlx::test(migrations = "./migrations")]
+                async fn $name(pool: sqlx::SqlitePool) -> anyhow::Result<()> {
+                    let storage = crate::storage::testing::sqlite_storage(pool).await?;
+                    super::$name(&storage).await
+                }
+            )*
+        }
+
+        #[cfg(feature = "postgres-test")]
+        mod postgres_backend {
+            $(
+                #[tokio::test]
+                async fn $name() -> anyhow::Result<()> {
+                    let test_name = concat!(module_path!(), "::", stringify!($name));
+                    let (db, storage) =
+                        crate::storage::testing::PostgresTestDb::seeded(test_name).await?;
+                    super::$name(&storage).await?;
+                    db.drop().await
+                }
+            )*
+        }
+    };
+}
+
+pub(crate) use storage_tests;
@@ -2,7 +2,7 @@ use anyhow::Context as _;
 use dotenvy::dotenv;
 use regex::Regex;
 use slack_morphism::{
-    SlackApiToken, SlackApiTokenType, SlackApiTokenValue, SlackBotId, SlackChannelId,
+    SlackApiToken, SlackApiTokenType, SlackApiTokenValue, SlackBotId, SlackChannelId, SlackUserId,
 };
 use std::env;
 
@@ -24,8 +24,21 @@ pub fn get_self_bot_id() -> anyhow::Result<SlackBotId> {
     Ok(SlackBotId(bot_id))
 }
 
+// Workspace admins given by the comma separated SLACK_ADMIN_IDS, none when it is unset
+pub fn get_admin_ids() -> Vec<SlackUserId> {
+    dotenv().ok();
+    env::var("SLACK_ADMIN_IDS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| SlackUserId::new(id.to_string()))
+        .collect()
+}
+
+// Accept a channel mention like `<#C12345|name>` or `<#C12345>`
 pub fn channel_preprocess(channel: &str) -> anyhow::Result<SlackChannelId> {
-    let channel_id_str = Regex::new(r"<#([^|]+)\|")
+    let channel_id_str = Regex::new(r"^<#([^|>]+)(\|[^>]*)?>$")
         .unwrap()
         .captures(channel)
         .and_then(|caps| caps.get(1).map(|s| s.as_str()))
@@ -33,6 +46,38 @@ pub fn channel_preprocess(channel: &str) -> anyhow::Result<SlackChannelId> {
     Ok(SlackChannelId(channel_id_str.to_string()))
 }
 
+// Accept a user mention like `<@U12345|name>` or a raw user or bot id
+pub fn sender_preprocess(sender: &str) -> anyhow::Result<String> {
+    let mention_id = Regex::new(r"^<@([A-Z0-9]+)(\|[^>]*)?>$")
+        .unwrap()
+        .captures(sender)
+        .and_then(|caps| caps.get(1).map(|s| s.as_str().to_string()));
+    let sender_id = match mention_id {
+        Some(id) => id,
+        None if Regex::new(r"^[UWB][A-Z0-9]+$").unwrap().is_match(sender) => sender.to_string(),
+        None => return Err(anyhow::anyhow!("validation error")),
+    };
+    Ok(sender_id)
+}
+
+// Accept a user mention like `<@U12345|name>`, a user group mention like `<!subteam^S12345|@group>`,
+// or a raw user or user group id
+pub fn member_preprocess(member: &str) -> anyhow::Result<String> {
+    let mention_id = Regex::new(r"^<(?:@|!subteam\^)([A-Z0-9]+)(\|[^>]*)?>$")
+        .unwrap()
+        .captures(member)
+        .and_then(|caps| caps.get(1).map(|s| s.as_str().to_string()));
+    let member_id = match mention_id {
+        Some(id) => id,
+        None if Regex::new(r"^[UWS][A-Z0-9]+$").unwrap().is_match(member) => member.to_string(),
+        None => return Err(anyhow::anyhow!("validation error")),
+    };
+    if !member_id.starts_with(['U', 'W', 'S']) {
+        return Err(anyhow::anyhow!("member should be a user or a user group"));
+    }
+    Ok(member_id)
+}
+
 pub fn channel_id_to_channel_name(channel_id: &SlackChannelId) -> String {
     let raw_channel_id = &channel_id.0;
     let channel_name = format!("<#{raw_channel_id}>");
@@ -48,6 +93,8 @@ mod tests {
         let slack_channel = "<#test_channel|https://xxxxxxxxxxxxxxxx>";
         let processed = channel_preprocess(slack_channel).unwrap();
         assert_eq!("test_channel", processed.0);
+        assert_eq!("C01", channel_preprocess("<#C01>").unwrap().0);
+        assert!(channel_preprocess("general").is_err());
     }
     #[tokio::test]
     async fn channel_id_to_channel_name_test() {
@@ -55,4 +102,22 @@ mod tests {
         let processed = channel_id_to_channel_name(&slack_channel);
         assert_eq!("<#C12345678>", processed);
     }
+    #[tokio::test]
+    async fn sender_preprocess_test() {
+        let mention = sender_preprocess("<@U12345678|someone>").unwrap();
+        let bot = sender_preprocess("B01234567").unwrap();
+        assert_eq!("U12345678", mention);
+        assert_eq!("B01234567", bot);
+        assert!(sender_preprocess("someone").is_err());
+    }
+    #[tokio::test]
+    async fn member_preprocess_test() {
+        let user = member_preprocess("<@U12345678|someone>").unwrap();
+        let group = member_preprocess("<!subteam^S12345678|@team>").unwrap();
+        assert_eq!("U12345678", user);
+        assert_eq!("S12345678", group);
+        assert_eq!("S12345678", member_preprocess("S12345678").unwrap());
+        assert!(member_preprocess("B01234567").is_err());
+        assert!(member_preprocess("<@B01234567>").is_err());
+    }
 }